@@ -0,0 +1,671 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::AppHandle;
+
+use crate::budget::{check_daily_cap, spend_on_day, BudgetPeriod, BudgetStatus};
+use crate::capability::notify;
+use crate::clock::{drop_future_samples, Clock, CLOCK};
+use crate::events::{emit, AlertFired, AlertKind, ALERT_FIRED};
+use crate::format::{format_cost, format_model_name, format_tokens};
+use crate::hooks::{fire_event_hook, HookEvent};
+use crate::model::{BlockData, DailyUsage};
+use crate::preview::sample_block;
+use crate::state::{cache_dir, record_write_result, AppSettings, CacheFile, SETTINGS_BACKUP};
+use crate::stats::{baseline_per_minute, detect_spend_spike, SPIKE_INTERVALS};
+use crate::tray::{tray_title_from_cache, update_tray_icon};
+
+/// How many recent alerts are kept
+const ALERT_LOG_LEN: usize = 20;
+
+/// Recent alerts, oldest first, and whether they've been seen by opening the menu
+struct AlertLog {
+    alerts: VecDeque<(chrono::DateTime<chrono::Local>, String)>,
+    acknowledged: bool,
+}
+
+static ALERT_LOG: Mutex<AlertLog> = Mutex::new(AlertLog { alerts: VecDeque::new(), acknowledged: true });
+
+/// How many block ids (or days, weeks, months) each once-only alert remembers
+const ALERTED_KEYS_LEN: usize = 20;
+
+/// Keys an alert has already fired for, oldest first. Short blocks roll over
+/// every hour or so, so only the last few are kept; still more than one, so
+/// a refresh that briefly reports an earlier block doesn't alert for it again.
+struct AlertedKeys {
+    keys: VecDeque<String>,
+}
+
+impl AlertedKeys {
+    const fn new() -> Self {
+        Self { keys: VecDeque::new() }
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        self.keys.iter().any(|known| known == key)
+    }
+
+    /// Remember `key`, returning false if it was already there
+    fn insert(&mut self, key: &str) -> bool {
+        if self.contains(key) {
+            return false;
+        }
+        if self.keys.len() == ALERTED_KEYS_LEN {
+            self.keys.pop_front();
+        }
+        self.keys.push_back(key.to_string());
+        true
+    }
+}
+
+/// Notify and log an alert, badging the tray icon until the menu is next
+/// opened and emitting `alert-fired` with `kind` and `value`. A preview is
+/// only notified, marked as such.
+fn raise_alert(app: &AppHandle, kind: AlertKind, value: Option<f64>, message: String, preview: bool) {
+    if preview {
+        notify(app, "CCUsage", format!("[preview] {}", message));
+        return;
+    }
+    emit(app, ALERT_FIRED, AlertFired { kind, value, message: message.clone() });
+    {
+        let mut log = ALERT_LOG.lock().unwrap();
+        if log.alerts.len() == ALERT_LOG_LEN {
+            log.alerts.pop_front();
+        }
+        log.alerts.push_back((CLOCK.now(), message.clone()));
+        log.acknowledged = false;
+    }
+    notify(app, "CCUsage", message);
+    update_tray_icon(app);
+}
+
+pub(crate) fn has_unacknowledged_alert() -> bool {
+    !ALERT_LOG.lock().unwrap().acknowledged
+}
+
+/// Mark every alert as seen, which clears the badge on the next redraw
+pub(crate) fn acknowledge_alerts() {
+    ALERT_LOG.lock().unwrap().acknowledged = true;
+}
+
+/// Recent alerts, newest first, as "HH:MM message" lines for Debug Info
+pub(crate) fn recent_alerts() -> Vec<String> {
+    let log = ALERT_LOG.lock().unwrap();
+    log.alerts.iter().rev().map(|(at, message)| format!("{} {}", at.format("%H:%M"), message)).collect()
+}
+
+/// Cost samples of the active block, one per refresh
+struct SpendTracker {
+    block_id: Option<String>,
+    samples: Vec<(chrono::DateTime<chrono::Local>, f64)>,
+    /// Blocks that have already triggered an alert
+    alerted: AlertedKeys,
+}
+
+static SPEND_TRACKER: Mutex<SpendTracker> = Mutex::new(SpendTracker {
+    block_id: None,
+    samples: Vec::new(),
+    alerted: AlertedKeys::new(),
+});
+
+/// Record the active block's cost and notify the first time in a block that
+/// spending is unusually fast compared to the past week
+pub(crate) fn check_spend_velocity(
+    app: &AppHandle,
+    block: Option<&BlockData>,
+    recent_days: &[DailyUsage],
+    now: chrono::DateTime<chrono::Local>,
+) {
+    let mut tracker = SPEND_TRACKER.lock().unwrap();
+    let Some(block) = block else {
+        tracker.block_id = None;
+        tracker.samples.clear();
+        return;
+    };
+    if tracker.block_id.as_deref() != Some(&block.id) {
+        tracker.block_id = Some(block.id.clone());
+        tracker.samples.clear();
+    }
+    drop_future_samples(&mut tracker.samples, now);
+    tracker.samples.push((now, block.cost_usd));
+    let excess = tracker.samples.len().saturating_sub(SPIKE_INTERVALS + 1);
+    tracker.samples.drain(..excess);
+    if tracker.alerted.contains(&block.id) || capped_today(now.date_naive()) {
+        return;
+    }
+
+    let history: Vec<(chrono::NaiveDate, f64)> = recent_days
+        .iter()
+        .filter_map(|day| Some((chrono::NaiveDate::parse_from_str(&day.date, "%Y-%m-%d").ok()?, day.total_cost)))
+        .collect();
+    let Some(spike) = detect_spend_spike(&tracker.samples, baseline_per_minute(&history, now.date_naive())) else {
+        return;
+    };
+    tracker.alerted.insert(&block.id);
+    fire_event_hook(HookEvent::SpendSpike, block, false);
+    let message = spend_spike_message(spike.amount, spike.minutes);
+    raise_alert(app, AlertKind::SpendSpike, Some(spike.amount), message, false);
+}
+
+fn spend_spike_message(amount: f64, minutes: f64) -> String {
+    format!("Unusually fast spending: {} in {} minutes", format_cost(amount), minutes.round())
+}
+
+fn output_cap_message(output_tokens: u64) -> String {
+    format!("High output volume: {} output tokens this session", format_tokens(output_tokens))
+}
+
+fn budget_message(status: &BudgetStatus) -> String {
+    format!(
+        "{} budget of {} exceeded: {} spent",
+        status.period.label(),
+        format_cost(status.budget),
+        format_cost(status.spent)
+    )
+}
+
+/// Whether the block has produced more output tokens than `output_token_cap`
+pub(crate) fn over_output_cap(block: &BlockData, settings: &AppSettings) -> bool {
+    settings.output_token_cap.is_some_and(|cap| block.token_counts.output_tokens > cap)
+}
+
+/// Blocks already alerted for going over the output token cap
+static OUTPUT_CAP_ALERTED: Mutex<AlertedKeys> = Mutex::new(AlertedKeys::new());
+
+/// Notify once per block when its output tokens pass the cap, including a
+/// block that was already over it the first time it was seen
+pub(crate) fn check_output_cap(app: &AppHandle, block: Option<&BlockData>, settings: &AppSettings) {
+    let capped = capped_today(CLOCK.now().date_naive());
+    let Some(output_tokens) = output_cap_crossing(&mut OUTPUT_CAP_ALERTED.lock().unwrap(), block, settings, capped)
+    else {
+        return;
+    };
+    raise_alert(app, AlertKind::OutputCap, Some(output_tokens as f64), output_cap_message(output_tokens), false);
+}
+
+/// The output tokens to alert about if `block` is over the cap and hasn't
+/// been alerted for yet. Passing the daily cap (`capped`) still marks the
+/// block, so it stays quiet after midnight too.
+fn output_cap_crossing(
+    alerted: &mut AlertedKeys,
+    block: Option<&BlockData>,
+    settings: &AppSettings,
+    capped: bool,
+) -> Option<u64> {
+    let block = block.filter(|block| over_output_cap(block, settings))?;
+    (alerted.insert(&block.id) && !capped).then_some(block.token_counts.output_tokens)
+}
+
+/// Settings backup the user has already been told about
+static NOTIFIED_SETTINGS_BACKUP: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Tell the user, once per backup, that a corrupt settings file was reset
+pub(crate) fn notify_settings_recovered(app: &AppHandle) {
+    let Some(backup) = SETTINGS_BACKUP.lock().unwrap().clone() else {
+        return;
+    };
+    {
+        let mut notified = NOTIFIED_SETTINGS_BACKUP.lock().unwrap();
+        if notified.as_ref() == Some(&backup) {
+            return;
+        }
+        *notified = Some(backup.clone());
+    }
+    notify(
+        app,
+        "CCUsage",
+        format!("The settings file was damaged and has been reset. A copy was saved to {}", backup.display()),
+    );
+}
+
+/// How long each phase of the over-budget flash lasts
+const FLASH_PHASE: Duration = Duration::from_millis(600);
+
+/// Recent blocks, days, weeks and months that went over each period's
+/// budget. Kept per period so hourly blocks can't push out today's breach.
+static BUDGET_BREACHED: Mutex<Vec<(BudgetPeriod, AlertedKeys)>> = Mutex::new(Vec::new());
+
+/// Note that `status` is over budget, returning false if it already was
+fn first_breach(status: &BudgetStatus) -> bool {
+    let mut breached = BUDGET_BREACHED.lock().unwrap();
+    if let Some((_, keys)) = breached.iter_mut().find(|(period, _)| *period == status.period) {
+        return keys.insert(&status.key);
+    }
+    let mut keys = AlertedKeys::new();
+    keys.insert(&status.key);
+    breached.push((status.period, keys));
+    true
+}
+
+/// The first time a block, day, week or month passes its budget, notify, run
+/// the budget hook for a block, and flash the tray title between "OVER BUDGET"
+/// and the cost if enabled. Block budgets stay quiet once the daily cap is passed.
+pub(crate) fn check_budget_breach(
+    app: &AppHandle,
+    statuses: &[BudgetStatus],
+    block: Option<&BlockData>,
+    settings: &AppSettings,
+) {
+    let capped = capped_today(CLOCK.now().date_naive());
+    let breaches: Vec<&BudgetStatus> = statuses
+        .iter()
+        .filter(|status| status.is_over() && first_breach(status))
+        .filter(|status| !(capped && status.period == BudgetPeriod::PerBlock))
+        .collect();
+    if breaches.is_empty() {
+        return;
+    }
+    for status in &breaches {
+        if status.period == BudgetPeriod::PerBlock {
+            if let Some(block) = block {
+                fire_event_hook(HookEvent::BudgetExceeded, block, false);
+            }
+        }
+        raise_alert(app, AlertKind::Budget, Some(status.spent), budget_message(status), false);
+    }
+    if !settings.flash_on_budget_breach {
+        return;
+    }
+
+    let Some(tray) = app.tray_by_id("main") else {
+        return;
+    };
+    let flashes = settings.budget_flash_count;
+    tauri::async_runtime::spawn(async move {
+        for _ in 0..flashes {
+            let _ = tray.set_title(Some("OVER BUDGET"));
+            tokio::time::sleep(FLASH_PHASE).await;
+            let _ = tray.set_title(Some(tray_title_from_cache()));
+            tokio::time::sleep(FLASH_PHASE).await;
+        }
+    });
+}
+
+/// The last day the daily cost cap was passed, persisted so restarting the
+/// same day neither notifies again nor brings the per-session alerts back
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct DailyCapLog {
+    fired_on: Option<chrono::NaiveDate>,
+}
+
+fn load_daily_cap_log() -> DailyCapLog {
+    CacheFile::DailyCap
+        .path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_daily_cap_log(log: &DailyCapLog) {
+    let Some(dir) = cache_dir() else {
+        return;
+    };
+    let Ok(json) = serde_json::to_string(log) else {
+        return;
+    };
+    let result =
+        std::fs::create_dir_all(&dir).and_then(|()| std::fs::write(dir.join(CacheFile::DailyCap.file_name()), json));
+    record_write_result(&dir, &result);
+    if let Err(e) = result {
+        eprintln!("Failed to save daily cap log: {}", e);
+    }
+}
+
+/// `DailyCapLog::fired_on`, read from disk the first time it's needed
+static DAILY_CAP_FIRED: Mutex<Option<Option<chrono::NaiveDate>>> = Mutex::new(None);
+
+fn daily_cap_fired_on() -> Option<chrono::NaiveDate> {
+    *DAILY_CAP_FIRED.lock().unwrap().get_or_insert_with(|| load_daily_cap_log().fired_on)
+}
+
+/// Whether the daily cap was passed today, which quiets the per-session
+/// alerts until midnight even if a recalculation brings the total back under
+fn capped_today(today: chrono::NaiveDate) -> bool {
+    capped_on(daily_cap_fired_on(), today)
+}
+
+fn capped_on(fired_on: Option<chrono::NaiveDate>, today: chrono::NaiveDate) -> bool {
+    fired_on == Some(today)
+}
+
+fn daily_cap_message(spent: f64, cap: f64) -> String {
+    format!(
+        "Daily cap of {} passed: {} spent today. Session alerts are paused until tomorrow.",
+        format_cost(cap),
+        format_cost(spent)
+    )
+}
+
+/// Notify the first time each day that today's spend passes `daily_cost_cap`.
+/// The tray marks the cap by itself for as long as the total is over it.
+pub(crate) fn check_daily_cost_cap(
+    app: &AppHandle,
+    today_blocks: &[BlockData],
+    settings: &AppSettings,
+    now: chrono::DateTime<chrono::Local>,
+) {
+    let today = now.date_naive();
+    let spent = spend_on_day(today_blocks, today, settings);
+    let check = check_daily_cap(spent, settings.daily_cost_cap, today, daily_cap_fired_on());
+    if !check.first_today {
+        return;
+    }
+    *DAILY_CAP_FIRED.lock().unwrap() = Some(Some(today));
+    save_daily_cap_log(&DailyCapLog { fired_on: Some(today) });
+    let cap = settings.daily_cost_cap.unwrap_or_default();
+    raise_alert(app, AlertKind::DailyCap, Some(spent), daily_cap_message(spent, cap), false);
+}
+
+pub(crate) fn model_added_message(model: &str, since: chrono::DateTime<chrono::Local>) -> String {
+    format!("{} started being used at {}", format_model_name(model), since.format("%I:%M %p"))
+}
+
+/// Notify about models that joined the active block since the last refresh, if enabled
+pub(crate) fn alert_new_models(
+    app: &AppHandle,
+    added: &[String],
+    now: chrono::DateTime<chrono::Local>,
+    settings: &AppSettings,
+) {
+    if !settings.model_change_alerts {
+        return;
+    }
+    for model in added {
+        raise_alert(app, AlertKind::ModelAdded, None, model_added_message(model, now), false);
+    }
+}
+
+/// One alert fired by a preview, and the hook it also runs, if any
+#[derive(Debug, Clone, PartialEq)]
+struct PreviewAlert {
+    kind: AlertKind,
+    value: Option<f64>,
+    message: String,
+    hook: Option<HookEvent>,
+}
+
+/// The alerts a preview fires for `settings`, one per configured alert type.
+/// Nothing here reads or updates the once-per-period state, so previews
+/// always fire and never stop a real alert from firing later.
+fn preview_plan(settings: &AppSettings, now: chrono::DateTime<chrono::Local>) -> Vec<PreviewAlert> {
+    let mut plan = Vec::new();
+    for period in BudgetPeriod::ALL {
+        let Some(budget) = period.amount(settings) else {
+            continue;
+        };
+        let status = BudgetStatus { period, key: "preview".to_string(), spent: budget * 1.2, budget };
+        plan.push(PreviewAlert {
+            kind: AlertKind::Budget,
+            value: Some(status.spent),
+            message: budget_message(&status),
+            hook: (period == BudgetPeriod::PerBlock).then_some(HookEvent::BudgetExceeded),
+        });
+    }
+    if settings.spend_spike_alerts {
+        plan.push(PreviewAlert {
+            kind: AlertKind::SpendSpike,
+            value: Some(4.2),
+            message: spend_spike_message(4.2, 10.0),
+            hook: Some(HookEvent::SpendSpike),
+        });
+    }
+    if let Some(cap) = settings.output_token_cap {
+        let output_tokens = cap + cap / 10;
+        plan.push(PreviewAlert {
+            kind: AlertKind::OutputCap,
+            value: Some(output_tokens as f64),
+            message: output_cap_message(output_tokens),
+            hook: None,
+        });
+    }
+    if let Some(cap) = settings.daily_cost_cap {
+        plan.push(PreviewAlert {
+            kind: AlertKind::DailyCap,
+            value: Some(cap * 1.1),
+            message: daily_cap_message(cap * 1.1, cap),
+            hook: None,
+        });
+    }
+    if settings.model_change_alerts {
+        plan.push(PreviewAlert {
+            kind: AlertKind::ModelAdded,
+            value: None,
+            message: model_added_message("claude-opus-4-20250514", now),
+            hook: None,
+        });
+    }
+    plan
+}
+
+/// Fire every configured alert once with sample data, so thresholds can be
+/// checked without waiting for them. Goes through the real notification and
+/// hook paths, marked "[preview]", but skips the once-per-period checks and
+/// leaves the alert history and icon dot alone.
+pub(crate) fn preview_alerts(app: &AppHandle, settings: &AppSettings) {
+    let now = CLOCK.now();
+    let plan = preview_plan(settings, now);
+    if plan.is_empty() {
+        let message = "No alerts are set up. Set a budget or output_token_cap, or turn on spend spike alerts.";
+        notify(app, "CCUsage", format!("[preview] {}", message));
+        return;
+    }
+    let block = sample_block(now, 90, 24.0, &["claude-opus-4-20250514"], true);
+    for alert in plan {
+        if let Some(event) = alert.hook {
+            fire_event_hook(event, &block, true);
+        }
+        raise_alert(app, alert.kind, alert.value, alert.message, true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::model::fixtures::{block, local};
+
+    #[test]
+    fn the_daily_cap_quiets_alerts_until_midnight() {
+        let clock = FakeClock::new(local(2025, 6, 11, 16, 0));
+        let fired_on = Some(clock.now().date_naive());
+        clock.set(local(2025, 6, 11, 23, 59));
+        assert!(capped_on(fired_on, clock.now().date_naive()));
+        clock.advance(chrono::Duration::minutes(2));
+        assert!(!capped_on(fired_on, clock.now().date_naive()));
+    }
+
+    #[test]
+    fn a_backward_jump_into_the_capped_day_quiets_alerts_again() {
+        let clock = FakeClock::new(local(2025, 6, 11, 16, 0));
+        let fired_on = Some(clock.now().date_naive());
+        clock.set(local(2025, 6, 12, 0, 30));
+        assert!(!capped_on(fired_on, clock.now().date_naive()));
+        clock.set(local(2025, 6, 11, 23, 50));
+        assert!(capped_on(fired_on, clock.now().date_naive()));
+    }
+
+    #[test]
+    fn a_backward_jump_before_the_capped_day_isnt_quiet() {
+        let clock = FakeClock::new(local(2025, 6, 11, 16, 0));
+        let fired_on = Some(clock.now().date_naive());
+        clock.set(local(2025, 6, 10, 23, 0));
+        assert!(!capped_on(fired_on, clock.now().date_naive()));
+    }
+
+    fn with_output(start_hour: u32, output_tokens: u64) -> BlockData {
+        let mut session = block(local(2025, 6, 11, start_hour, 0), local(2025, 6, 11, start_hour + 5, 0), 1.0);
+        session.token_counts.output_tokens = output_tokens;
+        session
+    }
+
+    fn capped_at(cap: u64) -> AppSettings {
+        AppSettings { output_token_cap: Some(cap), ..AppSettings::default() }
+    }
+
+    #[test]
+    fn output_cap_alerts_once_when_crossed() {
+        let mut alerted = AlertedKeys::new();
+        let settings = capped_at(100_000);
+        assert_eq!(output_cap_crossing(&mut alerted, Some(&with_output(9, 90_000)), &settings, false), None);
+        assert_eq!(output_cap_crossing(&mut alerted, Some(&with_output(9, 100_000)), &settings, false), None);
+        let crossed = output_cap_crossing(&mut alerted, Some(&with_output(9, 120_000)), &settings, false);
+        assert_eq!(crossed, Some(120_000));
+        assert_eq!(output_cap_crossing(&mut alerted, Some(&with_output(9, 150_000)), &settings, false), None);
+    }
+
+    #[test]
+    fn a_block_already_over_the_cap_alerts_at_first_sight() {
+        let mut alerted = AlertedKeys::new();
+        let session = with_output(9, 2_000_000);
+        assert_eq!(output_cap_crossing(&mut alerted, Some(&session), &capped_at(100_000), false), Some(2_000_000));
+    }
+
+    #[test]
+    fn a_new_block_can_alert_again() {
+        let mut alerted = AlertedKeys::new();
+        let settings = capped_at(100_000);
+        assert!(output_cap_crossing(&mut alerted, Some(&with_output(9, 200_000)), &settings, false).is_some());
+        assert_eq!(output_cap_crossing(&mut alerted, Some(&with_output(14, 50_000)), &settings, false), None);
+        assert!(output_cap_crossing(&mut alerted, Some(&with_output(14, 110_000)), &settings, false).is_some());
+    }
+
+    #[test]
+    fn output_cap_needs_a_cap_and_a_block() {
+        let mut alerted = AlertedKeys::new();
+        let settings = AppSettings::default();
+        assert_eq!(output_cap_crossing(&mut alerted, Some(&with_output(9, u64::MAX)), &settings, false), None);
+        assert_eq!(output_cap_crossing(&mut alerted, None, &capped_at(1), false), None);
+    }
+
+    #[test]
+    fn the_daily_cap_quiets_the_output_cap_for_the_rest_of_the_block() {
+        let mut alerted = AlertedKeys::new();
+        let settings = capped_at(100_000);
+        assert_eq!(output_cap_crossing(&mut alerted, Some(&with_output(18, 200_000)), &settings, true), None);
+        // Past midnight the daily cap no longer applies, but this block was already seen
+        assert_eq!(output_cap_crossing(&mut alerted, Some(&with_output(18, 210_000)), &settings, false), None);
+    }
+
+    fn everything_configured() -> AppSettings {
+        AppSettings {
+            block_budget: Some(10.0),
+            weekly_budget: Some(100.0),
+            daily_cost_cap: Some(50.0),
+            output_token_cap: Some(1_000_000),
+            spend_spike_alerts: true,
+            model_change_alerts: true,
+            ..AppSettings::default()
+        }
+    }
+
+    #[test]
+    fn previews_fire_each_configured_alert_once() {
+        let plan = preview_plan(&everything_configured(), local(2025, 6, 11, 14, 0));
+        let kinds: Vec<(AlertKind, Option<HookEvent>)> = plan.iter().map(|alert| (alert.kind, alert.hook)).collect();
+        assert_eq!(
+            kinds,
+            [
+                (AlertKind::Budget, Some(HookEvent::BudgetExceeded)),
+                (AlertKind::Budget, None),
+                (AlertKind::SpendSpike, Some(HookEvent::SpendSpike)),
+                (AlertKind::OutputCap, None),
+                (AlertKind::DailyCap, None),
+                (AlertKind::ModelAdded, None),
+            ]
+        );
+        assert_eq!(plan[0].message, "Block budget of $10.00 exceeded: $12.00 spent");
+        assert_eq!(plan[3].value, Some(1_100_000.0));
+        assert_eq!(plan[5].message, "Opus 4 started being used at 02:00 PM");
+    }
+
+    #[test]
+    fn previews_are_empty_without_alerts_set_up() {
+        let settings = AppSettings { spend_spike_alerts: false, ..AppSettings::default() };
+        assert!(preview_plan(&settings, local(2025, 6, 11, 14, 0)).is_empty());
+    }
+
+    #[test]
+    fn previews_bypass_dedup() {
+        let settings = everything_configured();
+        let now = local(2025, 6, 11, 14, 0);
+        // The sample block's output cap alert has already fired for real
+        let sample = sample_block(now, 90, 24.0, &["claude-opus-4-20250514"], true);
+        let mut alerted = AlertedKeys::new();
+        alerted.insert(&sample.id);
+
+        let first = preview_plan(&settings, now);
+        assert!(first.iter().any(|alert| alert.kind == AlertKind::OutputCap));
+        assert_eq!(preview_plan(&settings, now), first);
+        // and previewing leaves the real once-per-block state as it was
+        assert!(alerted.contains(&sample.id));
+        let mut over = with_output(9, 2_000_000);
+        over.id = "after-preview".to_string();
+        assert!(output_cap_crossing(&mut alerted, Some(&over), &settings, false).is_some());
+    }
+
+    #[test]
+    fn alerted_keys_forget_the_oldest_past_twenty() {
+        let mut alerted = AlertedKeys::new();
+        for n in 0..25 {
+            assert!(alerted.insert(&format!("block-{}", n)));
+        }
+        assert_eq!(alerted.keys.len(), ALERTED_KEYS_LEN);
+        assert!((0..5).all(|n| !alerted.contains(&format!("block-{}", n))));
+        assert!((5..25).all(|n| alerted.contains(&format!("block-{}", n))));
+        // Still remembered, so not alerted again; forgotten, so alerted again
+        assert!(!alerted.insert("block-10"));
+        assert!(alerted.insert("block-0"));
+        assert_eq!(alerted.keys.len(), ALERTED_KEYS_LEN);
+    }
+
+    /// Refresh every 15 minutes through twelve one-hour blocks that each go
+    /// over the output cap, with the daily cap reached from block
+    /// `capped_from` on. Returns how many output cap alerts fired.
+    fn simulate_hourly_blocks(capped_from: usize) -> usize {
+        let clock = FakeClock::new(local(2025, 6, 11, 8, 0));
+        let settings = capped_at(100_000);
+        let mut alerted = AlertedKeys::new();
+        let mut blocks: Vec<BlockData> = Vec::new();
+        let mut fired = 0;
+        for hour in 0..12 {
+            if let Some(last) = blocks.last_mut() {
+                last.is_active = false;
+            }
+            let start = clock.now();
+            let mut current = block(start, start + chrono::Duration::hours(1), 1.0);
+            current.id = format!("block-{}", hour);
+            current.is_active = true;
+            current.token_counts.output_tokens = 150_000;
+            blocks.push(current);
+
+            for _ in 0..4 {
+                let now = clock.now();
+                let capped = hour >= capped_from;
+                fired += usize::from(output_cap_crossing(&mut alerted, blocks.last(), &settings, capped).is_some());
+                // A refresh that briefly reports the block before doesn't alert for it again
+                if let Some(earlier) = hour.checked_sub(1).map(|earlier| &blocks[earlier]) {
+                    assert_eq!(output_cap_crossing(&mut alerted, Some(earlier), &settings, false), None);
+                    let previous = crate::stats::previous_block(&blocks, now).unwrap();
+                    assert_eq!(previous.id, earlier.id, "at {}", now);
+                }
+                assert!(alerted.keys.len() <= ALERTED_KEYS_LEN);
+                clock.advance(chrono::Duration::minutes(15));
+            }
+        }
+        fired
+    }
+
+    #[test]
+    fn hourly_blocks_alert_once_each() {
+        assert_eq!(simulate_hourly_blocks(usize::MAX), 12);
+    }
+
+    #[test]
+    fn hourly_blocks_stay_quiet_once_the_daily_cap_is_reached() {
+        assert_eq!(simulate_hourly_blocks(6), 6);
+        assert_eq!(simulate_hourly_blocks(0), 0);
+    }
+}
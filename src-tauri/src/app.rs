@@ -0,0 +1,796 @@
+use std::sync::{atomic::Ordering, Arc, Mutex};
+use tauri::menu::MenuEvent;
+use tauri::{AppHandle, Manager, RunEvent};
+use tauri_plugin_dialog::{MessageDialogButtons, MessageDialogKind};
+
+use crate::alerts::{
+    alert_new_models, check_budget_breach, check_daily_cost_cap, check_output_cap, check_spend_velocity,
+    notify_settings_recovered, preview_alerts,
+};
+use crate::budget::budget_statuses;
+use crate::capability::{copy_text, dialogs, notify};
+use crate::cleanup::AppDataRegistry;
+use crate::cli::run_from_args;
+use crate::clock::{wall_elapsed, Clock, CLOCK};
+use crate::conversations::refresh_conversations;
+use crate::datadirs::usage_data_dirs;
+use crate::debug::get_debug_info;
+#[cfg(target_os = "macos")]
+use crate::dock::native_policy;
+use crate::dock::{apply_activation_policy, show_main_window, ActivationPolicy};
+use crate::events::{
+    emit, emit_settings_changed, AvailabilityChanged, EventSink, RefreshFinished, RefreshStarted, SessionUpdated,
+    AVAILABILITY_CHANGED, REFRESH_FINISHED, REFRESH_STARTED, SESSION_UPDATED,
+};
+use crate::fetch::{fetch_recent_usage, run_shell_with_input, CommandRunner, RUNNER};
+use crate::lifetime::{lifetime_total, refresh_lifetime_totals, reset_lifetime_totals};
+use crate::menu::{menu_link, MenuIdKind, UPDATING_TICK};
+use crate::model::{parse_local_time, BlockData};
+use crate::modelwatch::record_block_models;
+use crate::onboarding::{needs_onboarding, run_onboarding};
+use crate::presence::is_claude_running;
+use crate::preview::{PreviewScenario, PREVIEW};
+use crate::report::send_daily_report_if_due;
+use crate::schedule::{
+    is_on_battery, next_refresh_delay, power_adjusted_delay, RefreshCoalescer, TriggerDecision, POWER_CHECK_INTERVAL,
+};
+use crate::shortcut::register_copy_cost_shortcut;
+use crate::state::{
+    check_settings_file, current_settings, save_current_settings, save_session_snapshot, settings_path, update_settings,
+    AppSettings, Availability, RefreshTrigger, SessionData, IS_REFRESHING, LAST_HOOK_RESULT, NEXT_REFRESH, ON_BATTERY,
+    REFRESH_TIMINGS, SESSION_CACHE,
+};
+use crate::stats::total_cost;
+use crate::status::{remove_status_file, write_status_file, StatusFile};
+use crate::timeline::record_cost_sample;
+use crate::tray::{
+    create_tray, menu_item_payload, rendered_title_from_cache, update_menu_text, update_tray, update_tray_icon,
+    update_tray_throttled,
+};
+use crate::watchdog::{clear_refresh_stall, run_watchdog_check, WATCHDOG_INTERVAL};
+
+/// How often the hourglass icon is redrawn
+const ICON_TICK: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How far the wall clock may run past a refresh delay before the gap is put down to sleep
+const WAKE_SLACK: std::time::Duration = std::time::Duration::from_secs(60);
+
+pub(crate) fn run() {
+    if let Some(code) = run_from_args() {
+        std::process::exit(code);
+    }
+
+    tauri::Builder::default()
+        // Registered first so a second launch hands over before setting anything up
+        .plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
+            on_second_launch(app);
+        }))
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_global_shortcut::Builder::new().build())
+        .invoke_handler(tauri::generate_handler![crate::heatmap::get_heatmap])
+        .setup(|app| {
+            let policy = current_settings().activation_policy;
+            #[cfg(target_os = "macos")]
+            app.set_activation_policy(native_policy(policy));
+            if policy == ActivationPolicy::Regular {
+                show_main_window(app.handle());
+            }
+
+            let shortcut = current_settings().copy_cost_shortcut.filter(|keys| !keys.trim().is_empty());
+            if let Some(keys) = shortcut {
+                if !register_copy_cost_shortcut(app.handle(), keys.trim(), copy_session_cost) {
+                    notify(
+                        app.handle(),
+                        "Shortcut unavailable",
+                        format!("{} couldn't be registered. It may be in use by another app.", keys.trim()),
+                    );
+                }
+            }
+
+            let app_handle = app.handle().clone();
+            let first_run = needs_onboarding();
+
+            // Start periodic refresh task
+            let periodic_handle = app_handle.clone();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    let settings = current_settings();
+                    let block_end = SESSION_CACHE.lock().unwrap().active_block.as_ref()
+                        .and_then(|block| parse_local_time(&block.end_time));
+                    let delay = next_refresh_delay(block_end, CLOCK.now(), &settings);
+
+                    // Slow down or pause while unplugged, if enabled
+                    let on_battery = settings.pause_on_battery && is_on_battery().await;
+                    if ON_BATTERY.swap(on_battery, Ordering::Relaxed) != on_battery {
+                        update_tray(&periodic_handle);
+                    }
+                    let Some(delay) = power_adjusted_delay(delay, on_battery, &settings) else {
+                        *NEXT_REFRESH.lock().unwrap() = None;
+                        tokio::time::sleep(POWER_CHECK_INTERVAL).await;
+                        continue;
+                    };
+                    let before_sleep = CLOCK.now();
+                    *NEXT_REFRESH.lock().unwrap() =
+                        chrono::Duration::from_std(delay).ok().map(|delay| before_sleep + delay);
+                    tokio::time::sleep(delay).await;
+                    // The sleep timer doesn't advance while the machine sleeps,
+                    // so a wall-clock gap well past the delay means it just woke
+                    let slept = wall_elapsed(before_sleep, CLOCK.now());
+                    let trigger = if slept > delay + WAKE_SLACK {
+                        RefreshTrigger::Wake
+                    } else {
+                        RefreshTrigger::Periodic
+                    };
+                    // Only refresh if not already refreshing and we have initial data.
+                    // In fetch-on-open mode the loop keeps running so switching back resumes it.
+                    if !IS_REFRESHING.load(Ordering::Relaxed) && !current_settings().fetch_on_open_only {
+                        let should_refresh = {
+                            let cache = SESSION_CACHE.lock().unwrap();
+                            // Only auto-refresh if we've refreshed at least once, and stop
+                            // retrying once the system has refused to run processes
+                            // (manual refresh still tries again)
+                            cache.last_updated.is_some() && cache.availability != Availability::Blocked
+                        };
+                        if should_refresh {
+                            request_refresh(&periodic_handle, trigger).await;
+                        }
+                    }
+                }
+            });
+
+            // Runs apart from the refresh loop so it notices if that loop stops
+            let watchdog_handle = app_handle.clone();
+            let launched = CLOCK.instant();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    tokio::time::sleep(WATCHDOG_INTERVAL).await;
+                    run_watchdog_check(&watchdog_handle, launched);
+                }
+            });
+
+            // Drain the hourglass icon between refreshes
+            let icon_handle = app_handle.clone();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    tokio::time::sleep(ICON_TICK).await;
+                    if current_settings().hourglass_icon {
+                        update_tray_icon(&icon_handle);
+                    }
+                }
+            });
+
+            tauri::async_runtime::spawn(async move {
+                // Initial data refresh on app startup
+                request_refresh(&app_handle, RefreshTrigger::Startup).await;
+
+                match create_tray(&app_handle, handle_menu_event, handle_menu_open) {
+                    // Store tray reference in app state
+                    Ok(tray) => {
+                        app_handle.manage(Arc::new(tray));
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to build initial menu: {}", e);
+                    }
+                }
+
+                if first_run {
+                    let availability = SESSION_CACHE.lock().unwrap().availability;
+                    let refresh_handle = app_handle.clone();
+                    run_onboarding(&app_handle, availability, move || {
+                        tauri::async_runtime::spawn(async move {
+                            request_refresh(&refresh_handle, RefreshTrigger::Manual).await;
+                        });
+                    });
+                }
+            });
+
+            Ok(())
+        })
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app, event| match event {
+            // Closing the main window shouldn't quit a menubar app; only Quit (an explicit exit code) does
+            RunEvent::ExitRequested { code: None, api, .. } => api.prevent_exit(),
+            // Clicking the dock icon brings the window back
+            #[cfg(target_os = "macos")]
+            RunEvent::Reopen { .. } if current_settings().activation_policy == ActivationPolicy::Regular => {
+                show_main_window(_app);
+            }
+            _ => {}
+        });
+}
+
+fn handle_menu_event(app: &AppHandle, event: MenuEvent) {
+    let payload = menu_item_payload(event.id().as_ref());
+    let payload = payload.as_ref().map(|(kind, value)| (*kind, value.as_str()));
+    if let Some(url) = menu_link(event.id().as_ref(), payload) {
+        open_link(app, url);
+        return;
+    }
+    if let Some((MenuIdKind::Conversation, id)) = payload {
+        copy_text(app, id.to_string());
+        return;
+    }
+    match event.id().as_ref() {
+        "quit" => {
+            remove_status_file(&current_settings());
+            app.exit(0);
+        }
+        "lifetime_total" => {
+            let today_blocks = SESSION_CACHE.lock().unwrap().today_blocks.clone();
+            if let Some(lifetime) = lifetime_total(&today_blocks) {
+                copy_text(app, format!("{:.2}", lifetime.cost));
+            }
+        }
+        "preview_alerts" => preview_alerts(app, &current_settings()),
+        "remove_app_data" => confirm_remove_app_data(app),
+        "recalculate_lifetime" => {
+            reset_lifetime_totals();
+            let app_handle = app.clone();
+            tauri::async_runtime::spawn(async move {
+                request_refresh(&app_handle, RefreshTrigger::Manual).await;
+            });
+        }
+        "compact_title" => {
+            change_setting(app, |s| s.compact_title = !s.compact_title);
+        }
+        "focus_mode" => {
+            change_setting(app, |s| s.focus_mode = !s.focus_mode);
+        }
+        "fetch_on_open_only" => {
+            change_setting(app, |s| s.fetch_on_open_only = !s.fetch_on_open_only);
+        }
+        "show_previous_session" => {
+            change_setting(app, |s| s.show_previous_session = !s.show_previous_session);
+        }
+        "show_in_dock" => {
+            change_setting(app, |s| {
+                s.activation_policy = match s.activation_policy {
+                    ActivationPolicy::Accessory => ActivationPolicy::Regular,
+                    ActivationPolicy::Regular => ActivationPolicy::Accessory,
+                };
+            });
+            apply_activation_policy(app, current_settings().activation_policy);
+        }
+        "separate_tokens_tray" => {
+            change_setting(app, |s| s.separate_tokens_tray = !s.separate_tokens_tray);
+        }
+        "hide_tokens_tray" => {
+            change_setting(app, |s| s.separate_tokens_tray = false);
+        }
+        "hourglass_icon" => {
+            change_setting(app, |s| s.hourglass_icon = !s.hourglass_icon);
+        }
+        "include_active_in_totals" => {
+            change_setting(app, |s| s.include_active_in_totals = !s.include_active_in_totals);
+        }
+        "count_window_only" => {
+            change_setting(app, |s| s.count_window_only = !s.count_window_only);
+        }
+        "show_cost_without_cache" => {
+            change_setting(app, |s| s.show_cost_without_cache = !s.show_cost_without_cache);
+        }
+        "pause_on_battery" => {
+            change_setting(app, |s| s.pause_on_battery = !s.pause_on_battery);
+        }
+        "detect_claude_running" => {
+            change_setting(app, |s| s.detect_claude_running = !s.detect_claude_running);
+        }
+        "flash_on_budget_breach" => {
+            change_setting(app, |s| s.flash_on_budget_breach = !s.flash_on_budget_breach);
+        }
+        "redact_project_names" => {
+            change_setting(app, |s| s.redact_project_names = !s.redact_project_names);
+        }
+        "model_change_alerts" => {
+            change_setting(app, |s| s.model_change_alerts = !s.model_change_alerts);
+        }
+        "auto_copy_cost" => {
+            change_setting(app, |s| s.auto_copy_cost = !s.auto_copy_cost);
+        }
+        "write_status_file" => {
+            change_setting(app, |s| s.write_status_file = !s.write_status_file);
+            let settings = current_settings();
+            if settings.write_status_file {
+                write_status_from_cache();
+            } else {
+                remove_status_file(&settings);
+            }
+        }
+        "spend_spike_alerts" => {
+            change_setting(app, |s| s.spend_spike_alerts = !s.spend_spike_alerts);
+        }
+        "preview_live" => {
+            *PREVIEW.lock().unwrap() = None;
+            update_tray(app);
+        }
+        id if PreviewScenario::from_id(id).is_some() => {
+            *PREVIEW.lock().unwrap() = PreviewScenario::from_id(id);
+            update_tray(app);
+        }
+        "confirm_links" => {
+            change_setting(app, |s| s.confirm_links = !s.confirm_links);
+        }
+        "open_settings" => open_settings_file(),
+        "show_data_dir" => {
+            // Reveal where ccusage looked, or if none of it exists, the setting that picks the directory
+            match usage_data_dirs(&current_settings()).into_iter().find(|dir| dir.exists()) {
+                Some(dir) => {
+                    let _ = tauri_plugin_opener::reveal_item_in_dir(dir);
+                }
+                None => open_settings_file(),
+            }
+        }
+        "refresh" => {
+            let app_handle = app.clone();
+            tauri::async_runtime::spawn(async move {
+                // Refresh all data (also rebuilds the menu), unless a refresh just ran
+                request_refresh(&app_handle, RefreshTrigger::Manual).await;
+            });
+        }
+        "debug" => {
+            let app_handle = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let debug_info = get_debug_info().await;
+                println!("=== DEBUG INFO ===\n{}\n==================", debug_info);
+
+                // Also show in a native dialog
+                if let Some(dialogs) = dialogs(&app_handle) {
+                    dialogs
+                        .message(debug_info)
+                        .title("CCUsage Debug Info")
+                        .kind(MessageDialogKind::Info)
+                        .show(|_| {});
+                }
+            });
+        }
+        _ => {}
+    }
+}
+
+/// How recent data must be for opening the menu not to refetch in fetch-on-open mode
+const OPEN_FETCH_TTL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// In fetch-on-open mode, refresh as the menu opens unless the data is recent.
+/// The open menu updates in place when the fetch finishes.
+fn handle_menu_open(app: &AppHandle) {
+    if !current_settings().fetch_on_open_only {
+        return;
+    }
+    let last_updated = SESSION_CACHE.lock().unwrap().last_updated;
+    if last_updated.is_some_and(|updated| CLOCK.instant().saturating_duration_since(updated) < OPEN_FETCH_TTL) {
+        return;
+    }
+    let app_handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        request_refresh(&app_handle, RefreshTrigger::MenuOpen).await;
+    });
+}
+
+/// Another copy of the app was started and exited in favour of this one.
+/// Point the user at the running copy instead of adding a second menubar item.
+fn on_second_launch(app: &AppHandle) {
+    if current_settings().activation_policy == ActivationPolicy::Regular {
+        show_main_window(app);
+    } else {
+        notify(app, "CCUsage", "CCUsage is already running. Click its menubar icon to see usage.");
+    }
+}
+
+static REFRESH_COALESCER: Mutex<RefreshCoalescer> = Mutex::new(RefreshCoalescer::new());
+
+/// Refresh unless one just ran or is running. A trigger during a refresh is
+/// remembered, and a single follow-up refresh runs once it's done.
+async fn request_refresh(app_handle: &AppHandle, trigger: RefreshTrigger) {
+    if REFRESH_COALESCER.lock().unwrap().request(trigger, CLOCK.instant()) != TriggerDecision::Start {
+        return;
+    }
+    let mut trigger = trigger;
+    loop {
+        refresh_session_data(app_handle, trigger).await;
+        match REFRESH_COALESCER.lock().unwrap().finished(CLOCK.instant()) {
+            Some(next) => trigger = next,
+            None => break,
+        }
+    }
+}
+
+async fn refresh_session_data(app_handle: &AppHandle, trigger: RefreshTrigger) {
+    // Set refresh flag
+    IS_REFRESHING.store(true, Ordering::Relaxed);
+
+    // Tick the "Updating" row until the refresh is done; the other rows keep
+    // showing the previous data
+    let ticker_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        while IS_REFRESHING.load(Ordering::Relaxed) {
+            update_menu_text(&ticker_handle);
+            tokio::time::sleep(UPDATING_TICK).await;
+        }
+    });
+
+    check_settings_file();
+    notify_settings_recovered(app_handle);
+
+    let settings = current_settings();
+    let (availability, took) = fetch_and_store(app_handle, &RUNNER, &CLOCK, &settings, &SESSION_CACHE, trigger).await;
+    REFRESH_TIMINGS.lock().unwrap().refreshes.push(took);
+    write_status_from_cache();
+    if availability == Availability::Available {
+        let (session, recent_days) = {
+            let cache = SESSION_CACHE.lock().unwrap();
+            (cache.active_block.clone(), cache.recent_days.clone())
+        };
+        save_session_snapshot(&session, rendered_title_from_cache());
+        record_cost_sample(session.as_ref(), CLOCK.now());
+        let added_models = record_block_models(session.as_ref(), CLOCK.now());
+        alert_new_models(app_handle, &added_models, CLOCK.now(), &current_settings());
+        copy_cost_if_changed(app_handle, session.as_ref());
+        let today_blocks = SESSION_CACHE.lock().unwrap().today_blocks.clone();
+        // Before the per-session alerts, which the cap quiets for the rest of the day
+        check_daily_cost_cap(app_handle, &today_blocks, &current_settings(), CLOCK.now());
+        send_daily_report_if_due(app_handle, &today_blocks, CLOCK.now()).await;
+        if current_settings().spend_spike_alerts {
+            check_spend_velocity(app_handle, session.as_ref(), &recent_days, CLOCK.now());
+        }
+    }
+
+    // Opt-in user hook, run in the background so it can't hold up the menu
+    if availability == Availability::Available {
+        if let Some(command) = current_settings().post_refresh_command.filter(|c| !c.trim().is_empty()) {
+            let session = SESSION_CACHE.lock().unwrap().active_block.clone();
+            tauri::async_runtime::spawn(run_post_refresh_command(command, session));
+        }
+    }
+
+    // Clear refresh flag before the final render so the Refresh row comes back
+    IS_REFRESHING.store(false, Ordering::Relaxed);
+    update_tray_throttled(app_handle);
+
+    // Flash over the freshly set title
+    if availability == Availability::Available {
+        let settings = current_settings();
+        let (session, counted_days) = {
+            let cache = SESSION_CACHE.lock().unwrap();
+            let days = if settings.count_window_only { &cache.window_days } else { &cache.recent_days };
+            (cache.active_block.clone(), days.clone())
+        };
+        let budgets = budget_statuses(&settings, session.as_ref(), &counted_days, CLOCK.now().date_naive());
+        check_budget_breach(app_handle, &budgets, session.as_ref(), &settings);
+        check_output_cap(app_handle, session.as_ref(), &settings);
+    }
+    emit_refresh_finished(app_handle, trigger, availability, took);
+}
+
+/// The part of a refresh that needs no tray or window: run ccusage, update
+/// `cache`, and send `refresh-started`, `availability-changed` and
+/// `session-updated`. Returns what ccusage's availability was and how long it took.
+async fn fetch_and_store(
+    events: &dyn EventSink,
+    runner: &dyn CommandRunner,
+    clock: &dyn Clock,
+    settings: &AppSettings,
+    cache: &Mutex<SessionData>,
+    trigger: RefreshTrigger,
+) -> (Availability, std::time::Duration) {
+    let started = clock.instant();
+    emit(events, REFRESH_STARTED, RefreshStarted { trigger: trigger.label() });
+
+    // One ccusage run covers the active session, today's blocks and the daily
+    // totals; the lifetime total catches up from it (a no-op unless the day has changed)
+    let (usage, availability) = match fetch_recent_usage(runner, clock).await {
+        Ok(usage) => (Some(usage), Availability::Available),
+        Err(e) => (None, e.into()),
+    };
+    refresh_lifetime_totals(runner, clock, usage.as_ref()).await;
+    // A second run, only when today's spend has moved since the last one
+    if let Some(usage) = &usage {
+        refresh_conversations(runner, clock, total_cost(&usage.today_blocks, true)).await;
+    }
+    let active_block = usage.as_ref().and_then(|usage| usage.active_block.clone());
+
+    // Without a session, tell "Claude Code is closed" apart from an idle session
+    let claude_running = if active_block.is_none() && settings.detect_claude_running {
+        is_claude_running(runner).await
+    } else {
+        None
+    };
+
+    // Update cache
+    let previous_availability = {
+        let mut cache = cache.lock().unwrap();
+        let now = clock.instant();
+        cache.active_block = active_block;
+        // Keep the previous totals when the fetch failed
+        if let Some(usage) = usage {
+            cache.today_blocks = usage.today_blocks;
+            cache.recent_days = usage.recent_days;
+            cache.window_days = usage.window_days;
+            cache.recent_days_updated = Some(now);
+            cache.last_activity = usage.last_activity;
+            cache.previous_block = usage.previous_block;
+            cache.model_exclusion = usage.model_exclusion;
+        }
+        if availability == Availability::Available {
+            cache.last_success = Some(now);
+            clear_refresh_stall();
+        }
+        cache.last_updated = Some(now);
+        cache.last_trigger = Some(trigger);
+        let previous_availability = std::mem::replace(&mut cache.availability, availability);
+        cache.claude_running = claude_running;
+        previous_availability
+    };
+    if previous_availability != availability {
+        emit(events, AVAILABILITY_CHANGED, AvailabilityChanged { from: previous_availability, to: availability });
+    }
+    if availability == Availability::Available {
+        let session = cache.lock().unwrap().active_block.clone();
+        emit(events, SESSION_UPDATED, SessionUpdated { session });
+    }
+    (availability, clock.instant() - started)
+}
+
+fn emit_refresh_finished(
+    events: &dyn EventSink,
+    trigger: RefreshTrigger,
+    availability: Availability,
+    took: std::time::Duration,
+) {
+    let finished = RefreshFinished {
+        trigger: trigger.label(),
+        duration_ms: took.as_millis() as u64,
+        success: availability == Availability::Available,
+    };
+    emit(events, REFRESH_FINISHED, finished);
+}
+
+/// Write the status file from the cached session, if enabled
+fn write_status_from_cache() {
+    let (block, availability) = {
+        let cache = SESSION_CACHE.lock().unwrap();
+        (cache.active_block.clone(), cache.availability)
+    };
+    let status = StatusFile::new(block.as_ref(), availability, CLOCK.now().timestamp(), rendered_title_from_cache());
+    write_status_file(&current_settings(), &status);
+}
+
+/// Cost last written to the clipboard by `auto_copy_cost`
+static LAST_COPIED_COST: Mutex<Option<String>> = Mutex::new(None);
+
+/// The session cost as copied to the clipboard, e.g. "12.34"
+fn clipboard_cost(block: &BlockData) -> String {
+    format!("{:.2}", block.cost_usd)
+}
+
+/// Copy the session cost to the clipboard if enabled and it changed since the
+/// last copy, so an unchanged cost doesn't overwrite whatever the user copied since
+fn copy_cost_if_changed(app_handle: &AppHandle, block: Option<&BlockData>) {
+    let Some(block) = block.filter(|_| current_settings().auto_copy_cost) else {
+        return;
+    };
+    let cost = clipboard_cost(block);
+    let mut last = LAST_COPIED_COST.lock().unwrap();
+    if last.as_deref() == Some(&cost) {
+        return;
+    }
+    if copy_text(app_handle, cost.clone()) {
+        *last = Some(cost);
+    }
+}
+
+/// Copy the cached session cost, from the global shortcut. With no active
+/// session there's nothing to copy, so the clipboard is left alone.
+fn copy_session_cost(app: &AppHandle) {
+    let Some(cost) = SESSION_CACHE.lock().unwrap().active_block.as_ref().map(clipboard_cost) else {
+        return;
+    };
+    if copy_text(app, cost.clone()) {
+        *LAST_COPIED_COST.lock().unwrap() = Some(cost);
+    }
+}
+
+/// How long a post-refresh command may run before it's killed
+const POST_REFRESH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Run the user's post-refresh command, feeding it the active session as JSON
+async fn run_post_refresh_command(command: String, session: Option<BlockData>) {
+    let payload = serde_json::to_string(&session).unwrap_or_else(|_| "null".to_string());
+    let summary = match run_shell_with_input(&command, &payload, POST_REFRESH_TIMEOUT).await {
+        Ok(()) => format!("{}: ok", command),
+        Err(e) => {
+            eprintln!("Post-refresh command failed: {}", e);
+            format!("{}: {}", command, e)
+        }
+    };
+    *LAST_HOOK_RESULT.lock().unwrap() = Some(summary);
+}
+
+/// Open a URL in the browser, first asking for confirmation if enabled
+fn open_link(app: &AppHandle, url: &'static str) {
+    if !current_settings().confirm_links {
+        let _ = tauri_plugin_opener::open_url(url, None::<String>);
+        return;
+    }
+    // Without dialogs the link can't be confirmed, so it stays closed; the menu says why
+    let Some(dialogs) = dialogs(app) else {
+        return;
+    };
+    dialogs
+        .message(format!("Open this link in your browser?\n\n{}", url))
+        .title("Open Link")
+        .kind(MessageDialogKind::Info)
+        .buttons(MessageDialogButtons::OkCancel)
+        .show(move |confirmed| {
+            if confirmed {
+                let _ = tauri_plugin_opener::open_url(url, None::<String>);
+            }
+        });
+}
+
+/// List the files the app has written, and after confirmation delete them and quit.
+/// Quitting straight away keeps a refresh from writing them again.
+fn confirm_remove_app_data(app: &AppHandle) {
+    let registry = AppDataRegistry::current(&current_settings());
+    let paths = registry.existing_paths();
+    let Some(dialogs) = dialogs(app) else {
+        return;
+    };
+    if paths.is_empty() {
+        dialogs
+            .message("The app hasn't written any files.")
+            .title("Remove All App Data")
+            .kind(MessageDialogKind::Info)
+            .show(|_| {});
+        return;
+    }
+    let listed: Vec<String> = paths.iter().map(|path| path.display().to_string()).collect();
+    let app_handle = app.clone();
+    dialogs
+        .message(format!(
+            "These files will be deleted and the app will quit:\n\n{}\n\n\
+             ccusage and Claude's own logs are not touched.",
+            listed.join("\n")
+        ))
+        .title("Remove All App Data")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom("Remove and Quit".to_string(), "Cancel".to_string()))
+        .show(move |confirmed| {
+            if !confirmed {
+                return;
+            }
+            let failures = registry.remove(&paths);
+            if failures.is_empty() {
+                app_handle.exit(0);
+                return;
+            }
+            let failed: Vec<String> = failures.iter().map(|(path, e)| format!("{}: {}", path.display(), e)).collect();
+            eprintln!("Failed to remove app data:\n{}", failed.join("\n"));
+            let Some(dialogs) = dialogs(&app_handle) else {
+                app_handle.exit(0);
+                return;
+            };
+            let quit_handle = app_handle.clone();
+            dialogs
+                .message(format!("Some files couldn't be deleted:\n\n{}", failed.join("\n")))
+                .title("Remove All App Data")
+                .kind(MessageDialogKind::Error)
+                .show(move |_| quit_handle.exit(0));
+        });
+}
+
+/// Open the settings file in the default editor, writing it out first if it doesn't exist yet
+fn open_settings_file() {
+    if let Some(path) = settings_path() {
+        if !path.exists() {
+            save_current_settings();
+        }
+        let _ = tauri_plugin_opener::open_path(path.to_string_lossy().to_string(), None::<String>);
+    }
+}
+
+/// Persist a settings change and re-render the tray from cached data
+fn change_setting(app: &AppHandle, f: impl FnOnce(&mut AppSettings)) {
+    let changed = update_settings(f);
+    emit_settings_changed(app, changed);
+    update_tray(app);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::events::RecordedEvents;
+    use crate::fetch::fake::{success, FakeRunner};
+    use crate::model::fixtures::{block, local};
+    use serde_json::json;
+
+    /// Refreshes without the tray, into a cache of their own
+    struct Refresher {
+        events: RecordedEvents,
+        clock: FakeClock,
+        settings: AppSettings,
+        cache: Mutex<SessionData>,
+    }
+
+    impl Refresher {
+        /// One refresh with each of `results` in turn as the output of ccusage,
+        /// or of the process list; returns the programs it ran
+        fn refresh(&self, results: Vec<std::io::Result<std::process::Output>>) -> Vec<String> {
+            let runner = FakeRunner::new(results);
+            tauri::async_runtime::block_on(async {
+                let (availability, took) = fetch_and_store(
+                    &self.events,
+                    &runner,
+                    &self.clock,
+                    &self.settings,
+                    &self.cache,
+                    RefreshTrigger::Manual,
+                )
+                .await;
+                emit_refresh_finished(&self.events, RefreshTrigger::Manual, availability, took);
+            });
+            let calls = runner.calls.lock().unwrap();
+            calls.iter().map(|(program, _, _)| program.clone()).collect()
+        }
+    }
+
+    #[test]
+    fn refreshes_send_their_events_in_order() {
+        let clock = FakeClock::new(local(2025, 6, 11, 14, 0));
+        let mut active = block(local(2025, 6, 11, 13, 0), local(2025, 6, 11, 18, 0), 2.5);
+        active.is_active = true;
+        let blocks = json!({ "blocks": [active.clone()] }).to_string();
+        let refresher = Refresher {
+            events: RecordedEvents::default(),
+            clock,
+            settings: AppSettings { detect_claude_running: true, ..AppSettings::default() },
+            cache: Mutex::new(SessionData::new()),
+        };
+
+        // ccusage missing, then found twice, then gone again
+        let missing = refresher.refresh(Vec::new());
+        refresher.refresh(vec![success(&blocks)]);
+        let found = refresher.refresh(vec![success(&blocks)]);
+        refresher.refresh(Vec::new());
+
+        // Without a session the process list is read through the runner too
+        assert_eq!(missing.last().map(String::as_str), Some("ps"));
+        assert!(!found.iter().any(|program| program == "ps"));
+        let cache = refresher.cache.lock().unwrap();
+        assert_eq!(cache.availability, Availability::NotFound);
+        assert!(cache.last_success.is_some());
+        assert_eq!(cache.claude_running, None);
+
+        let recorded = refresher.events.0.lock().unwrap();
+        let names: Vec<&str> = recorded.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "refresh-started",
+                "refresh-finished",
+                "refresh-started",
+                "availability-changed",
+                "session-updated",
+                "refresh-finished",
+                "refresh-started",
+                "session-updated",
+                "refresh-finished",
+                "refresh-started",
+                "availability-changed",
+                "refresh-finished",
+            ]
+        );
+        assert_eq!(recorded[0].1, json!({ "trigger": "manual" }));
+        assert_eq!(recorded[1].1, json!({ "trigger": "manual", "duration_ms": 0, "success": false }));
+        assert_eq!(recorded[3].1, json!({ "from": "not_found", "to": "available" }));
+        assert_eq!(recorded[4].1["session"]["id"], active.id.as_str());
+        assert_eq!(recorded[5].1["success"], true);
+        assert_eq!(recorded[7].1["session"]["costUSD"], 2.5);
+        assert_eq!(recorded[10].1, json!({ "from": "available", "to": "not_found" }));
+        assert_eq!(recorded[11].1["success"], false);
+    }
+}
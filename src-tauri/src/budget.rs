@@ -0,0 +1,415 @@
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+
+use crate::format::{format_cost, format_cost_grouped};
+use crate::model::{parse_local_time, BlockData, DailyUsage};
+use crate::state::AppSettings;
+use crate::stats::block_window_fraction;
+
+/// Span of time a budget covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum BudgetPeriod {
+    PerBlock,
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl BudgetPeriod {
+    pub(crate) const ALL: [BudgetPeriod; 4] =
+        [BudgetPeriod::PerBlock, BudgetPeriod::Daily, BudgetPeriod::Weekly, BudgetPeriod::Monthly];
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            BudgetPeriod::PerBlock => "Block",
+            BudgetPeriod::Daily => "Daily",
+            BudgetPeriod::Weekly => "Weekly",
+            BudgetPeriod::Monthly => "Monthly",
+        }
+    }
+
+    /// Menu id of the period's budget row
+    pub(crate) fn id(self) -> &'static str {
+        match self {
+            BudgetPeriod::PerBlock => "budget_block",
+            BudgetPeriod::Daily => "budget_daily",
+            BudgetPeriod::Weekly => "budget_weekly",
+            BudgetPeriod::Monthly => "budget_monthly",
+        }
+    }
+
+    pub(crate) fn amount(self, settings: &AppSettings) -> Option<f64> {
+        match self {
+            BudgetPeriod::PerBlock => settings.block_budget,
+            BudgetPeriod::Daily => settings.daily_budget,
+            BudgetPeriod::Weekly => settings.weekly_budget,
+            BudgetPeriod::Monthly => settings.monthly_budget,
+        }
+    }
+}
+
+/// First day of the week for weekly budgets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum WeekStart {
+    #[default]
+    Monday,
+    Sunday,
+}
+
+/// First day of the period containing `today`. Blocks have no calendar start,
+/// so for them this is just `today`.
+pub(crate) fn period_start(period: BudgetPeriod, today: chrono::NaiveDate, week_start: WeekStart) -> chrono::NaiveDate {
+    match period {
+        BudgetPeriod::PerBlock | BudgetPeriod::Daily => today,
+        BudgetPeriod::Weekly => {
+            let days_in = match week_start {
+                WeekStart::Monday => today.weekday().num_days_from_monday(),
+                WeekStart::Sunday => today.weekday().num_days_from_sunday(),
+            };
+            today - chrono::Duration::days(i64::from(days_in))
+        }
+        BudgetPeriod::Monthly => today.with_day(1).unwrap_or(today),
+    }
+}
+
+/// Identifies one occurrence of a period, so each is only alerted once: the
+/// block id, the date, the ISO week or the month. Sunday-start weeks are
+/// named after the ISO week they mostly overlap.
+pub(crate) fn period_key(
+    period: BudgetPeriod,
+    today: chrono::NaiveDate,
+    week_start: WeekStart,
+    block_id: Option<&str>,
+) -> Option<String> {
+    match period {
+        BudgetPeriod::PerBlock => block_id.map(str::to_string),
+        BudgetPeriod::Daily => Some(today.format("%Y-%m-%d").to_string()),
+        BudgetPeriod::Weekly => {
+            let week = (period_start(period, today, week_start) + chrono::Duration::days(3)).iso_week();
+            Some(format!("{}-W{:02}", week.year(), week.week()))
+        }
+        BudgetPeriod::Monthly => Some(today.format("%Y-%m").to_string()),
+    }
+}
+
+/// Spend against one budget
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct BudgetStatus {
+    pub(crate) period: BudgetPeriod,
+    pub(crate) key: String,
+    pub(crate) spent: f64,
+    pub(crate) budget: f64,
+}
+
+impl BudgetStatus {
+    pub(crate) fn is_over(&self) -> bool {
+        self.spent > self.budget
+    }
+
+    pub(crate) fn used_percent(&self) -> f64 {
+        if self.budget > 0.0 {
+            self.spent / self.budget * 100.0
+        } else {
+            100.0
+        }
+    }
+
+    /// Menu row such as "Weekly budget: $12.50 left (75%)"
+    pub(crate) fn row_text(&self) -> String {
+        let remaining = self.budget - self.spent;
+        let amount = if remaining < 0.0 {
+            format!("{} over", format_cost(-remaining))
+        } else {
+            format!("{} left", format_cost(remaining))
+        };
+        format!("{} budget: {} ({:.0}%)", self.period.label(), amount, self.used_percent())
+    }
+}
+
+/// Where `spent` of `budget` stands for the period containing `today`. `None`
+/// for a block budget without an active block.
+pub(crate) fn evaluate_budget(
+    period: BudgetPeriod,
+    spent: f64,
+    budget: f64,
+    today: chrono::NaiveDate,
+    week_start: WeekStart,
+    block_id: Option<&str>,
+) -> Option<BudgetStatus> {
+    Some(BudgetStatus {
+        period,
+        key: period_key(period, today, week_start, block_id)?,
+        spent,
+        budget,
+    })
+}
+
+/// Spend in the period containing `today`: the active block's cost, or the
+/// daily totals from the period start through today
+pub(crate) fn period_spend(
+    period: BudgetPeriod,
+    block: Option<&BlockData>,
+    days: &[DailyUsage],
+    today: chrono::NaiveDate,
+    week_start: WeekStart,
+) -> f64 {
+    if period == BudgetPeriod::PerBlock {
+        return block.map_or(0.0, |block| block.cost_usd);
+    }
+    let start = period_start(period, today, week_start);
+    days.iter()
+        .filter_map(|day| Some((chrono::NaiveDate::parse_from_str(&day.date, "%Y-%m-%d").ok()?, day.total_cost)))
+        .filter(|(date, _)| (start..=today).contains(date))
+        .map(|(_, cost)| cost)
+        .sum()
+}
+
+/// Menu row comparing the month's pay-as-you-go cost with a subscription,
+/// e.g. "Value used: $180 of $20 plan (9.0x)". `None` for a plan that's free.
+pub(crate) fn plan_value_text(month_spend: f64, plan_cost: f64) -> Option<String> {
+    (plan_cost > 0.0).then(|| {
+        format!(
+            "Value used: {} of {} plan ({:.1}x)",
+            format_cost_grouped(month_spend),
+            format_cost_grouped(plan_cost),
+            month_spend / plan_cost
+        )
+    })
+}
+
+/// Spend of the blocks that started on `today`, counted the way the menu's
+/// today total is: the active block only with `include_active_in_totals`, and
+/// only usage inside the counting window with `count_window_only`. Blocks from
+/// before local midnight are left out, so a cache that hasn't been refreshed
+/// since doesn't carry yesterday's total over.
+pub(crate) fn spend_on_day(blocks: &[BlockData], today: chrono::NaiveDate, settings: &AppSettings) -> f64 {
+    let window = settings.count_window_only.then_some(&settings.counting_window);
+    blocks
+        .iter()
+        .filter(|block| !block.is_gap && (settings.include_active_in_totals || !block.is_active))
+        .filter(|block| parse_local_time(&block.start_time).is_some_and(|start| start.date_naive() == today))
+        .map(|block| block.cost_usd * window.map_or(1.0, |window| block_window_fraction(block, window)))
+        .sum()
+}
+
+/// Where today's spend stands against `daily_cost_cap`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DailyCapCheck {
+    /// Over the cap right now; a recalculation that lowers the total clears it
+    pub(crate) over: bool,
+    /// Over the cap for the first time today
+    pub(crate) first_today: bool,
+}
+
+/// Compare `spent` today with `cap`, given the last day the cap was passed
+pub(crate) fn check_daily_cap(
+    spent: f64,
+    cap: Option<f64>,
+    today: chrono::NaiveDate,
+    fired_on: Option<chrono::NaiveDate>,
+) -> DailyCapCheck {
+    let over = cap.is_some_and(|cap| spent > cap);
+    DailyCapCheck { over, first_today: over && fired_on != Some(today) }
+}
+
+/// Status of every budget that's set
+pub(crate) fn budget_statuses(
+    settings: &AppSettings,
+    block: Option<&BlockData>,
+    days: &[DailyUsage],
+    today: chrono::NaiveDate,
+) -> Vec<BudgetStatus> {
+    BudgetPeriod::ALL
+        .into_iter()
+        .filter_map(|period| {
+            let budget = period.amount(settings)?;
+            let spent = period_spend(period, block, days, today, settings.week_start);
+            evaluate_budget(period, spent, budget, today, settings.week_start, block.map(|block| block.id.as_str()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::fixtures::{block, local};
+    use BudgetPeriod::*;
+    use WeekStart::*;
+
+    fn date(text: &str) -> chrono::NaiveDate {
+        chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn period_keys_across_boundaries() {
+        // 2020 has an ISO week 53, running Mon 2020-12-28 to Sun 2021-01-03
+        let cases = [
+            (Daily, "2020-12-31", Monday, "2020-12-31"),
+            (Daily, "2021-01-01", Monday, "2021-01-01"),
+            (Monthly, "2020-12-31", Monday, "2020-12"),
+            (Monthly, "2021-01-01", Sunday, "2021-01"),
+            (Weekly, "2020-12-27", Monday, "2020-W52"),
+            (Weekly, "2020-12-28", Monday, "2020-W53"),
+            (Weekly, "2021-01-03", Monday, "2020-W53"),
+            (Weekly, "2021-01-04", Monday, "2021-W01"),
+            (Weekly, "2020-12-26", Sunday, "2020-W52"),
+            (Weekly, "2020-12-27", Sunday, "2020-W53"),
+            (Weekly, "2021-01-02", Sunday, "2020-W53"),
+            (Weekly, "2021-01-03", Sunday, "2021-W01"),
+            // ISO weeks can belong to the next calendar year
+            (Weekly, "2019-12-30", Monday, "2020-W01"),
+        ];
+        for (period, today, week_start, expected) in cases {
+            let key = period_key(period, date(today), week_start, None);
+            assert_eq!(key.as_deref(), Some(expected), "{:?} {} {:?}", period, today, week_start);
+        }
+    }
+
+    #[test]
+    fn block_keys_are_the_block_id() {
+        assert_eq!(period_key(PerBlock, date("2021-01-01"), Monday, Some("b1")).as_deref(), Some("b1"));
+        assert_eq!(period_key(PerBlock, date("2021-01-01"), Monday, None), None);
+        assert_eq!(evaluate_budget(PerBlock, 0.0, 10.0, date("2021-01-01"), Monday, None), None);
+    }
+
+    #[test]
+    fn period_starts() {
+        let cases = [
+            (Daily, "2021-01-03", Monday, "2021-01-03"),
+            (Weekly, "2021-01-03", Monday, "2020-12-28"),
+            (Weekly, "2021-01-03", Sunday, "2021-01-03"),
+            (Weekly, "2021-01-02", Sunday, "2020-12-27"),
+            (Weekly, "2021-01-04", Monday, "2021-01-04"),
+            (Monthly, "2021-01-31", Monday, "2021-01-01"),
+        ];
+        for (period, today, week_start, expected) in cases {
+            assert_eq!(period_start(period, date(today), week_start), date(expected), "{:?} {}", period, today);
+        }
+    }
+
+    #[test]
+    fn budget_evaluation() {
+        // (spent, budget, over, used percent, row)
+        let cases = [
+            (0.0, 50.0, false, 0.0, "Weekly budget: $50.00 left (0%)"),
+            (37.5, 50.0, false, 75.0, "Weekly budget: $12.50 left (75%)"),
+            (50.0, 50.0, false, 100.0, "Weekly budget: $0.00 left (100%)"),
+            (60.0, 50.0, true, 120.0, "Weekly budget: $10.00 over (120%)"),
+            (1.0, 0.0, true, 100.0, "Weekly budget: $1.00 over (100%)"),
+        ];
+        for (spent, budget, over, used, row) in cases {
+            let status = evaluate_budget(Weekly, spent, budget, date("2021-01-03"), Monday, None).unwrap();
+            assert_eq!(status.key, "2020-W53");
+            assert_eq!(status.is_over(), over, "{} of {}", spent, budget);
+            assert_eq!(status.used_percent(), used, "{} of {}", spent, budget);
+            assert_eq!(status.row_text(), row);
+        }
+    }
+
+    #[test]
+    fn weekly_spend_crosses_the_new_year() {
+        let days: Vec<DailyUsage> = [("2020-12-27", 1.0), ("2020-12-28", 2.0), ("2020-12-31", 4.0), ("2021-01-03", 8.0)]
+            .into_iter()
+            .map(|(date, total_cost)| DailyUsage { date: date.to_string(), total_tokens: 0, total_cost })
+            .collect();
+        assert_eq!(period_spend(Weekly, None, &days, date("2021-01-03"), Monday), 14.0);
+        assert_eq!(period_spend(Weekly, None, &days, date("2021-01-03"), Sunday), 8.0);
+        assert_eq!(period_spend(Weekly, None, &days, date("2021-01-02"), Sunday), 7.0);
+        assert_eq!(period_spend(Monthly, None, &days, date("2021-01-03"), Monday), 8.0);
+        assert_eq!(period_spend(Daily, None, &days, date("2020-12-31"), Monday), 4.0);
+    }
+
+    #[test]
+    fn daily_cap_fires_once_a_day() {
+        let today = date("2021-01-03");
+        let yesterday = date("2021-01-02");
+        assert_eq!(check_daily_cap(9.0, Some(10.0), today, None), DailyCapCheck { over: false, first_today: false });
+        assert_eq!(check_daily_cap(11.0, Some(10.0), today, None), DailyCapCheck { over: true, first_today: true });
+        let again = check_daily_cap(11.0, Some(10.0), today, Some(today));
+        assert_eq!(again, DailyCapCheck { over: true, first_today: false });
+        let new_day = check_daily_cap(11.0, Some(10.0), today, Some(yesterday));
+        assert_eq!(new_day, DailyCapCheck { over: true, first_today: true });
+        assert_eq!(check_daily_cap(1e9, None, today, None), DailyCapCheck { over: false, first_today: false });
+    }
+
+    fn june(day: u32) -> chrono::NaiveDate {
+        chrono::NaiveDate::from_ymd_opt(2025, 6, day).unwrap()
+    }
+
+    fn cap_settings(include_active: bool, count_window_only: bool) -> AppSettings {
+        AppSettings {
+            daily_cost_cap: Some(10.0),
+            include_active_in_totals: include_active,
+            count_window_only,
+            ..AppSettings::default()
+        }
+    }
+
+    #[test]
+    fn the_cap_counts_today_as_the_menu_totals_it() {
+        let mut active = block(local(2025, 6, 11, 14, 0), local(2025, 6, 11, 19, 0), 5.0);
+        active.is_active = true;
+        let mut gap = block(local(2025, 6, 11, 11, 0), local(2025, 6, 11, 14, 0), 50.0);
+        gap.is_gap = true;
+        let blocks = [block(local(2025, 6, 11, 9, 0), local(2025, 6, 11, 11, 0), 6.0), gap, active];
+
+        let with_active = cap_settings(true, false);
+        assert_eq!(spend_on_day(&blocks, june(11), &with_active), 11.0);
+        assert!(check_daily_cap(11.0, with_active.daily_cost_cap, june(11), None).over);
+        // Settled totals leave the active block out, so the cap isn't passed yet
+        assert_eq!(spend_on_day(&blocks, june(11), &cap_settings(false, false)), 6.0);
+
+        // An evening block outside the default 09:00–18:00 window doesn't count towards it
+        let evening = [
+            block(local(2025, 6, 11, 9, 0), local(2025, 6, 11, 11, 0), 6.0),
+            block(local(2025, 6, 11, 19, 0), local(2025, 6, 11, 21, 0), 8.0),
+        ];
+        assert_eq!(spend_on_day(&evening, june(11), &cap_settings(true, false)), 14.0);
+        assert_eq!(spend_on_day(&evening, june(11), &cap_settings(true, true)), 6.0);
+    }
+
+    #[test]
+    fn a_recalculation_under_the_cap_clears_it_but_not_the_fired_day() {
+        let settings = cap_settings(true, false);
+        let today = june(11);
+        let mut fired_on = None;
+        let mut refresh = |cost: f64| {
+            let blocks = [block(local(2025, 6, 11, 9, 0), local(2025, 6, 11, 14, 0), cost)];
+            let check = check_daily_cap(spend_on_day(&blocks, today, &settings), Some(10.0), today, fired_on);
+            if check.first_today {
+                fired_on = Some(today);
+            }
+            check
+        };
+        assert_eq!(refresh(9.0), DailyCapCheck { over: false, first_today: false });
+        assert_eq!(refresh(10.5), DailyCapCheck { over: true, first_today: true });
+        // Repricing lowers the total below the cap...
+        assert_eq!(refresh(9.5), DailyCapCheck { over: false, first_today: false });
+        // ...and passing it again the same day doesn't notify twice
+        assert_eq!(refresh(10.2), DailyCapCheck { over: true, first_today: false });
+        assert_eq!(fired_on, Some(today));
+    }
+
+    #[test]
+    fn the_cap_starts_over_at_local_midnight() {
+        let settings = cap_settings(true, false);
+        let mut late = block(local(2025, 6, 11, 22, 0), local(2025, 6, 12, 3, 0), 12.0);
+        late.is_active = true;
+        let fired_on = Some(june(11));
+        let spent = spend_on_day(std::slice::from_ref(&late), june(11), &settings);
+        assert!(check_daily_cap(spent, Some(10.0), june(11), fired_on).over);
+
+        // Past midnight the block is still active, but it started yesterday
+        let spent = spend_on_day(std::slice::from_ref(&late), june(12), &settings);
+        assert_eq!(spent, 0.0);
+        let check = check_daily_cap(spent, Some(10.0), june(12), fired_on);
+        assert_eq!(check, DailyCapCheck { over: false, first_today: false });
+
+        let blocks = [late, block(local(2025, 6, 12, 3, 0), local(2025, 6, 12, 8, 0), 11.0)];
+        let spent = spend_on_day(&blocks, june(12), &settings);
+        let check = check_daily_cap(spent, Some(10.0), june(12), fired_on);
+        assert_eq!(check, DailyCapCheck { over: true, first_today: true });
+    }
+}
+
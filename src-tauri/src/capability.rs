@@ -0,0 +1,227 @@
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, Wry};
+use tauri_plugin_clipboard_manager::{Clipboard, ClipboardExt};
+use tauri_plugin_dialog::{Dialog, DialogExt};
+use tauri_plugin_notification::{Notification, NotificationExt, PermissionState};
+
+use crate::tray::update_tray;
+
+/// A system integration that can be missing or refused permission
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Capability {
+    Notifications,
+    Clipboard,
+    Dialogs,
+}
+
+impl Capability {
+    pub(crate) const ALL: [Capability; 3] = [Capability::Notifications, Capability::Clipboard, Capability::Dialogs];
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Capability::Notifications => "Notifications",
+            Capability::Clipboard => "Clipboard",
+            Capability::Dialogs => "Dialogs",
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Capability::Notifications => 0,
+            Capability::Clipboard => 1,
+            Capability::Dialogs => 2,
+        }
+    }
+}
+
+/// What's known about whether a capability works
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum CapabilityState {
+    /// Not used yet
+    #[default]
+    Unknown,
+    Available,
+    /// Refused permission, failed, or its plugin isn't registered
+    Denied,
+}
+
+/// What one check or use of a capability showed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Probe {
+    /// The plugin isn't registered
+    Missing,
+    /// The system hasn't asked the user for permission yet
+    NotAsked,
+    Granted,
+    Refused,
+    Succeeded,
+    Failed,
+}
+
+impl CapabilityState {
+    /// State after `probe`. A grant or a success always recovers, so turning a
+    /// permission on in System Settings takes effect the next time it's used.
+    /// A pending prompt keeps what's known, so a denial isn't forgotten.
+    pub(crate) fn after(self, probe: Probe) -> CapabilityState {
+        match probe {
+            Probe::Granted | Probe::Succeeded => CapabilityState::Available,
+            Probe::Missing | Probe::Refused | Probe::Failed => CapabilityState::Denied,
+            Probe::NotAsked => self,
+        }
+    }
+
+    pub(crate) fn description(self) -> &'static str {
+        match self {
+            CapabilityState::Unknown => "not used yet",
+            CapabilityState::Available => "available",
+            CapabilityState::Denied => "denied",
+        }
+    }
+}
+
+static CAPABILITIES: Mutex<[CapabilityState; 3]> = Mutex::new([CapabilityState::Unknown; 3]);
+
+pub(crate) fn capability_state(capability: Capability) -> CapabilityState {
+    CAPABILITIES.lock().unwrap()[capability.index()]
+}
+
+/// Capabilities known not to work, for the menu's hints
+pub(crate) fn denied_capabilities() -> Vec<Capability> {
+    Capability::ALL.into_iter().filter(|capability| capability_state(*capability) == CapabilityState::Denied).collect()
+}
+
+/// Apply `probe`, redrawing the menu when a capability starts or stops being denied
+fn record(app: &AppHandle, capability: Capability, probe: Probe) -> CapabilityState {
+    let (before, after) = {
+        let mut states = CAPABILITIES.lock().unwrap();
+        let before = states[capability.index()];
+        states[capability.index()] = before.after(probe);
+        (before, states[capability.index()])
+    };
+    if (before == CapabilityState::Denied) != (after == CapabilityState::Denied) {
+        update_tray(app);
+    }
+    after
+}
+
+fn notification_permission(app: &AppHandle) -> Probe {
+    if app.try_state::<Notification<Wry>>().is_none() {
+        return Probe::Missing;
+    }
+    match app.notification().permission_state() {
+        Ok(PermissionState::Granted) => Probe::Granted,
+        Ok(PermissionState::Denied) => Probe::Refused,
+        Ok(_) => Probe::NotAsked,
+        Err(_) => Probe::Failed,
+    }
+}
+
+/// Whether to show the system's permission prompt: only while it hasn't been
+/// answered and nothing else has shown the capability working or failing
+fn should_ask(probe: Probe, state: CapabilityState) -> bool {
+    probe == Probe::NotAsked && state == CapabilityState::Unknown
+}
+
+/// Show a notification, asking for permission the first time. Once refused,
+/// it isn't asked again. Returns whether it was shown.
+pub(crate) fn notify(app: &AppHandle, title: &str, body: impl Into<String>) -> bool {
+    let probe = notification_permission(app);
+    let mut state = record(app, Capability::Notifications, probe);
+    if should_ask(probe, state) {
+        let granted = matches!(app.notification().request_permission(), Ok(PermissionState::Granted));
+        state = record(app, Capability::Notifications, if granted { Probe::Granted } else { Probe::Refused });
+    }
+    if state == CapabilityState::Denied {
+        return false;
+    }
+    let shown = app.notification().builder().title(title).body(body).show();
+    if let Err(e) = &shown {
+        eprintln!("Failed to show notification: {}", e);
+    }
+    let probe = if shown.is_ok() { Probe::Succeeded } else { Probe::Failed };
+    record(app, Capability::Notifications, probe) == CapabilityState::Available
+}
+
+/// Put `text` on the clipboard, returning whether it worked
+pub(crate) fn copy_text(app: &AppHandle, text: String) -> bool {
+    if app.try_state::<Clipboard<Wry>>().is_none() {
+        record(app, Capability::Clipboard, Probe::Missing);
+        return false;
+    }
+    let written = app.clipboard().write_text(text);
+    if let Err(e) = &written {
+        eprintln!("Failed to write to the clipboard: {}", e);
+    }
+    let probe = if written.is_ok() { Probe::Succeeded } else { Probe::Failed };
+    record(app, Capability::Clipboard, probe) == CapabilityState::Available
+}
+
+/// The dialog plugin, or `None` if it isn't registered. Dialogs don't report
+/// failures, so only a missing plugin counts as denied.
+pub(crate) fn dialogs(app: &AppHandle) -> Option<&Dialog<Wry>> {
+    if app.try_state::<Dialog<Wry>>().is_none() {
+        record(app, Capability::Dialogs, Probe::Missing);
+        return None;
+    }
+    record(app, Capability::Dialogs, Probe::Succeeded);
+    Some(app.dialog())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use CapabilityState::{Available, Denied, Unknown};
+
+    #[test]
+    fn state_after_each_probe() {
+        let cases = [
+            (Probe::Missing, [Denied, Denied, Denied]),
+            (Probe::NotAsked, [Unknown, Available, Denied]),
+            (Probe::Granted, [Available, Available, Available]),
+            (Probe::Refused, [Denied, Denied, Denied]),
+            (Probe::Succeeded, [Available, Available, Available]),
+            (Probe::Failed, [Denied, Denied, Denied]),
+        ];
+        for (probe, expected) in cases {
+            assert_eq!([Unknown, Available, Denied].map(|state| state.after(probe)), expected, "{:?}", probe);
+        }
+    }
+
+    /// Run `probes` from an unused capability, as successive uses would
+    fn replay(probes: &[Probe]) -> CapabilityState {
+        probes.iter().fold(CapabilityState::default(), |state, probe| state.after(*probe))
+    }
+
+    #[test]
+    fn a_denial_is_remembered_while_the_prompt_is_pending() {
+        assert_eq!(replay(&[Probe::NotAsked, Probe::Refused, Probe::NotAsked]), Denied);
+        assert!(!should_ask(Probe::NotAsked, replay(&[Probe::Refused])));
+    }
+
+    #[test]
+    fn enabling_in_system_settings_recovers() {
+        assert_eq!(replay(&[Probe::Refused, Probe::Granted, Probe::Succeeded]), Available);
+        assert_eq!(replay(&[Probe::Failed, Probe::Succeeded]), Available);
+    }
+
+    #[test]
+    fn a_working_capability_can_start_failing() {
+        assert_eq!(replay(&[Probe::Granted, Probe::Succeeded, Probe::Failed]), Denied);
+    }
+
+    #[test]
+    fn permission_is_only_asked_for_once() {
+        assert!(should_ask(Probe::NotAsked, Unknown));
+        assert!(!should_ask(Probe::NotAsked, Available));
+        assert!(!should_ask(Probe::Granted, Unknown));
+        assert!(!should_ask(Probe::Refused, Unknown));
+        assert!(!should_ask(Probe::Missing, Unknown));
+    }
+
+    #[test]
+    fn capabilities_have_distinct_slots() {
+        let indexes = Capability::ALL.map(Capability::index);
+        assert_eq!(indexes, [0, 1, 2]);
+    }
+}
+
@@ -0,0 +1,147 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::state::{cache_dir, settings_backups, settings_path, AppSettings, CacheFile};
+use crate::status::status_file_path;
+
+/// Temporary files `write_atomically` leaves beside `path` if it's interrupted
+fn temp_files_beside(path: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(stem)) = (path.parent(), path.file_stem()) else {
+        return Vec::new();
+    };
+    let prefix = format!("{}.", stem.to_string_lossy());
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|candidate| {
+            let name = candidate.file_name().unwrap_or_default().to_string_lossy();
+            name.starts_with(&prefix) && name.ends_with(".tmp")
+        })
+        .collect()
+}
+
+/// Everywhere the app writes. Removing the app's data works from this alone,
+/// so a feature that writes somewhere new has to be added here.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct AppDataRegistry {
+    pub(crate) settings_file: Option<PathBuf>,
+    /// Holds every `CacheFile`
+    pub(crate) cache_dir: Option<PathBuf>,
+    pub(crate) status_file: PathBuf,
+}
+
+impl AppDataRegistry {
+    /// The locations in use with `settings`
+    pub(crate) fn current(settings: &AppSettings) -> Self {
+        Self { settings_file: settings_path(), cache_dir: cache_dir(), status_file: status_file_path(settings) }
+    }
+
+    fn settings_dir(&self) -> Option<&Path> {
+        self.settings_file.as_deref().and_then(Path::parent)
+    }
+
+    /// Every file the app can write, whether or not it exists
+    fn files(&self) -> Vec<PathBuf> {
+        let cache_files =
+            self.cache_dir.iter().flat_map(|dir| CacheFile::ALL.into_iter().map(|file| dir.join(file.file_name())));
+        self.settings_file.iter().cloned().chain(cache_files).chain(std::iter::once(self.status_file.clone())).collect()
+    }
+
+    /// Every file the app has written that still exists: the settings file and
+    /// its corrupt or pre-migration backups, the cache files, the status file,
+    /// and any temporary files left beside them
+    pub(crate) fn existing_paths(&self) -> Vec<PathBuf> {
+        let files = self.files();
+        let mut paths: Vec<PathBuf> = files.iter().flat_map(|file| temp_files_beside(file)).collect();
+        paths.extend(self.settings_dir().map(settings_backups).unwrap_or_default());
+        paths.extend(files);
+        paths.retain(|path| path.is_file());
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+
+    /// Delete `paths`, then the settings and cache directories if that left
+    /// them empty. Returns the files that couldn't be deleted.
+    pub(crate) fn remove(&self, paths: &[PathBuf]) -> Vec<(PathBuf, io::Error)> {
+        let failures: Vec<(PathBuf, io::Error)> = paths
+            .iter()
+            .filter_map(|path| std::fs::remove_file(path).err().map(|e| (path.clone(), e)))
+            .filter(|(_, e)| e.kind() != io::ErrorKind::NotFound)
+            .collect();
+        for dir in self.settings_dir().into_iter().chain(self.cache_dir.as_deref()) {
+            // Only succeeds on an empty directory, so nothing else in it is touched
+            let _ = std::fs::remove_dir(dir);
+        }
+        failures
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::TestDir;
+
+    fn sandbox(root: &Path) -> AppDataRegistry {
+        AppDataRegistry {
+            settings_file: Some(root.join("config").join("settings.json")),
+            cache_dir: Some(root.join("cache")),
+            status_file: root.join("status").join("ccusage.json"),
+        }
+    }
+
+    fn touch(path: &Path) -> PathBuf {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "{}").unwrap();
+        path.to_path_buf()
+    }
+
+    #[test]
+    fn every_app_file_is_found_and_removed() {
+        let root = TestDir::new("cleanup");
+        let registry = sandbox(root.path());
+        let config = root.path().join("config");
+        let cache = root.path().join("cache");
+        let mut written = vec![
+            touch(&config.join("settings.json")),
+            touch(&config.join("settings.corrupt-20250611-090000.json")),
+            touch(&config.join("settings.v1-20250611-090000.json")),
+            // Left by a write that was interrupted
+            touch(&config.join("settings.json.4242-0.tmp")),
+            touch(&root.path().join("status").join("ccusage.json")),
+        ];
+        written.extend(CacheFile::ALL.map(|file| touch(&cache.join(file.file_name()))));
+        written.sort();
+        let unrelated = [touch(&cache.join("notes.txt")), touch(&root.path().join("status").join("other.json"))];
+
+        assert_eq!(registry.existing_paths(), written);
+        assert!(registry.remove(&written).is_empty());
+        assert!(written.iter().all(|path| !path.exists()));
+        assert!(unrelated.iter().all(|path| path.exists()));
+        // Emptied, so removed; the cache directory still holds someone else's file
+        assert!(!config.exists());
+        assert!(cache.exists());
+        assert!(registry.existing_paths().is_empty());
+    }
+
+    #[test]
+    fn nothing_is_listed_before_the_app_has_written_anything() {
+        let root = TestDir::new("cleanup-empty");
+        let registry = sandbox(root.path());
+        assert!(registry.existing_paths().is_empty());
+        assert!(registry.remove(&[]).is_empty());
+    }
+
+    #[test]
+    fn files_already_gone_are_not_failures() {
+        let root = TestDir::new("cleanup-gone");
+        let registry = sandbox(root.path());
+        let settings = touch(&root.path().join("config").join("settings.json"));
+        let paths = registry.existing_paths();
+        std::fs::remove_file(&settings).unwrap();
+        assert!(registry.remove(&paths).is_empty());
+    }
+}
@@ -0,0 +1,143 @@
+use crate::fetch::{fetch_session_data, RUNNER};
+use crate::format::format_cost;
+use crate::model::BlockData;
+use crate::state::{load_session_snapshot, Availability};
+
+/// Exit code when there is no active session to print
+const EXIT_NO_SESSION: i32 = 2;
+/// Exit code for bad arguments or when ccusage can't be run
+const EXIT_FAILURE: i32 = 1;
+
+const USAGE: &str = "usage: ccusage-macos-menubar --print cost|tokens|json [--cached]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PrintField {
+    Cost,
+    Tokens,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PrintArgs {
+    pub(crate) field: PrintField,
+    /// Read the snapshot saved by the last refresh instead of running ccusage
+    pub(crate) cached: bool,
+}
+
+/// Parse the arguments after the program name. `Ok(None)` means no `--print`
+/// was given and the tray app should start as usual.
+pub(crate) fn parse_args(args: &[String]) -> Result<Option<PrintArgs>, String> {
+    let mut field = None;
+    let mut cached = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--print" => {
+                field = Some(match args.next().map(String::as_str) {
+                    Some("cost") => PrintField::Cost,
+                    Some("tokens") => PrintField::Tokens,
+                    Some("json") => PrintField::Json,
+                    Some(other) => return Err(format!("unknown --print value '{}'", other)),
+                    None => return Err("--print needs a value".to_string()),
+                });
+            }
+            "--cached" => cached = true,
+            // Ignore anything else, like the process serial number macOS may pass
+            _ => {}
+        }
+    }
+    match field {
+        Some(field) => Ok(Some(PrintArgs { field, cached })),
+        None if cached => Err("--cached only applies with --print".to_string()),
+        None => Ok(None),
+    }
+}
+
+/// Text printed for the active session
+pub(crate) fn format_output(field: PrintField, block: &BlockData) -> String {
+    match field {
+        PrintField::Cost => format_cost(block.cost_usd),
+        PrintField::Tokens => block.token_counts.total().to_string(),
+        PrintField::Json => serde_json::to_string(block).unwrap_or_else(|_| "null".to_string()),
+    }
+}
+
+/// Handle `--print` if it was passed, returning the exit code. `None` means
+/// the tray app should start.
+pub(crate) fn run_from_args() -> Option<i32> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let print_args = match parse_args(&args) {
+        Ok(print_args) => print_args?,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            return Some(EXIT_FAILURE);
+        }
+    };
+
+    let block = if print_args.cached {
+        load_session_snapshot().and_then(|snapshot| snapshot.session)
+    } else {
+        let (block, availability) = tauri::async_runtime::block_on(fetch_session_data(&RUNNER));
+        if availability != Availability::Available {
+            eprintln!("ccusage could not be run");
+            return Some(EXIT_FAILURE);
+        }
+        block
+    };
+
+    match block {
+        Some(block) => {
+            println!("{}", format_output(print_args.field, &block));
+            Some(0)
+        }
+        None => Some(EXIT_NO_SESSION),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::fixtures::{block, local};
+
+    fn parse(args: &[&str]) -> Result<Option<PrintArgs>, String> {
+        parse_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn no_arguments_start_the_tray() {
+        assert_eq!(parse(&[]), Ok(None));
+        // macOS may pass a process serial number when launched from Finder
+        assert_eq!(parse(&["-psn_0_12345"]), Ok(None));
+    }
+
+    #[test]
+    fn print_takes_a_field_and_optional_cached_flag() {
+        let cost = PrintArgs { field: PrintField::Cost, cached: false };
+        assert_eq!(parse(&["--print", "cost"]), Ok(Some(cost)));
+        let tokens = PrintArgs { field: PrintField::Tokens, cached: true };
+        assert_eq!(parse(&["--print", "tokens", "--cached"]), Ok(Some(tokens)));
+        assert_eq!(parse(&["--cached", "--print", "tokens"]), Ok(Some(tokens)));
+        let json = PrintArgs { field: PrintField::Json, cached: false };
+        assert_eq!(parse(&["--print", "json"]), Ok(Some(json)));
+    }
+
+    #[test]
+    fn bad_print_arguments_are_errors() {
+        assert!(parse(&["--print"]).is_err());
+        assert!(parse(&["--print", "money"]).is_err());
+        assert!(parse(&["--cached"]).is_err());
+    }
+
+    #[test]
+    fn output_for_each_field() {
+        let mut session = block(local(2025, 6, 11, 9, 0), local(2025, 6, 11, 14, 0), 12.4);
+        session.token_counts.input_tokens = 1_000;
+        session.token_counts.output_tokens = 234;
+        assert_eq!(format_output(PrintField::Cost, &session), "$12.40");
+        assert_eq!(format_output(PrintField::Tokens, &session), "1234");
+
+        let json: serde_json::Value = serde_json::from_str(&format_output(PrintField::Json, &session)).unwrap();
+        assert_eq!(json["costUSD"], 12.4);
+        assert_eq!(json["tokenCounts"]["outputTokens"], 234);
+    }
+}
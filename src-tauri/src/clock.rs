@@ -0,0 +1,119 @@
+use std::time::Instant;
+
+/// Source of wall-clock and monotonic time. Time-dependent code takes a
+/// `&dyn Clock` so it can be driven by a fake clock instead of the system one.
+pub(crate) trait Clock: Send + Sync {
+    fn now(&self) -> chrono::DateTime<chrono::Local>;
+    fn instant(&self) -> Instant;
+}
+
+pub(crate) struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> chrono::DateTime<chrono::Local> {
+        chrono::Local::now()
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+pub(crate) static CLOCK: SystemClock = SystemClock;
+
+/// Wall time from `earlier` to `now`, zero if the clock was set back in between
+pub(crate) fn wall_elapsed(
+    earlier: chrono::DateTime<chrono::Local>,
+    now: chrono::DateTime<chrono::Local>,
+) -> std::time::Duration {
+    (now - earlier).to_std().unwrap_or_default()
+}
+
+/// Drop samples timestamped after `now`, which only happens when the wall
+/// clock was set back, so time series stay in order
+pub(crate) fn drop_future_samples<T>(
+    samples: &mut Vec<(chrono::DateTime<chrono::Local>, T)>,
+    now: chrono::DateTime<chrono::Local>,
+) {
+    samples.retain(|(time, _)| *time <= now);
+}
+
+/// A clock that stands still until a test moves it
+#[cfg(test)]
+pub(crate) struct FakeClock {
+    now: std::sync::Mutex<chrono::DateTime<chrono::Local>>,
+    instant: std::sync::Mutex<Instant>,
+}
+
+#[cfg(test)]
+impl FakeClock {
+    pub(crate) fn new(now: chrono::DateTime<chrono::Local>) -> Self {
+        Self { now: std::sync::Mutex::new(now), instant: std::sync::Mutex::new(Instant::now()) }
+    }
+
+    /// Move both clocks forward by `by`, as if that much time passed
+    pub(crate) fn advance(&self, by: chrono::Duration) {
+        *self.now.lock().unwrap() += by;
+        *self.instant.lock().unwrap() += by.to_std().expect("a forward step");
+    }
+
+    /// Change only the wall clock, as a manual change or an NTP correction
+    /// would; the monotonic clock keeps going as before
+    pub(crate) fn set(&self, now: chrono::DateTime<chrono::Local>) {
+        *self.now.lock().unwrap() = now;
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> chrono::DateTime<chrono::Local> {
+        *self.now.lock().unwrap()
+    }
+
+    fn instant(&self) -> Instant {
+        *self.instant.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::fixtures::local;
+
+    #[test]
+    fn elapsed_wall_time_follows_forward_jumps() {
+        let clock = FakeClock::new(local(2025, 6, 11, 10, 0));
+        let before = clock.now();
+        clock.set(local(2025, 6, 11, 11, 30));
+        assert_eq!(wall_elapsed(before, clock.now()), std::time::Duration::from_secs(90 * 60));
+    }
+
+    #[test]
+    fn elapsed_wall_time_is_zero_after_a_backward_jump() {
+        let clock = FakeClock::new(local(2025, 6, 11, 10, 0));
+        let before = clock.now();
+        clock.set(local(2025, 6, 11, 9, 3));
+        assert_eq!(wall_elapsed(before, clock.now()), std::time::Duration::ZERO);
+    }
+
+    #[test]
+    fn setting_the_wall_clock_leaves_the_monotonic_clock_alone() {
+        let clock = FakeClock::new(local(2025, 6, 11, 10, 0));
+        let started = clock.instant();
+        clock.set(local(2025, 6, 11, 9, 0));
+        clock.advance(chrono::Duration::minutes(5));
+        assert_eq!(clock.instant() - started, std::time::Duration::from_secs(5 * 60));
+        assert_eq!(clock.now(), local(2025, 6, 11, 9, 5));
+    }
+
+    #[test]
+    fn samples_from_before_a_backward_jump_are_dropped() {
+        let clock = FakeClock::new(local(2025, 6, 11, 10, 0));
+        let mut samples = vec![(clock.now(), 1.0)];
+        clock.advance(chrono::Duration::minutes(10));
+        samples.push((clock.now(), 2.0));
+        clock.set(local(2025, 6, 11, 10, 5));
+        drop_future_samples(&mut samples, clock.now());
+        assert_eq!(samples, [(local(2025, 6, 11, 10, 0), 1.0)]);
+    }
+}
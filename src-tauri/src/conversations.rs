@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::clock::Clock;
+use crate::fetch::{fetch_ccusage, CommandRunner};
+use crate::model::{SessionResponse, SessionUsage};
+
+/// How many conversations "Today's conversations" lists
+pub(crate) const TOP_CONVERSATIONS: usize = 5;
+
+/// Characters of a conversation id shown before it's cut off
+const SHORT_ID_CHARS: usize = 8;
+
+/// A conversation's spend. ccusage reports it for the whole conversation, so
+/// one that started yesterday counts yesterday's messages too.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ConversationCost {
+    pub(crate) id: String,
+    pub(crate) cost: f64,
+    pub(crate) tokens: u64,
+}
+
+#[derive(Debug, Clone)]
+struct TodaysConversations {
+    date: chrono::NaiveDate,
+    /// Today's spend when these were fetched; they can't have changed until it moves
+    today_cost: f64,
+    conversations: Vec<ConversationCost>,
+}
+
+static CONVERSATIONS: Mutex<Option<TodaysConversations>> = Mutex::new(None);
+
+/// Cost and tokens per conversation last active on `today`, in the order
+/// first seen. A conversation that moved between projects is listed once per
+/// project by ccusage and summed here.
+pub(crate) fn group_conversations(sessions: &[SessionUsage], today: chrono::NaiveDate) -> Vec<ConversationCost> {
+    let mut grouped: Vec<ConversationCost> = Vec::new();
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    for session in sessions {
+        let last_active =
+            session.last_activity.get(..10).and_then(|date| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+        if last_active != Some(today) {
+            continue;
+        }
+        match index.get(session.session_id.as_str()) {
+            Some(&i) => {
+                grouped[i].cost += session.total_cost;
+                grouped[i].tokens += session.total_tokens;
+            }
+            None => {
+                index.insert(&session.session_id, grouped.len());
+                grouped.push(ConversationCost {
+                    id: session.session_id.clone(),
+                    cost: session.total_cost,
+                    tokens: session.total_tokens,
+                });
+            }
+        }
+    }
+    grouped
+}
+
+/// The `n` most expensive conversations, most expensive first. Ties go to the
+/// one with more tokens, then by id, so the menu doesn't reshuffle between refreshes.
+pub(crate) fn top_conversations(mut conversations: Vec<ConversationCost>, n: usize) -> Vec<ConversationCost> {
+    conversations.sort_by(|a, b| b.cost.total_cmp(&a.cost).then(b.tokens.cmp(&a.tokens)).then_with(|| a.id.cmp(&b.id)));
+    conversations.truncate(n);
+    conversations
+}
+
+/// The start of a conversation id, enough to tell conversations apart in the menu
+pub(crate) fn short_id(id: &str) -> String {
+    if id.chars().count() <= SHORT_ID_CHARS {
+        return id.to_string();
+    }
+    let mut short: String = id.chars().take(SHORT_ID_CHARS).collect();
+    short.push('…');
+    short
+}
+
+/// Fetch today's most expensive conversations with `ccusage session`. Skipped
+/// while today's spend hasn't moved since the last fetch; a failed run keeps
+/// what was fetched before.
+pub(crate) async fn refresh_conversations(runner: &dyn CommandRunner, clock: &dyn Clock, today_cost: f64) {
+    let today = clock.now().date_naive();
+    let unchanged = |cached: &TodaysConversations| cached.date == today && cached.today_cost == today_cost;
+    if CONVERSATIONS.lock().unwrap().as_ref().is_some_and(unchanged) {
+        return;
+    }
+    let since = today.format("%Y%m%d").to_string();
+    let args = ["session", "--json", "--since", &since];
+    let Ok(response) = fetch_ccusage::<SessionResponse>(runner, &args).await else {
+        return;
+    };
+    let conversations = top_conversations(group_conversations(&response.sessions, today), TOP_CONVERSATIONS);
+    *CONVERSATIONS.lock().unwrap() = Some(TodaysConversations { date: today, today_cost, conversations });
+}
+
+/// The most expensive conversations last active on `today`, empty until
+/// they've been fetched that day
+pub(crate) fn todays_conversations(today: chrono::NaiveDate) -> Vec<ConversationCost> {
+    CONVERSATIONS
+        .lock()
+        .unwrap()
+        .as_ref()
+        .filter(|cached| cached.date == today)
+        .map(|cached| cached.conversations.clone())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::fetch::fake::{success, FakeRunner};
+    use crate::model::fixtures::local;
+
+    fn date(day: u32) -> chrono::NaiveDate {
+        chrono::NaiveDate::from_ymd_opt(2025, 6, day).unwrap()
+    }
+
+    fn session(id: &str, cost: f64, tokens: u64, last_activity: &str) -> SessionUsage {
+        SessionUsage {
+            session_id: id.to_string(),
+            total_tokens: tokens,
+            total_cost: cost,
+            last_activity: last_activity.to_string(),
+        }
+    }
+
+    fn conversation(id: &str, cost: f64, tokens: u64) -> ConversationCost {
+        ConversationCost { id: id.to_string(), cost, tokens }
+    }
+
+    fn ids(conversations: &[ConversationCost]) -> Vec<&str> {
+        conversations.iter().map(|conversation| conversation.id.as_str()).collect()
+    }
+
+    #[test]
+    fn conversations_are_summed_across_projects() {
+        let sessions = [
+            session("aaaa", 1.0, 100, "2025-06-11"),
+            session("bbbb", 2.0, 200, "2025-06-11"),
+            session("aaaa", 0.5, 50, "2025-06-11"),
+        ];
+        assert_eq!(
+            group_conversations(&sessions, date(11)),
+            [conversation("aaaa", 1.5, 150), conversation("bbbb", 2.0, 200)]
+        );
+    }
+
+    #[test]
+    fn only_conversations_active_today_are_listed() {
+        let sessions = [
+            session("yesterday", 9.0, 900, "2025-06-10"),
+            session("today", 1.0, 100, "2025-06-11"),
+            session("timestamped", 2.0, 200, "2025-06-11T09:30:00+02:00"),
+            session("unknown", 3.0, 300, ""),
+        ];
+        assert_eq!(ids(&group_conversations(&sessions, date(11))), ["today", "timestamped"]);
+    }
+
+    #[test]
+    fn the_top_five_are_kept_most_expensive_first() {
+        let conversations = (1..=7).map(|n| conversation(&format!("c{}", n), f64::from(n), 0)).collect();
+        assert_eq!(ids(&top_conversations(conversations, TOP_CONVERSATIONS)), ["c7", "c6", "c5", "c4", "c3"]);
+    }
+
+    #[test]
+    fn ties_go_to_more_tokens_then_id() {
+        let conversations = vec![
+            conversation("dddd", 2.0, 100),
+            conversation("cccc", 2.0, 500),
+            conversation("bbbb", 2.0, 100),
+            conversation("aaaa", 1.0, 900),
+        ];
+        assert_eq!(ids(&top_conversations(conversations, 3)), ["cccc", "bbbb", "dddd"]);
+    }
+
+    #[test]
+    fn fewer_than_five_are_all_kept() {
+        let conversations = vec![conversation("aaaa", 1.0, 0), conversation("bbbb", 3.0, 0)];
+        assert_eq!(ids(&top_conversations(conversations, TOP_CONVERSATIONS)), ["bbbb", "aaaa"]);
+        assert!(top_conversations(Vec::new(), TOP_CONVERSATIONS).is_empty());
+    }
+
+    #[test]
+    fn long_ids_are_cut_short() {
+        assert_eq!(short_id("0f3c9a1e-7b2d-4c55-9e61-2a8d4f0b7c13"), "0f3c9a1e…");
+        assert_eq!(short_id("0f3c9a1e"), "0f3c9a1e");
+        assert_eq!(short_id("çàéèüößñ-x"), "çàéèüößñ…");
+    }
+
+    #[test]
+    fn conversations_are_refetched_only_when_todays_spend_moves() {
+        let json = r#"{"sessions": [
+            {"sessionId": "aaaa", "totalTokens": 100, "totalCost": 1.0, "lastActivity": "2025-06-11"},
+            {"sessionId": "bbbb", "totalTokens": 200, "totalCost": 2.0, "lastActivity": "2025-06-11"}
+        ]}"#;
+        let runner = FakeRunner::new([success(json), success(json)]);
+        let clock = FakeClock::new(local(2025, 6, 11, 14, 0));
+        let refresh = |today_cost| tauri::async_runtime::block_on(refresh_conversations(&runner, &clock, today_cost));
+
+        refresh(3.0);
+        assert_eq!(ids(&todays_conversations(date(11))), ["bbbb", "aaaa"]);
+        assert!(runner.calls.lock().unwrap()[0].1.iter().any(|arg| arg.contains("session --json --since 20250611")));
+        refresh(3.0);
+        assert_eq!(runner.calls.lock().unwrap().len(), 1);
+        refresh(3.5);
+        assert_eq!(runner.calls.lock().unwrap().len(), 2);
+        // Yesterday's list isn't shown after midnight
+        assert!(todays_conversations(date(12)).is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+use std::path::{Path, PathBuf};
+
+use crate::state::AppSettings;
+
+/// Data directories with duplicates and nested paths removed, since ccusage
+/// would otherwise count the conversations under them twice
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct DedupedDirs {
+    pub(crate) kept: Vec<PathBuf>,
+    /// Dropped directories, each with the kept directory that covers it
+    pub(crate) ignored: Vec<(PathBuf, PathBuf)>,
+}
+
+/// Resolve a leading `~/` against the home directory
+pub(crate) fn expand_home(dir: &str) -> PathBuf {
+    match (dir.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(dir),
+    }
+}
+
+/// Resolve symlinks where possible so aliases of the same directory compare equal
+fn canonical(dir: &Path) -> PathBuf {
+    std::fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf())
+}
+
+/// Drop directories that repeat or sit inside another listed directory,
+/// keeping the rest in their original order
+pub(crate) fn dedupe_data_dirs(dirs: &[PathBuf]) -> DedupedDirs {
+    let canonical_dirs: Vec<PathBuf> = dirs.iter().map(|dir| canonical(dir)).collect();
+    let mut deduped = DedupedDirs::default();
+
+    for (i, dir) in canonical_dirs.iter().enumerate() {
+        let covering = canonical_dirs.iter().enumerate().find(|&(j, other)| {
+            (other == dir && j < i) || (other != dir && dir.starts_with(other))
+        });
+        match covering {
+            Some((j, _)) => deduped.ignored.push((dirs[i].clone(), dirs[j].clone())),
+            None => deduped.kept.push(dirs[i].clone()),
+        }
+    }
+    deduped
+}
+
+/// The configured Claude data directories, deduplicated
+pub(crate) fn configured_data_dirs(settings: &AppSettings) -> DedupedDirs {
+    let dirs: Vec<PathBuf> = settings
+        .claude_config_dirs
+        .iter()
+        .filter(|dir| !dir.trim().is_empty())
+        .map(|dir| expand_home(dir.trim()))
+        .collect();
+    dedupe_data_dirs(&dirs)
+}
+
+/// Where ccusage looks for Claude's logs when no data directories are configured
+fn default_data_dirs() -> Vec<PathBuf> {
+    dirs::home_dir()
+        .map(|home| vec![home.join(".config").join("claude"), home.join(".claude")])
+        .unwrap_or_default()
+}
+
+/// The directories ccusage reads: the configured ones, else `CLAUDE_CONFIG_DIR`
+/// from the app's own environment, else ccusage's defaults
+pub(crate) fn usage_data_dirs(settings: &AppSettings) -> Vec<PathBuf> {
+    let configured = configured_data_dirs(settings).kept;
+    if !configured.is_empty() {
+        return configured;
+    }
+    match std::env::var("CLAUDE_CONFIG_DIR") {
+        Ok(dirs) if !dirs.trim().is_empty() => {
+            dirs.split(',').filter(|dir| !dir.trim().is_empty()).map(|dir| expand_home(dir.trim())).collect()
+        }
+        _ => default_data_dirs(),
+    }
+}
+
+/// Whether `dir` holds any Claude Code logs, i.e. a `projects/<project>/*.jsonl`
+/// file. Stops at the first one found, so it's cheap on a populated directory.
+pub(crate) fn has_usage_logs(dir: &Path) -> bool {
+    let Ok(projects) = std::fs::read_dir(dir.join("projects")) else {
+        return false;
+    };
+    projects.flatten().any(|project| {
+        std::fs::read_dir(project.path()).is_ok_and(|mut files| {
+            files.any(|file| file.is_ok_and(|file| file.path().extension().is_some_and(|ext| ext == "jsonl")))
+        })
+    })
+}
+
+/// An empty ccusage response either means nothing was used in the window, or
+/// that ccusage is looking in the wrong place
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EmptyUsage {
+    /// Logs exist, there's just no recent usage
+    HasLogs,
+    /// None of the data directories hold any logs
+    NoLogs,
+}
+
+pub(crate) fn classify_empty_usage(dirs: &[PathBuf]) -> EmptyUsage {
+    if dirs.iter().any(|dir| has_usage_logs(dir)) {
+        EmptyUsage::HasLogs
+    } else {
+        EmptyUsage::NoLogs
+    }
+}
+
+/// Environment passed to ccusage: `CLAUDE_CONFIG_DIR` as a comma-separated
+/// list when data directories are configured, otherwise nothing
+pub(crate) fn ccusage_env(settings: &AppSettings) -> Vec<(String, String)> {
+    let dirs = configured_data_dirs(settings).kept;
+    if dirs.is_empty() {
+        return Vec::new();
+    }
+    let joined = dirs.iter().map(|dir| dir.to_string_lossy()).collect::<Vec<_>>().join(",");
+    vec![("CLAUDE_CONFIG_DIR".to_string(), joined)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::TestDir;
+
+    fn make_dir(path: PathBuf) -> PathBuf {
+        std::fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn nested_and_repeated_dirs_are_dropped() {
+        let root = TestDir::new("dedupe");
+        let outer = make_dir(root.path().join("claude"));
+        let inner = make_dir(outer.join("work"));
+        let other = make_dir(root.path().join("claude-other"));
+
+        let deduped = dedupe_data_dirs(&[inner.clone(), outer.clone(), other.clone(), outer.clone()]);
+        assert_eq!(deduped.kept, [outer.clone(), other]);
+        assert_eq!(deduped.ignored, [(inner, outer.clone()), (outer.clone(), outer)]);
+    }
+
+    #[test]
+    fn paths_that_only_share_a_name_prefix_are_kept() {
+        let deduped = dedupe_data_dirs(&[PathBuf::from("/nonexistent/claude"), PathBuf::from("/nonexistent/claude2")]);
+        assert_eq!(deduped.kept.len(), 2);
+        assert!(deduped.ignored.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_to_listed_dirs_are_dropped() {
+        let root = TestDir::new("symlinks");
+        let real = make_dir(root.path().join("real"));
+        let nested = make_dir(real.join("nested"));
+        let alias = root.path().join("alias");
+        let nested_alias = root.path().join("nested alias");
+        std::os::unix::fs::symlink(&real, &alias).unwrap();
+        std::os::unix::fs::symlink(&nested, &nested_alias).unwrap();
+
+        let deduped = dedupe_data_dirs(&[real.clone(), alias.clone(), nested_alias.clone()]);
+        assert_eq!(deduped.kept, [real.clone()]);
+        assert_eq!(deduped.ignored, [(alias, real.clone()), (nested_alias, real)]);
+    }
+
+    #[test]
+    fn ccusage_env_lists_the_kept_dirs() {
+        let root = TestDir::new("env");
+        let outer = make_dir(root.path().join("a"));
+        let inner = make_dir(outer.join("b"));
+        let settings = AppSettings {
+            claude_config_dirs: vec![outer.display().to_string(), inner.display().to_string(), "  ".to_string()],
+            ..AppSettings::default()
+        };
+        assert_eq!(ccusage_env(&settings), [("CLAUDE_CONFIG_DIR".to_string(), outer.display().to_string())]);
+        assert!(ccusage_env(&AppSettings::default()).is_empty());
+    }
+
+    fn write_file(path: PathBuf) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "{}\n").unwrap();
+    }
+
+    #[test]
+    fn logs_are_found_under_projects() {
+        let root = TestDir::new("logs");
+        let claude = root.path().join("claude");
+        write_file(claude.join("projects").join("-Users-ann-api").join("a1b2.jsonl"));
+        assert!(has_usage_logs(&claude));
+        assert_eq!(classify_empty_usage(&[claude]), EmptyUsage::HasLogs);
+    }
+
+    #[test]
+    fn missing_and_empty_dirs_have_no_logs() {
+        let root = TestDir::new("no-logs");
+        let empty = make_dir(root.path().join("empty"));
+        let no_projects = make_dir(empty.join("projects"));
+        let missing = root.path().join("missing");
+        assert_eq!(classify_empty_usage(&[]), EmptyUsage::NoLogs);
+        assert_eq!(classify_empty_usage(&[empty, no_projects, missing]), EmptyUsage::NoLogs);
+    }
+
+    #[test]
+    fn files_in_the_wrong_place_are_not_logs() {
+        let root = TestDir::new("misplaced");
+        let claude = root.path().join("claude");
+        write_file(claude.join("session.jsonl"));
+        write_file(claude.join("projects").join("loose.jsonl"));
+        write_file(claude.join("projects").join("api").join("notes.json"));
+        assert!(!has_usage_logs(&claude));
+    }
+
+    #[test]
+    fn any_dir_with_logs_counts() {
+        let root = TestDir::new("several");
+        let empty = make_dir(root.path().join("empty"));
+        let work = root.path().join("work");
+        write_file(work.join("projects").join("api").join("a.jsonl"));
+        assert_eq!(classify_empty_usage(&[empty, root.path().join("missing"), work]), EmptyUsage::HasLogs);
+    }
+}
+
@@ -0,0 +1,275 @@
+use std::path::Path;
+use tokio::process::Command;
+
+use crate::alerts::recent_alerts;
+use crate::capability::{capability_state, Capability};
+use crate::datadirs::configured_data_dirs;
+use crate::fetch::{homebrew_ccusage, proxy_env, shell_command, EXTENDED_PATH, LOCATE_COMMAND, RUNNER};
+use crate::format::{format_seconds, mask_url_credentials};
+use crate::redact::redact_for_egress;
+use crate::model::schema_notes;
+use crate::shortcut::SHORTCUT_ERROR;
+use crate::state::{
+    cache_dir, current_settings, settings_path, write_atomically, Availability, CacheFile, RawResponse, CONFIG_DIR_ENV,
+    LAST_HOOK_RESULT, LAST_RAW_RESPONSE, NPX_DOWNLOADS, RAW_RESPONSE_LIMIT, REFRESH_TIMINGS, SESSION_CACHE,
+};
+use crate::tray::tray_icon_failed;
+
+/// Try creating and removing a file in `dir`, describing the outcome
+fn probe_writable(dir: &Path) -> String {
+    let probe = dir.join(".write-test");
+    let result = std::fs::create_dir_all(dir)
+        .and_then(|()| std::fs::write(&probe, b""))
+        .and_then(|()| std::fs::remove_file(&probe));
+    match result {
+        Ok(()) => "writable".to_string(),
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => format!("permission denied ({})", e),
+        Err(e) => format!("error - {}", e),
+    }
+}
+
+/// Most characters of the raw response shown inline; the rest is in the saved file
+const RAW_PREVIEW_CHARS: usize = 2000;
+
+/// The last raw response with notes on fields our structs expect but it lacks.
+/// Pretty-printed in full to `last-response.json` in the cache directory, with
+/// the start of it inline.
+fn raw_response_section(raw: &RawResponse) -> String {
+    let mut section = format!("\nLast raw response (ccusage {}):\n", raw.args);
+    if raw.truncated {
+        section.push_str(&format!("(cut off at {} KB)\n", RAW_RESPONSE_LIMIT / 1024));
+    }
+    let text = match serde_json::from_str::<serde_json::Value>(&raw.stdout) {
+        Ok(value) => {
+            let notes = schema_notes(&value);
+            if notes.is_empty() {
+                section.push_str("All expected fields present\n");
+            }
+            for note in notes {
+                section.push_str(&format!("{}\n", note));
+            }
+            serde_json::to_string_pretty(&value).unwrap_or_else(|_| raw.stdout.clone())
+        }
+        Err(e) => {
+            section.push_str(&format!("Not valid JSON: {}\n", e));
+            raw.stdout.clone()
+        }
+    };
+    if let Some(path) = CacheFile::LastResponse.path() {
+        if write_atomically(&path, &text).is_ok() {
+            section.push_str(&format!("Saved to {}\n", path.display()));
+        }
+    }
+    let preview: String = text.chars().take(RAW_PREVIEW_CHARS).collect();
+    section.push_str(&preview);
+    if preview.len() < text.len() {
+        section.push_str("\n…");
+    }
+    section.push('\n');
+    section
+}
+
+/// Environment, permission and ccusage diagnostics, redacted for sharing
+pub(crate) async fn get_debug_info() -> String {
+    let mut debug_info = String::new();
+
+    // Get PATH environment variable
+    debug_info.push_str("Environment:\n");
+    if let Ok(path) = std::env::var("PATH") {
+        debug_info.push_str(&format!("Default PATH: {}\n", path));
+    } else {
+        debug_info.push_str("Default PATH: (not set)\n");
+    }
+
+    // Define extended PATH that we actually use
+    let extended_path = EXTENDED_PATH;
+    debug_info.push_str(&format!("Extended PATH used: {}\n\n", extended_path));
+
+    // Homebrew's standalone ccusage is preferred when present
+    match homebrew_ccusage(&RUNNER).await {
+        Some(homebrew) => debug_info.push_str(&format!(
+            "Using Homebrew ccusage {} at {}\n\n",
+            homebrew.version, homebrew.path
+        )),
+        None if cfg!(target_os = "macos") => debug_info.push_str("Homebrew ccusage: not installed\n\n"),
+        None => {}
+    }
+
+    // Test commands with extended PATH
+    debug_info.push_str("Command availability (with extended PATH):\n");
+
+    let commands_to_test = vec![
+        (format!("{} {} npx", extended_path, LOCATE_COMMAND), "npx location"),
+        (format!("{} {} node", extended_path, LOCATE_COMMAND), "node location"),
+        (format!("{} {} ccusage", extended_path, LOCATE_COMMAND), "ccusage location"),
+        (format!("{} npx --version", extended_path), "npx version"),
+        (format!("{} node --version", extended_path), "node version"),
+        (format!("{} ccusage --version 2>&1 || echo not found", extended_path), "ccusage version"),
+    ];
+
+    for (cmd, desc) in commands_to_test {
+        let (program, args) = shell_command(&cmd);
+        let output = Command::new(program)
+            .args(&args)
+            .output()
+            .await;
+
+        match output {
+            Ok(output) if output.status.success() => {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                debug_info.push_str(&format!("{}: {}\n", desc, stdout.trim()));
+            }
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                if stderr.trim().is_empty() {
+                    debug_info.push_str(&format!("{}: not found\n", desc));
+                } else {
+                    debug_info.push_str(&format!("{}: {}\n", desc, stderr.trim()));
+                }
+            }
+            Err(e) => {
+                debug_info.push_str(&format!("{}: error - {}\n", desc, e));
+            }
+        }
+    }
+
+    // Refresh timings
+    {
+        let timings = REFRESH_TIMINGS.lock().unwrap();
+        debug_info.push_str("\nRefresh timings:\n");
+        for (label, stats) in [("Refresh", &timings.refreshes), ("ccusage call", &timings.fetch_attempts)] {
+            match (stats.mean(), stats.p95()) {
+                (Some(mean), Some(p95)) => debug_info.push_str(&format!(
+                    "{}: avg {}, p95 {} (last {})\n",
+                    label,
+                    format_seconds(mean),
+                    format_seconds(p95),
+                    stats.samples.len()
+                )),
+                _ => debug_info.push_str(&format!("{}: no samples yet\n", label)),
+            }
+        }
+    }
+    if let Some(took) = NPX_DOWNLOADS.lock().unwrap().last_duration {
+        debug_info.push_str(&format!("Last npx download of ccusage: {}\n", format_seconds(took)));
+    }
+    {
+        let cache = SESSION_CACHE.lock().unwrap();
+        if let (Some(trigger), Some(updated)) = (cache.last_trigger, cache.last_updated) {
+            debug_info.push_str(&format!(
+                "Last refresh: {}, {} s ago\n",
+                trigger.label(),
+                updated.elapsed().as_secs()
+            ));
+        }
+    }
+
+    // Permissions
+    debug_info.push_str("\nPermissions:\n");
+    let availability = SESSION_CACHE.lock().unwrap().availability;
+    debug_info.push_str(&format!(
+        "Process spawning: {}\n",
+        if availability == Availability::Blocked { "blocked by system policy" } else { "allowed" }
+    ));
+    let data_access = match availability {
+        Availability::AccessDenied => "denied (grant Full Disk Access)",
+        Availability::NoData => "no usage logs found in the data directories",
+        _ => "ok",
+    };
+    debug_info.push_str(&format!("Claude data access: {}\n", data_access));
+    if let Some(dir) = settings_path().as_deref().and_then(Path::parent) {
+        debug_info.push_str(&format!("Config dir ({}): {}\n", dir.display(), probe_writable(dir)));
+    }
+    if let Some(dir) = cache_dir() {
+        debug_info.push_str(&format!("Cache dir ({}): {}\n", dir.display(), probe_writable(&dir)));
+    }
+    if let Some(dir) = std::env::var_os(CONFIG_DIR_ENV) {
+        debug_info.push_str(&format!("{} override: {}\n", CONFIG_DIR_ENV, dir.to_string_lossy()));
+    }
+
+    // Data directories
+    let data_dirs = configured_data_dirs(&current_settings());
+    if !data_dirs.kept.is_empty() || !data_dirs.ignored.is_empty() {
+        debug_info.push_str("\nData directories:\n");
+        for dir in &data_dirs.kept {
+            debug_info.push_str(&format!("{}\n", dir.display()));
+        }
+        for (dir, covered_by) in &data_dirs.ignored {
+            debug_info.push_str(&format!(
+                "ignored nested directory {} (covered by {})\n",
+                dir.display(),
+                covered_by.display()
+            ));
+        }
+    }
+
+    // Proxy settings, with credentials masked
+    let proxies = proxy_env(&current_settings());
+    if !proxies.is_empty() {
+        debug_info.push_str("\nProxy settings:\n");
+        for (name, value) in &proxies {
+            debug_info.push_str(&format!("{}: {}\n", name, mask_url_credentials(value)));
+        }
+    }
+
+    // Notifications, clipboard and dialogs as far as they've been used
+    debug_info.push_str("\nCapabilities:\n");
+    for capability in Capability::ALL {
+        debug_info.push_str(&format!("{}: {}\n", capability.label(), capability_state(capability).description()));
+    }
+    if tray_icon_failed() {
+        debug_info.push_str("Tray icon: couldn't be shown, using a text title\n");
+    }
+    if let Some(error) = SHORTCUT_ERROR.lock().unwrap().as_ref() {
+        debug_info.push_str(&format!("Copy-cost shortcut: failed, {}\n", error));
+    }
+
+    // Post-refresh command
+    let last_hook_result = LAST_HOOK_RESULT.lock().unwrap().clone();
+    if let Some(result) = last_hook_result {
+        debug_info.push_str(&format!("\nLast post-refresh command: {}\n", result));
+    }
+
+    // Recent alerts, which the tray icon's dot points at
+    let alerts = recent_alerts();
+    if !alerts.is_empty() {
+        debug_info.push_str("\nRecent alerts:\n");
+        for alert in alerts {
+            debug_info.push_str(&format!("  {}\n", alert));
+        }
+    }
+
+    // Last raw ccusage output, for when parsing breaks
+    let raw = LAST_RAW_RESPONSE.lock().unwrap().clone();
+    if let Some(raw) = raw {
+        debug_info.push_str(&raw_response_section(&raw));
+    }
+
+    // Test ccusage with extended PATH
+    debug_info.push_str("\nTesting ccusage:\n");
+    let (program, args) = shell_command(&format!("{} npx ccusage@latest --version", extended_path));
+    let ccusage_output = Command::new(program)
+        .args(&args)
+        .output()
+        .await;
+
+    match ccusage_output {
+        Ok(output) => {
+            if output.status.success() {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                debug_info.push_str(&format!("ccusage version: {}\n", stdout.trim()));
+            } else {
+                debug_info.push_str("ccusage: not available (npx ccusage@latest failed)\n");
+                if !output.stderr.is_empty() {
+                    debug_info.push_str(&format!("Error: {}\n", String::from_utf8_lossy(&output.stderr).trim()));
+                }
+            }
+        }
+        Err(e) => {
+            debug_info.push_str(&format!("Error executing ccusage: {}\n", e));
+        }
+    }
+
+    // Shown for copying into bug reports, so strip personal paths
+    redact_for_egress(&debug_info)
+}
@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindowBuilder};
+
+/// Label of the app window, which the capabilities in `capabilities/` grant to
+const MAIN_WINDOW: &str = "main";
+
+/// How the app presents itself besides the menubar item
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ActivationPolicy {
+    /// Menubar only: no dock icon and no window
+    #[default]
+    Accessory,
+    /// A dock icon and a main window, like a regular app
+    Regular,
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn native_policy(policy: ActivationPolicy) -> tauri::ActivationPolicy {
+    match policy {
+        ActivationPolicy::Accessory => tauri::ActivationPolicy::Accessory,
+        ActivationPolicy::Regular => tauri::ActivationPolicy::Regular,
+    }
+}
+
+/// Show the main window, creating it on first use
+pub(crate) fn show_main_window(app: &AppHandle) {
+    let window = match app.get_webview_window(MAIN_WINDOW) {
+        Some(window) => window,
+        None => {
+            let built = WebviewWindowBuilder::new(app, MAIN_WINDOW, WebviewUrl::App("index.html".into()))
+                .title("CCUsage")
+                .inner_size(480.0, 360.0)
+                .build();
+            match built {
+                Ok(window) => window,
+                Err(e) => {
+                    eprintln!("Failed to create main window: {}", e);
+                    return;
+                }
+            }
+        }
+    };
+    let _ = window.show();
+    let _ = window.set_focus();
+}
+
+/// Switch to `policy` while running: add or remove the dock icon, and show
+/// the main window for `Regular` or hide it for `Accessory`. Outside macOS
+/// there's no dock, so only the window changes.
+pub(crate) fn apply_activation_policy(app: &AppHandle, policy: ActivationPolicy) {
+    #[cfg(target_os = "macos")]
+    if let Err(e) = app.set_activation_policy(native_policy(policy)) {
+        eprintln!("Failed to set activation policy: {}", e);
+    }
+    match policy {
+        ActivationPolicy::Regular => show_main_window(app),
+        ActivationPolicy::Accessory => {
+            if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+                let _ = window.hide();
+            }
+        }
+    }
+}
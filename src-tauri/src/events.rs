@@ -0,0 +1,107 @@
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::model::BlockData;
+use crate::state::Availability;
+
+/// Event names, for frontends and plugins listening with `listen()`
+pub(crate) const SESSION_UPDATED: &str = "session-updated";
+pub(crate) const AVAILABILITY_CHANGED: &str = "availability-changed";
+pub(crate) const REFRESH_STARTED: &str = "refresh-started";
+pub(crate) const REFRESH_FINISHED: &str = "refresh-finished";
+pub(crate) const ALERT_FIRED: &str = "alert-fired";
+pub(crate) const SETTINGS_CHANGED: &str = "settings-changed";
+
+/// The active session after a successful refresh, `null` when there is none
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct SessionUpdated {
+    pub(crate) session: Option<BlockData>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub(crate) struct AvailabilityChanged {
+    pub(crate) from: Availability,
+    pub(crate) to: Availability,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct RefreshStarted {
+    /// What started it, e.g. "periodic" or "manual"
+    pub(crate) trigger: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct RefreshFinished {
+    pub(crate) trigger: &'static str,
+    pub(crate) duration_ms: u64,
+    /// Whether ccusage returned usable data
+    pub(crate) success: bool,
+}
+
+/// Which alert fired
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum AlertKind {
+    Budget,
+    SpendSpike,
+    OutputCap,
+    ModelAdded,
+    DailyCap,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct AlertFired {
+    pub(crate) kind: AlertKind,
+    /// The figure that set it off: the amount spent, the spike's cost or the
+    /// output tokens. `None` for a new model.
+    pub(crate) value: Option<f64>,
+    /// The notification text
+    pub(crate) message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct SettingsChanged {
+    /// Names of the settings that changed, as in settings.json
+    pub(crate) keys: Vec<String>,
+}
+
+/// Where events go: the app's listeners, or a recording in tests
+pub(crate) trait EventSink: Sync {
+    fn send(&self, event: &str, payload: serde_json::Value);
+}
+
+impl EventSink for AppHandle {
+    fn send(&self, event: &str, payload: serde_json::Value) {
+        if let Err(e) = self.emit(event, payload) {
+            eprintln!("Failed to emit {}: {}", event, e);
+        }
+    }
+}
+
+/// Every event sent, in order, as event name and JSON payload
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct RecordedEvents(pub(crate) std::sync::Mutex<Vec<(String, serde_json::Value)>>);
+
+#[cfg(test)]
+impl EventSink for RecordedEvents {
+    fn send(&self, event: &str, payload: serde_json::Value) {
+        self.0.lock().unwrap().push((event.to_string(), payload));
+    }
+}
+
+/// Send `payload` to every listener. These payloads always serialize, so a
+/// failure is just logged.
+pub(crate) fn emit<T: Serialize>(events: &dyn EventSink, event: &str, payload: T) {
+    match serde_json::to_value(payload) {
+        Ok(payload) => events.send(event, payload),
+        Err(e) => eprintln!("Failed to serialize {}: {}", event, e),
+    }
+}
+
+/// Emit `settings-changed` if any settings changed
+pub(crate) fn emit_settings_changed(events: &dyn EventSink, keys: Vec<String>) {
+    if !keys.is_empty() {
+        emit(events, SETTINGS_CHANGED, SettingsChanged { keys });
+    }
+}
@@ -0,0 +1,834 @@
+use chrono::Datelike;
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::process::{Output, Stdio};
+use std::sync::Mutex;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+
+use crate::clock::{Clock, CLOCK};
+use crate::datadirs::{ccusage_env, classify_empty_usage, usage_data_dirs, EmptyUsage};
+use crate::model::{parse_local_time, BlockData, BlocksResponse, DailyUsage};
+use crate::stats::{
+    block_last_end, daily_usage_from_blocks, daily_usage_in_window, exclude_models, previous_block, ModelExclusion,
+    PROJECTION_WINDOW_DAYS,
+};
+use crate::state::{
+    current_settings, AppSettings, Availability, RawResponse, LAST_RAW_RESPONSE, NPX_DOWNLOADS, REFRESH_TIMINGS,
+};
+
+/// PATH prefix covering the usual node/npm install locations, since apps
+/// launched from the desktop don't inherit the login shell's PATH
+#[cfg(target_os = "macos")]
+pub(crate) const EXTENDED_PATH: &str = "PATH=/usr/local/bin:/opt/homebrew/bin:/usr/bin:/bin:$HOME/.npm/bin:$HOME/.nvm/versions/node/*/bin:$HOME/.volta/bin:$PATH";
+#[cfg(all(unix, not(target_os = "macos")))]
+pub(crate) const EXTENDED_PATH: &str = "PATH=$HOME/.local/bin:/usr/local/bin:/usr/bin:/bin:$HOME/.npm-global/bin:$HOME/.npm/bin:$HOME/.nvm/versions/node/*/bin:$HOME/.volta/bin:$PATH";
+#[cfg(windows)]
+pub(crate) const EXTENDED_PATH: &str = r#"set "PATH=%APPDATA%\npm;%ProgramFiles%\nodejs;%LOCALAPPDATA%\Volta\bin;%PATH%" &&"#;
+
+/// Command used to locate executables on PATH
+#[cfg(windows)]
+pub(crate) const LOCATE_COMMAND: &str = "where";
+#[cfg(not(windows))]
+pub(crate) const LOCATE_COMMAND: &str = "which";
+
+/// Runs external programs. Fetching goes through this instead of spawning
+/// directly so the ccusage invocations can be swapped out.
+pub(crate) trait CommandRunner: Send + Sync {
+    fn output<'a>(
+        &'a self,
+        program: &'a str,
+        args: &'a [String],
+        envs: &'a [(String, String)],
+    ) -> Pin<Box<dyn Future<Output = io::Result<Output>> + Send + 'a>>;
+}
+
+pub(crate) struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn output<'a>(
+        &'a self,
+        program: &'a str,
+        args: &'a [String],
+        envs: &'a [(String, String)],
+    ) -> Pin<Box<dyn Future<Output = io::Result<Output>> + Send + 'a>> {
+        Box::pin(async move {
+            let mut child = Command::new(program)
+                .args(args)
+                .envs(envs.iter().cloned())
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                // Callers that give up on a command shouldn't leave it running
+                .kill_on_drop(true)
+                .spawn()?;
+            let mut stdout = child.stdout.take().expect("stdout is piped");
+            let stderr = child.stderr.take().expect("stderr is piped");
+            let read_stdout = async {
+                let mut buf = Vec::new();
+                stdout.read_to_end(&mut buf).await.map(|_| buf)
+            };
+            let _watch = DownloadWatch;
+            let read_stderr = read_stderr_watching_downloads(stderr, mark_download_started);
+            let (stdout, stderr) = tokio::try_join!(read_stdout, read_stderr)?;
+            let status = child.wait().await?;
+            Ok(Output { status, stdout, stderr })
+        })
+    }
+}
+
+/// Whether an stderr line from npx says it's downloading the package, e.g.
+/// "npm warn exec The following package was not found and will be installed: ccusage@15.9.7"
+/// or, from older npm, "Need to install the following packages:"
+pub(crate) fn is_npx_download_line(line: &str) -> bool {
+    let line = line.to_ascii_lowercase();
+    line.contains("will be installed") || line.contains("need to install the following packages")
+}
+
+/// Read all of `stderr`, calling `on_download` as soon as a line announces an
+/// npx download, so the menu can say so while the run is still going
+async fn read_stderr_watching_downloads(
+    stderr: impl AsyncRead + Unpin,
+    on_download: impl FnOnce(),
+) -> io::Result<Vec<u8>> {
+    let mut reader = BufReader::new(stderr);
+    let mut buf = Vec::new();
+    let mut on_download = Some(on_download);
+    loop {
+        let start = buf.len();
+        if reader.read_until(b'\n', &mut buf).await? == 0 {
+            return Ok(buf);
+        }
+        if on_download.is_some() && is_npx_download_line(&String::from_utf8_lossy(&buf[start..])) {
+            if let Some(on_download) = on_download.take() {
+                on_download();
+            }
+        }
+    }
+}
+
+fn mark_download_started() {
+    let mut downloads = NPX_DOWNLOADS.lock().unwrap();
+    if downloads.started.is_none() {
+        downloads.started = Some(CLOCK.instant());
+    }
+}
+
+/// Ends a download when its run finishes or is abandoned, recording how long it took
+struct DownloadWatch;
+
+impl Drop for DownloadWatch {
+    fn drop(&mut self) {
+        let mut downloads = NPX_DOWNLOADS.lock().unwrap();
+        if let Some(started) = downloads.started.take() {
+            let took = CLOCK.instant().saturating_duration_since(started);
+            eprintln!("npx downloaded ccusage in {:.1}s", took.as_secs_f64());
+            downloads.last_duration = Some(took);
+        }
+    }
+}
+
+pub(crate) static RUNNER: SystemRunner = SystemRunner;
+
+/// `sh -c <script>`, or `cmd /C <script>` on Windows
+pub(crate) fn shell_command(script: &str) -> (&'static str, Vec<String>) {
+    #[cfg(windows)]
+    {
+        ("cmd", vec!["/C".into(), script.into()])
+    }
+    #[cfg(not(windows))]
+    {
+        ("sh", vec!["-c".into(), script.into()])
+    }
+}
+
+/// Homebrew prefixes in the order to probe them: Apple Silicon installs to
+/// `/opt/homebrew`, Intel to `/usr/local`, and Rosetta setups can have both
+pub(crate) fn homebrew_prefixes(arch: &str) -> [&'static str; 2] {
+    if arch == "aarch64" {
+        ["/opt/homebrew", "/usr/local"]
+    } else {
+        ["/usr/local", "/opt/homebrew"]
+    }
+}
+
+/// A working ccusage binary installed by Homebrew
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct HomebrewCcusage {
+    pub(crate) path: String,
+    pub(crate) version: String,
+}
+
+/// Probe result, looked up once per launch
+static HOMEBREW_CCUSAGE: Mutex<Option<Option<HomebrewCcusage>>> = Mutex::new(None);
+
+/// The Homebrew ccusage, if one is installed and answers `--version`. It's
+/// self-contained, so it's preferred over anything that needs node.
+pub(crate) async fn homebrew_ccusage(runner: &dyn CommandRunner) -> Option<HomebrewCcusage> {
+    let probed = HOMEBREW_CCUSAGE.lock().unwrap().clone();
+    if let Some(probed) = probed {
+        return probed;
+    }
+    let mut found = None;
+    if cfg!(target_os = "macos") {
+        for prefix in homebrew_prefixes(std::env::consts::ARCH) {
+            let path = format!("{}/bin/ccusage", prefix);
+            if !std::path::Path::new(&path).exists() {
+                continue;
+            }
+            match runner.output(&path, &["--version".to_string()], &[]).await {
+                Ok(output) if output.status.success() => {
+                    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                    found = Some(HomebrewCcusage { path, version });
+                    break;
+                }
+                _ => eprintln!("Ignoring {}: it didn't answer --version", path),
+            }
+        }
+    }
+    *HOMEBREW_CCUSAGE.lock().unwrap() = Some(found.clone());
+    found
+}
+
+/// Every way of invoking `ccusage <args>` worth trying on this platform, most likely first
+fn command_variants(args: &[&str], homebrew: Option<&HomebrewCcusage>) -> Vec<(String, Vec<String>)> {
+    let joined = args.join(" ");
+    let npx_args: Vec<String> = std::iter::once("ccusage@latest")
+        .chain(args.iter().copied())
+        .map(String::from)
+        .collect();
+    let ccusage_args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+
+    let mut variants = Vec::new();
+    if let Some(homebrew) = homebrew {
+        variants.push((homebrew.path.clone(), ccusage_args.clone()));
+    }
+    variants.extend([
+        // Most likely to succeed: Try with explicit PATH that includes common npm locations
+        shell_command(&format!("{} npx ccusage@latest {}", EXTENDED_PATH, joined)),
+        // Try with explicit PATH for global ccusage
+        shell_command(&format!("{} ccusage {}", EXTENDED_PATH, joined)),
+        // Use shell to ensure proper PATH resolution (may work in dev environments)
+        shell_command(&format!("npx ccusage@latest {}", joined)),
+        // Try global ccusage if installed
+        shell_command(&format!("ccusage {}", joined)),
+    ]
+    .map(|(program, args)| (program.to_string(), args)));
+
+    #[cfg(windows)]
+    {
+        // PowerShell picks up the user's profile PATH changes that cmd may miss
+        variants.push((
+            "powershell".to_string(),
+            vec!["-NoProfile".into(), "-Command".into(), format!("npx.cmd ccusage@latest {}", joined)],
+        ));
+        // npm installs .cmd shims on Windows rather than bare executables
+        variants.push(("npx.cmd".to_string(), npx_args));
+        variants.push(("ccusage.cmd".to_string(), ccusage_args));
+    }
+    #[cfg(not(windows))]
+    {
+        // Try direct npx if in PATH
+        variants.push(("npx".to_string(), npx_args));
+        // Try direct ccusage command
+        variants.push(("ccusage".to_string(), ccusage_args));
+    }
+
+    variants
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FetchError {
+    /// No variant produced a usable response
+    Unavailable,
+    /// The system refused to spawn processes at all
+    Blocked,
+    /// ccusage ran but wasn't allowed to read Claude's data directory
+    AccessDenied,
+    /// ccusage returned no blocks and its data directories have no logs
+    NoData,
+}
+
+/// Whether a spawn failure means process execution is denied by policy rather
+/// than the command simply not existing
+fn is_spawn_blocked(error: &io::Error) -> bool {
+    // EPERM ("operation not permitted") and EACCES both surface as PermissionDenied
+    error.kind() == io::ErrorKind::PermissionDenied
+}
+
+/// Whether ccusage failed because it couldn't read Claude's data, as happens
+/// when macOS privacy controls (TCC) deny the app Full Disk Access
+fn is_access_denied_output(stderr: &str) -> bool {
+    ["EPERM", "EACCES", "Operation not permitted", "operation not permitted", "Permission denied"]
+        .iter()
+        .any(|marker| stderr.contains(marker))
+}
+
+/// Proxy and registry variables for npx, for users behind corporate proxies
+pub(crate) fn proxy_env(settings: &AppSettings) -> Vec<(String, String)> {
+    [
+        ("HTTP_PROXY", &settings.http_proxy),
+        ("HTTPS_PROXY", &settings.https_proxy),
+        ("NPM_CONFIG_REGISTRY", &settings.npm_registry),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| {
+        let value = value.as_deref().map(str::trim).filter(|value| !value.is_empty())?;
+        Some((name.to_string(), value.to_string()))
+    })
+    .collect()
+}
+
+/// What to log for a response that didn't parse: the error and only the start
+/// of the response kept in `raw`, since a long history runs to megabytes
+fn parse_failure_message(error: &serde_json::Error, raw: &RawResponse) -> String {
+    let cut = if raw.truncated { format!(" (first {} bytes)", raw.stdout.len()) } else { String::new() };
+    format!("Failed to parse ccusage response: {}\nResponse was{}: {}", error, cut, raw.stdout)
+}
+
+/// Run `ccusage <args>` trying each known way of locating it, returning the
+/// parsed JSON response from the first variant that succeeds
+pub(crate) async fn fetch_ccusage<T: DeserializeOwned>(
+    runner: &dyn CommandRunner,
+    args: &[&str],
+) -> Result<T, FetchError> {
+    let joined = args.join(" ");
+    let mut access_denied = false;
+    let settings = current_settings();
+    let mut envs = ccusage_env(&settings);
+    envs.extend(proxy_env(&settings));
+    let homebrew = homebrew_ccusage(runner).await;
+    // Index of the first variant that goes through the system shell
+    let first_shell_attempt = usize::from(homebrew.is_some());
+
+    for (attempt, (cmd, args)) in command_variants(args, homebrew.as_ref()).into_iter().enumerate() {
+        let started = CLOCK.instant();
+        let output = runner.output(&cmd, &args, &envs).await;
+        REFRESH_TIMINGS.lock().unwrap().fetch_attempts.push(CLOCK.instant() - started);
+
+        match output {
+            Ok(output) if output.status.success() => {
+                let raw = RawResponse::new(&joined, &output.stdout);
+
+                // Parse straight from the bytes; a long history can be tens of
+                // megabytes, not worth copying into a string first
+                let parsed = serde_json::from_slice::<T>(&output.stdout);
+                if let Err(e) = &parsed {
+                    eprintln!("{}", parse_failure_message(e, &raw));
+                }
+                *LAST_RAW_RESPONSE.lock().unwrap() = Some(raw);
+                match parsed {
+                    Ok(response) => return Ok(response),
+                    Err(_) => continue,
+                }
+            }
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                eprintln!("ccusage command failed with status: {}", output.status);
+                eprintln!("stderr: {}", stderr);
+                access_denied |= is_access_denied_output(&stderr);
+                continue;
+            }
+            Err(e) => {
+                eprintln!("Failed to execute command '{}': {}", cmd, e);
+                // A permission error running the system shell means no
+                // other variant can succeed either
+                if attempt == first_shell_attempt && is_spawn_blocked(&e) {
+                    eprintln!("Process execution appears to be blocked by system policy");
+                    return Err(FetchError::Blocked);
+                }
+                continue;
+            }
+        }
+    }
+
+    eprintln!("All attempts to run ccusage {} failed", joined);
+    if access_denied {
+        Err(FetchError::AccessDenied)
+    } else {
+        Err(FetchError::Unavailable)
+    }
+}
+
+impl From<FetchError> for Availability {
+    fn from(error: FetchError) -> Self {
+        match error {
+            FetchError::Blocked => Availability::Blocked,
+            FetchError::Unavailable => Availability::NotFound,
+            FetchError::AccessDenied => Availability::AccessDenied,
+            FetchError::NoData => Availability::NoData,
+        }
+    }
+}
+
+/// Just the active block, for one-off lookups like `--print`
+pub(crate) async fn fetch_session_data(runner: &dyn CommandRunner) -> (Option<BlockData>, Availability) {
+    match fetch_ccusage::<BlocksResponse>(runner, &["blocks", "--json", "--active"]).await {
+        // ccusage is working! Return the active block (if any)
+        Ok(response) => {
+            let mut active = response.blocks.into_iter().find(|block| block.is_active);
+            if let Some(block) = &mut active {
+                exclude_models(block, &current_settings().excluded_models);
+            }
+            (active, Availability::Available)
+        }
+        Err(e) => (None, e.into()),
+    }
+}
+
+/// Everything a refresh shows, derived from a single `ccusage blocks` run
+#[derive(Debug, Clone)]
+pub(crate) struct RecentUsage {
+    pub(crate) active_block: Option<BlockData>,
+    /// All blocks that started today (local time), including the active one
+    pub(crate) today_blocks: Vec<BlockData>,
+    /// Daily totals from `since` through today
+    pub(crate) recent_days: Vec<DailyUsage>,
+    /// `recent_days` counting only usage inside `counting_window`
+    pub(crate) window_days: Vec<DailyUsage>,
+    /// `recent_days` counting every model, for the lifetime total
+    pub(crate) unfiltered_days: Vec<DailyUsage>,
+    /// How `excluded_models` applied to the blocks
+    pub(crate) model_exclusion: ModelExclusion,
+    /// When Claude was last used, across all fetched blocks
+    pub(crate) last_activity: Option<chrono::DateTime<chrono::Local>>,
+    /// The block before the current one, if it ended recently
+    pub(crate) previous_block: Option<BlockData>,
+    pub(crate) since: chrono::NaiveDate,
+}
+
+/// `NoData` when ccusage returned no blocks because none of `dirs` hold any
+/// logs, rather than because nothing was used lately
+fn empty_usage_error(blocks: &[BlockData], dirs: &[PathBuf]) -> Option<FetchError> {
+    (blocks.is_empty() && classify_empty_usage(dirs) == EmptyUsage::NoLogs).then_some(FetchError::NoData)
+}
+
+/// Fetch every block since the earlier of the month start and the projection
+/// window in one ccusage run, and compute the active block, today's blocks and
+/// the daily totals from it locally rather than spawning ccusage for each
+pub(crate) async fn fetch_recent_usage(
+    runner: &dyn CommandRunner,
+    clock: &dyn Clock,
+) -> Result<RecentUsage, FetchError> {
+    let now = clock.now();
+    let today = now.date_naive();
+    let month_start = today.with_day(1).unwrap_or(today);
+    let since = month_start.min(today - chrono::Duration::days(PROJECTION_WINDOW_DAYS));
+    let since_arg = since.format("%Y%m%d").to_string();
+    let args = ["blocks", "--json", "--since", &since_arg];
+    let mut blocks = fetch_ccusage::<BlocksResponse>(runner, &args).await?.blocks;
+    let settings = current_settings();
+    if let Some(error) = empty_usage_error(&blocks, &usage_data_dirs(&settings)) {
+        return Err(error);
+    }
+
+    let unfiltered_days = daily_usage_from_blocks(&blocks);
+    let mut model_exclusion = ModelExclusion::NONE;
+    if !settings.excluded_models.is_empty() {
+        for block in &mut blocks {
+            model_exclusion = model_exclusion.merge(exclude_models(block, &settings.excluded_models));
+        }
+    }
+    let recent_days = daily_usage_from_blocks(&blocks);
+    let window_days = daily_usage_in_window(&blocks, &settings.counting_window);
+    let last_activity = blocks
+        .iter()
+        .filter(|block| !block.is_gap && block.cost_usd > 0.0)
+        .filter_map(block_last_end)
+        .max();
+    let active_block = blocks.iter().find(|block| block.is_active).cloned();
+    let previous_block = previous_block(&blocks, now).cloned();
+    let today_blocks = blocks
+        .into_iter()
+        .filter(|block| parse_local_time(&block.start_time).is_some_and(|start| start.date_naive() == today))
+        .collect();
+    Ok(RecentUsage {
+        active_block,
+        today_blocks,
+        recent_days,
+        window_days,
+        unfiltered_days,
+        model_exclusion,
+        last_activity,
+        previous_block,
+        since,
+    })
+}
+
+/// Run a shell command with `stdin_payload` on stdin, failing on a non-zero
+/// exit or when it outlives `timeout`
+pub(crate) async fn run_shell_with_input(
+    command: &str,
+    stdin_payload: &str,
+    timeout: std::time::Duration,
+) -> Result<(), String> {
+    let (program, args) = shell_command(command);
+    let mut child = Command::new(program)
+        .args(&args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("failed to start: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The command may not read stdin at all, so a broken pipe isn't an error
+        let _ = stdin.write_all(stdin_payload.as_bytes()).await;
+    }
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| format!("timed out after {}s", timeout.as_secs()))?
+        .map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// A `CommandRunner` that plays back canned results, one per run
+#[cfg(test)]
+pub(crate) mod fake {
+    use super::CommandRunner;
+    use std::collections::VecDeque;
+    use std::future::Future;
+    use std::io;
+    use std::pin::Pin;
+    use std::process::{ExitStatus, Output};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    pub(crate) struct FakeRunner {
+        results: Mutex<VecDeque<io::Result<Output>>>,
+        /// Program, arguments and environment of every run but `--version` probes
+        pub(crate) calls: Mutex<Vec<(String, Vec<String>, Vec<(String, String)>)>>,
+    }
+
+    impl FakeRunner {
+        pub(crate) fn new(results: impl IntoIterator<Item = io::Result<Output>>) -> Self {
+            Self { results: Mutex::new(results.into_iter().collect()), calls: Mutex::new(Vec::new()) }
+        }
+    }
+
+    fn exit_status(code: i32) -> ExitStatus {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            ExitStatus::from_raw(code << 8)
+        }
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::ExitStatusExt;
+            ExitStatus::from_raw(code as u32)
+        }
+    }
+
+    /// A run that exits 0 printing `stdout`
+    pub(crate) fn success(stdout: &str) -> io::Result<Output> {
+        Ok(Output { status: exit_status(0), stdout: stdout.as_bytes().to_vec(), stderr: Vec::new() })
+    }
+
+    /// A run that exits 1 printing `stderr`
+    pub(crate) fn failure(stderr: &str) -> io::Result<Output> {
+        Ok(Output { status: exit_status(1), stdout: Vec::new(), stderr: stderr.as_bytes().to_vec() })
+    }
+
+    /// A run that couldn't be started
+    pub(crate) fn spawn_error(kind: io::ErrorKind) -> io::Result<Output> {
+        Err(io::Error::from(kind))
+    }
+
+    impl CommandRunner for FakeRunner {
+        fn output<'a>(
+            &'a self,
+            program: &'a str,
+            args: &'a [String],
+            envs: &'a [(String, String)],
+        ) -> Pin<Box<dyn Future<Output = io::Result<Output>> + Send + 'a>> {
+            // Turn down the Homebrew probe so a real install never takes part
+            let result = if args == ["--version"] {
+                failure("")
+            } else {
+                self.calls.lock().unwrap().push((program.to_string(), args.to_vec(), envs.to_vec()));
+                self.results.lock().unwrap().pop_front().unwrap_or_else(|| spawn_error(io::ErrorKind::NotFound))
+            };
+            Box::pin(async move { result })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fake::{failure, spawn_error, success, FakeRunner};
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::model::fixtures::{block, local};
+
+    fn blocks_json(blocks: Vec<BlockData>) -> String {
+        serde_json::to_string(&BlocksResponse { blocks }).unwrap()
+    }
+
+    #[test]
+    fn today_blocks_follow_the_clock_past_midnight() {
+        let earlier = block(local(2025, 6, 10, 10, 0), local(2025, 6, 10, 15, 0), 3.0);
+        let mut late = block(local(2025, 6, 10, 22, 0), local(2025, 6, 11, 3, 0), 2.0);
+        late.is_active = true;
+        let json = blocks_json(vec![earlier, late]);
+        let runner = FakeRunner::new([success(&json), success(&json)]);
+        let clock = FakeClock::new(local(2025, 6, 10, 23, 30));
+
+        let usage = tauri::async_runtime::block_on(fetch_recent_usage(&runner, &clock)).unwrap();
+        assert_eq!(usage.today_blocks.len(), 2);
+        assert!(usage.active_block.is_some());
+
+        clock.advance(chrono::Duration::hours(1));
+        let usage = tauri::async_runtime::block_on(fetch_recent_usage(&runner, &clock)).unwrap();
+        // The late block stays with the day it started on
+        assert!(usage.today_blocks.is_empty());
+        assert!(usage.active_block.is_some());
+        assert_eq!(usage.recent_days.len(), 1);
+        assert_eq!(usage.recent_days[0].date, "2025-06-10");
+    }
+
+    #[test]
+    fn recent_usage_reaches_back_over_the_projection_window_early_in_the_month() {
+        let runner = FakeRunner::new([success(&blocks_json(Vec::new()))]);
+        let clock = FakeClock::new(local(2025, 6, 3, 12, 0));
+        // An empty response with no logs is NoData; the arguments are what's checked here
+        let _ = tauri::async_runtime::block_on(fetch_recent_usage(&runner, &clock));
+        let calls = runner.calls.lock().unwrap();
+        let args = calls[0].1.join(" ");
+        assert!(args.ends_with("blocks --json --since 20250520"), "{}", args);
+    }
+
+    #[test]
+    fn permission_errors_mean_spawning_is_blocked() {
+        assert!(is_spawn_blocked(&io::Error::from(io::ErrorKind::PermissionDenied)));
+        assert!(!is_spawn_blocked(&io::Error::from(io::ErrorKind::NotFound)));
+        assert!(!is_spawn_blocked(&io::Error::other("exec format error")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn eperm_and_eacces_from_spawn_mean_blocked() {
+        // EPERM and EACCES
+        assert!(is_spawn_blocked(&io::Error::from_raw_os_error(1)));
+        assert!(is_spawn_blocked(&io::Error::from_raw_os_error(13)));
+        // ENOENT
+        assert!(!is_spawn_blocked(&io::Error::from_raw_os_error(2)));
+    }
+
+    #[test]
+    fn access_denied_is_recognized_in_ccusage_stderr() {
+        assert!(is_access_denied_output("Error: EPERM: operation not permitted, scandir '/Users/me/.claude'"));
+        assert!(is_access_denied_output("EACCES: permission denied"));
+        assert!(is_access_denied_output("ls: .claude: Permission denied"));
+        assert!(!is_access_denied_output("sh: ccusage: command not found"));
+        assert!(!is_access_denied_output(""));
+    }
+
+    fn fetch_blocks(runner: &FakeRunner) -> Result<BlocksResponse, FetchError> {
+        tauri::async_runtime::block_on(fetch_ccusage::<BlocksResponse>(runner, &["blocks", "--json"]))
+    }
+
+    #[test]
+    fn fetch_tries_variants_until_one_parses() {
+        let json = blocks_json(vec![block(local(2025, 6, 11, 9, 0), local(2025, 6, 11, 14, 0), 1.0)]);
+        let runner = FakeRunner::new([failure("npx: command not found"), success("not json"), success(&json)]);
+        let response = fetch_blocks(&runner).unwrap();
+        assert_eq!(response.blocks.len(), 1);
+        assert_eq!(runner.calls.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn fetch_passes_the_arguments_to_every_variant() {
+        let runner = FakeRunner::new([]);
+        let _ = fetch_blocks(&runner);
+        let calls = runner.calls.lock().unwrap();
+        assert!(calls.len() > 1);
+        assert!(calls.iter().all(|(_, args, _)| args.join(" ").ends_with("blocks --json")));
+    }
+
+    #[test]
+    fn fetch_stops_when_the_shell_cannot_be_spawned() {
+        let runner = FakeRunner::new([spawn_error(io::ErrorKind::PermissionDenied)]);
+        assert_eq!(fetch_blocks(&runner).err(), Some(FetchError::Blocked));
+        assert_eq!(runner.calls.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn a_later_permission_error_is_not_blocked() {
+        let runner =
+            FakeRunner::new([spawn_error(io::ErrorKind::NotFound), spawn_error(io::ErrorKind::PermissionDenied)]);
+        assert_eq!(fetch_blocks(&runner).err(), Some(FetchError::Unavailable));
+        assert!(runner.calls.lock().unwrap().len() > 2);
+    }
+
+    #[test]
+    fn fetch_reports_denied_data_access() {
+        let runner = FakeRunner::new([failure("Error: EPERM: operation not permitted, scandir '/Users/me/.claude'")]);
+        assert_eq!(fetch_blocks(&runner).err(), Some(FetchError::AccessDenied));
+    }
+
+    #[test]
+    fn fetch_is_unavailable_when_nothing_runs() {
+        let runner = FakeRunner::new([]);
+        assert_eq!(fetch_blocks(&runner).err(), Some(FetchError::Unavailable));
+    }
+
+    #[test]
+    fn homebrew_prefixes_follow_the_architecture() {
+        assert_eq!(homebrew_prefixes("aarch64"), ["/opt/homebrew", "/usr/local"]);
+        assert_eq!(homebrew_prefixes("x86_64"), ["/usr/local", "/opt/homebrew"]);
+    }
+
+    #[test]
+    fn homebrew_ccusage_is_tried_before_npx() {
+        let homebrew =
+            HomebrewCcusage { path: "/opt/homebrew/bin/ccusage".to_string(), version: "15.2.0".to_string() };
+        let with_homebrew = command_variants(&["blocks", "--json"], Some(&homebrew));
+        let without = command_variants(&["blocks", "--json"], None);
+        assert_eq!(with_homebrew[0], (homebrew.path.clone(), vec!["blocks".to_string(), "--json".to_string()]));
+        assert_eq!(with_homebrew[1..], without[..]);
+    }
+
+    #[test]
+    fn without_homebrew_the_extended_path_npx_goes_first() {
+        let variants = command_variants(&["blocks"], None);
+        let (program, args) = shell_command(&format!("{} npx ccusage@latest blocks", EXTENDED_PATH));
+        assert_eq!(variants[0], (program.to_string(), args));
+    }
+
+    #[test]
+    fn an_empty_response_without_logs_means_no_data() {
+        let root = crate::state::TestDir::new("empty-response");
+        let claude = root.path().join("claude");
+        let empty: BlocksResponse = serde_json::from_str(r#"{"blocks":[]}"#).unwrap();
+        assert_eq!(empty_usage_error(&empty.blocks, std::slice::from_ref(&claude)), Some(FetchError::NoData));
+
+        let log = claude.join("projects").join("api").join("a.jsonl");
+        std::fs::create_dir_all(log.parent().unwrap()).unwrap();
+        std::fs::write(&log, "{}\n").unwrap();
+        assert_eq!(empty_usage_error(&empty.blocks, std::slice::from_ref(&claude)), None);
+    }
+
+    #[test]
+    fn a_response_with_blocks_is_never_no_data() {
+        let blocks = vec![block(local(2025, 6, 11, 9, 0), local(2025, 6, 11, 14, 0), 1.0)];
+        let response: BlocksResponse = serde_json::from_str(&blocks_json(blocks)).unwrap();
+        assert_eq!(empty_usage_error(&response.blocks, &[]), None);
+    }
+
+    /// Feed `stderr` to the download watcher, returning what it read and how
+    /// many times it reported a download
+    fn watch(stderr: &[u8]) -> (Vec<u8>, usize) {
+        let mut announced = 0;
+        let read = tauri::async_runtime::block_on(read_stderr_watching_downloads(stderr, || announced += 1));
+        (read.unwrap(), announced)
+    }
+
+    #[test]
+    fn npx_download_announcements_are_recognised() {
+        let npm10 = b"npm warn exec The following package was not found and will be installed: ccusage@15.9.7\n";
+        let npm8 = b"npm WARN exec The following package was not found and will be installed: ccusage@15.2.0\n";
+        let npm6 = b"Need to install the following packages:\n  ccusage@15.9.7\nOk to proceed? (y) ";
+        for stderr in [&npm10[..], &npm8[..], &npm6[..]] {
+            assert_eq!(watch(stderr), (stderr.to_vec(), 1), "{}", String::from_utf8_lossy(stderr));
+        }
+    }
+
+    #[test]
+    fn ordinary_stderr_is_not_a_download() {
+        let stderr = b"npm warn config production Use `--omit=dev` instead.\r\n\
+            (node:4242) ExperimentalWarning: The Fetch API is an experimental feature.\n\
+            [ccusage] \xe2\x9d\x8c No valid Claude data directories found";
+        assert_eq!(watch(stderr), (stderr.to_vec(), 0));
+        assert_eq!(watch(b""), (Vec::new(), 0));
+    }
+
+    #[test]
+    fn a_download_is_reported_once_among_other_output() {
+        let stderr = b"npm warn config production Use `--omit=dev` instead.\n\
+            npm warn exec The following package was not found and will be installed: ccusage@15.9.7\r\n\
+            Need to install the following packages:\n\
+            npm warn deprecated some-dependency@1.0.0: no longer supported";
+        assert_eq!(watch(stderr), (stderr.to_vec(), 1));
+    }
+
+    #[test]
+    fn a_download_is_reported_while_npx_is_still_running() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        let announced = &AtomicBool::new(false);
+        let (mut npx, stderr) = tokio::io::duplex(64);
+        let run = async move {
+            npx.write_all(b"npm warn exec The following package was not found and will be installed: ccusage@15.9.7\n")
+                .await
+                .unwrap();
+            // The download takes a while; the menu should know about it meanwhile
+            for _ in 0..1000 {
+                if announced.load(Ordering::Relaxed) {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            }
+            assert!(announced.load(Ordering::Relaxed));
+            npx.write_all(b"done\n").await.unwrap();
+        };
+        let read = read_stderr_watching_downloads(stderr, || announced.store(true, Ordering::Relaxed));
+        let (read, ()) = tauri::async_runtime::block_on(async { tokio::join!(read, run) });
+        assert!(read.unwrap().ends_with(b"done\n"));
+    }
+
+    #[test]
+    fn responses_larger_than_the_kept_raw_response_parse_in_full() {
+        let blocks: Vec<BlockData> = (0..3000)
+            .map(|n| {
+                let start = local(2025, 6, 1, 0, 0) + chrono::Duration::hours(n);
+                let mut spanning = block(start, start + chrono::Duration::hours(5), 0.25);
+                spanning.models = vec!["claude-opus-4-20250514".to_string(), "modèle-été".to_string()];
+                spanning
+            })
+            .collect();
+        let json = blocks_json(blocks);
+        assert!(json.len() > crate::state::RAW_RESPONSE_LIMIT);
+
+        let response = fetch_blocks(&FakeRunner::new([success(&json)])).unwrap();
+        assert_eq!(response.blocks.len(), 3000);
+        assert_eq!(response.blocks[2999].models[1], "modèle-été");
+    }
+
+    #[test]
+    fn a_large_response_that_fails_to_parse_logs_only_its_start() {
+        // Megabytes, like a long history, cut off before it closes
+        let blocks = (0..60_000)
+            .map(|n| {
+                let start = local(2025, 6, 1, 0, 0) + chrono::Duration::hours(n);
+                block(start, start + chrono::Duration::hours(5), 0.25)
+            })
+            .collect();
+        let mut json = blocks_json(blocks);
+        json.pop();
+        assert!(json.len() > 10 * crate::state::RAW_RESPONSE_LIMIT);
+
+        let error = serde_json::from_str::<BlocksResponse>(&json).unwrap_err();
+        let message = parse_failure_message(&error, &RawResponse::new("blocks --json", json.as_bytes()));
+        assert!(message.len() < crate::state::RAW_RESPONSE_LIMIT + 200);
+        assert!(message.contains(&format!("(first {} bytes)", crate::state::RAW_RESPONSE_LIMIT)));
+        assert!(fetch_blocks(&FakeRunner::new([success(&json)])).is_err());
+    }
+
+    #[test]
+    fn a_small_response_that_fails_to_parse_is_logged_whole() {
+        let error = serde_json::from_str::<BlocksResponse>("not json").unwrap_err();
+        let message = parse_failure_message(&error, &RawResponse::new("blocks --json", b"not json"));
+        assert!(message.ends_with("Response was: not json"));
+    }
+}
+
@@ -0,0 +1,38 @@
+use std::sync::Mutex;
+
+use crate::state::SESSION_CACHE;
+use crate::stats::total_cost;
+
+/// Today's spend when the menu was last opened
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Glance {
+    pub(crate) date: chrono::NaiveDate,
+    pub(crate) today_total: f64,
+}
+
+static LAST_GLANCE: Mutex<Option<Glance>> = Mutex::new(None);
+
+/// Spend between the previous menu open and this one, shown until the next open
+pub(crate) static SINCE_LAST_LOOKED: Mutex<Option<f64>> = Mutex::new(None);
+
+/// Spend since `previous`. Today's total already spans block changes; across
+/// midnight it restarts, so everything since midnight is counted instead.
+/// `None` on the first open.
+pub(crate) fn spend_since(previous: Option<Glance>, current: Glance) -> Option<f64> {
+    let previous = previous?;
+    let delta = if previous.date == current.date {
+        current.today_total - previous.today_total
+    } else {
+        current.today_total
+    };
+    Some(delta.max(0.0))
+}
+
+/// Snapshot today's spend as the menu opens, keeping the change since the
+/// previous open for the menu to show
+pub(crate) fn record_menu_open(now: chrono::DateTime<chrono::Local>) {
+    let today_total = total_cost(&SESSION_CACHE.lock().unwrap().today_blocks, true);
+    let current = Glance { date: now.date_naive(), today_total };
+    let previous = LAST_GLANCE.lock().unwrap().replace(current);
+    *SINCE_LAST_LOOKED.lock().unwrap() = spend_since(previous, current);
+}
@@ -0,0 +1,57 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::clock::{Clock, CLOCK};
+use crate::fetch::{fetch_ccusage, RUNNER};
+use crate::model::DailyResponse;
+use crate::stats::heatmap_levels;
+
+/// Longest range `get_heatmap` serves, a year of weeks
+const MAX_HEATMAP_WEEKS: u32 = 53;
+
+/// One day of the dashboard's contribution heatmap
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct HeatmapDay {
+    /// Local date, `YYYY-MM-DD`
+    pub(crate) date: String,
+    pub(crate) cost: f64,
+    pub(crate) tokens: u64,
+    /// Intensity 0–4 relative to the other days in the range
+    pub(crate) level: u8,
+}
+
+/// Daily spend for the last `weeks` weeks up to and including today, oldest
+/// first, with days ccusage has no usage for filled in as zero. Reads the
+/// daily history only; the session cache is left alone.
+#[tauri::command]
+pub(crate) async fn get_heatmap(weeks: u32) -> Result<Vec<HeatmapDay>, String> {
+    let weeks = weeks.clamp(1, MAX_HEATMAP_WEEKS);
+    let today = CLOCK.now().date_naive();
+    let first = today - chrono::Duration::days(i64::from(weeks) * 7 - 1);
+    let since = first.format("%Y%m%d").to_string();
+    let response = fetch_ccusage::<DailyResponse>(&RUNNER, &["daily", "--json", "--since", &since])
+        .await
+        .map_err(|e| format!("Failed to load daily usage: {:?}", e))?;
+
+    let by_date: HashMap<&str, (f64, u64)> = response
+        .daily
+        .iter()
+        .map(|day| (day.date.as_str(), (day.total_cost, day.total_tokens)))
+        .collect();
+    let days: Vec<(String, f64, u64)> = first
+        .iter_days()
+        .take_while(|date| *date <= today)
+        .map(|date| {
+            let date = date.format("%Y-%m-%d").to_string();
+            let (cost, tokens) = by_date.get(date.as_str()).copied().unwrap_or_default();
+            (date, cost, tokens)
+        })
+        .collect();
+
+    let costs: Vec<f64> = days.iter().map(|(_, cost, _)| *cost).collect();
+    Ok(days
+        .into_iter()
+        .zip(heatmap_levels(&costs))
+        .map(|((date, cost, tokens), level)| HeatmapDay { date, cost, tokens, level })
+        .collect())
+}
@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::fetch::{shell_command, CommandRunner, RUNNER};
+use crate::model::BlockData;
+use crate::state::current_settings;
+
+/// How long an event hook may run before it's killed
+const HOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Alerts a user command can be attached to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HookEvent {
+    BudgetExceeded,
+    SpendSpike,
+}
+
+impl HookEvent {
+    /// Value of `CCUSAGE_EVENT`
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            HookEvent::BudgetExceeded => "budget_exceeded",
+            HookEvent::SpendSpike => "spend_spike",
+        }
+    }
+}
+
+/// Shell commands run when an alert fires, one per event; unset ones do nothing
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct EventHooks {
+    pub(crate) budget_exceeded: Option<String>,
+    pub(crate) spend_spike: Option<String>,
+}
+
+impl EventHooks {
+    fn command(&self, event: HookEvent) -> Option<&str> {
+        let command = match event {
+            HookEvent::BudgetExceeded => &self.budget_exceeded,
+            HookEvent::SpendSpike => &self.spend_spike,
+        };
+        command.as_deref().filter(|command| !command.trim().is_empty())
+    }
+}
+
+/// Event details passed to the hook as environment variables. Previews with
+/// sample data also get `CCUSAGE_PREVIEW=1`, so hooks can tell them apart.
+pub(crate) fn hook_env(event: HookEvent, block: &BlockData, preview: bool) -> Vec<(String, String)> {
+    let mut env = vec![
+        ("CCUSAGE_EVENT".to_string(), event.name().to_string()),
+        ("CCUSAGE_COST".to_string(), format!("{:.2}", block.cost_usd)),
+        ("CCUSAGE_BLOCK_ID".to_string(), block.id.clone()),
+    ];
+    if preview {
+        env.push(("CCUSAGE_PREVIEW".to_string(), "1".to_string()));
+    }
+    env
+}
+
+/// Run `command` for `event`, logging its output. Failures and timeouts are
+/// only logged, never passed on.
+pub(crate) async fn run_event_hook(
+    runner: &dyn CommandRunner,
+    command: &str,
+    event: HookEvent,
+    block: &BlockData,
+    preview: bool,
+) {
+    let (program, args) = shell_command(command);
+    let envs = hook_env(event, block, preview);
+    match tokio::time::timeout(HOOK_TIMEOUT, runner.output(program, &args, &envs)).await {
+        Ok(Ok(output)) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            eprintln!(
+                "{} hook exited with {}\nstdout: {}\nstderr: {}",
+                event.name(),
+                output.status,
+                stdout.trim(),
+                stderr.trim()
+            );
+        }
+        Ok(Err(e)) => eprintln!("{} hook failed to start: {}", event.name(), e),
+        Err(_) => eprintln!("{} hook timed out after {} s", event.name(), HOOK_TIMEOUT.as_secs()),
+    }
+}
+
+/// Run the user's hook for `event` in the background, if one is set
+pub(crate) fn fire_event_hook(event: HookEvent, block: &BlockData, preview: bool) {
+    let Some(command) = current_settings().event_hooks.command(event).map(str::to_string) else {
+        return;
+    };
+    let block = block.clone();
+    tauri::async_runtime::spawn(async move {
+        run_event_hook(&RUNNER, &command, event, &block, preview).await;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch::fake::{failure, spawn_error, success, FakeRunner};
+    use crate::model::fixtures::{block, local};
+
+    fn session() -> BlockData {
+        block(local(2025, 6, 11, 10, 0), local(2025, 6, 11, 15, 0), 12.3)
+    }
+
+    fn env(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    fn run(runner: &FakeRunner, event: HookEvent, preview: bool) {
+        tauri::async_runtime::block_on(run_event_hook(runner, "notify-me", event, &session(), preview));
+    }
+
+    #[test]
+    fn each_event_passes_its_details_in_the_environment() {
+        let id = session().id;
+        for event in [HookEvent::BudgetExceeded, HookEvent::SpendSpike] {
+            let runner = FakeRunner::new([success("")]);
+            run(&runner, event, false);
+            let (program, args) = shell_command("notify-me");
+            let expected =
+                env(&[("CCUSAGE_EVENT", event.name()), ("CCUSAGE_COST", "12.30"), ("CCUSAGE_BLOCK_ID", id.as_str())]);
+            assert_eq!(*runner.calls.lock().unwrap(), [(program.to_string(), args, expected)]);
+        }
+    }
+
+    #[test]
+    fn previews_are_marked_in_the_environment() {
+        let runner = FakeRunner::new([success("")]);
+        run(&runner, HookEvent::SpendSpike, true);
+        let calls = runner.calls.lock().unwrap();
+        assert_eq!(calls[0].2.last(), Some(&("CCUSAGE_PREVIEW".to_string(), "1".to_string())));
+    }
+
+    #[test]
+    fn failing_hooks_are_only_logged() {
+        let runner = FakeRunner::new([failure("no such light"), spawn_error(std::io::ErrorKind::PermissionDenied)]);
+        run(&runner, HookEvent::BudgetExceeded, false);
+        run(&runner, HookEvent::BudgetExceeded, false);
+        assert_eq!(runner.calls.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn blank_commands_are_unset() {
+        let hooks = EventHooks { budget_exceeded: Some("  ".to_string()), spend_spike: Some("say hi".to_string()) };
+        assert_eq!(hooks.command(HookEvent::BudgetExceeded), None);
+        assert_eq!(hooks.command(HookEvent::SpendSpike), Some("say hi"));
+    }
+}
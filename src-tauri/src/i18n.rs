@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use crate::state::AppSettings;
+
+/// Language used for keys a locale doesn't translate
+const FALLBACK_LANGUAGE: &str = "en";
+
+/// Bundled locale files, flat maps from message key to text. Text may contain
+/// `{name}` placeholders, filled in by `Translator::fill`.
+const LOCALE_FILES: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.json")),
+    ("de", include_str!("../locales/de.json")),
+    ("ja", include_str!("../locales/ja.json")),
+];
+
+type Messages = HashMap<String, String>;
+
+static LOCALES: LazyLock<HashMap<&'static str, Messages>> = LazyLock::new(|| {
+    LOCALE_FILES
+        .iter()
+        .filter_map(|(language, json)| match serde_json::from_str(json) {
+            Ok(messages) => Some((*language, messages)),
+            Err(e) => {
+                eprintln!("Bundled locale {} is invalid: {}", language, e);
+                None
+            }
+        })
+        .collect()
+});
+
+/// Two-letter language code from a locale name such as "de_DE.UTF-8", "de-AT" or "ja"
+fn language_code(locale: &str) -> Option<String> {
+    let code: String = locale.chars().take_while(|c| c.is_ascii_alphabetic()).collect();
+    (code.len() == 2).then(|| code.to_ascii_lowercase())
+}
+
+/// The system's language, from the usual locale variables or, for apps
+/// started from Finder where those are unset, the macOS preference
+static SYSTEM_LANGUAGE: LazyLock<Option<String>> = LazyLock::new(|| {
+    let from_env = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty() && value != "C" && value != "POSIX");
+    if let Some(locale) = from_env {
+        return language_code(&locale);
+    }
+    #[cfg(target_os = "macos")]
+    if let Ok(output) = std::process::Command::new("defaults").args(["read", "-g", "AppleLocale"]).output() {
+        return language_code(String::from_utf8_lossy(&output.stdout).trim());
+    }
+    None
+});
+
+/// Looks up menu text in the chosen language, falling back to English for
+/// keys it lacks
+pub(crate) struct Translator {
+    messages: Option<&'static Messages>,
+}
+
+impl Translator {
+    /// The `language` setting if it's bundled, else the system language, else English
+    pub(crate) fn new(settings: &AppSettings) -> Self {
+        let chosen = settings.language.as_deref().and_then(language_code).or_else(|| SYSTEM_LANGUAGE.clone());
+        Self { messages: chosen.and_then(|language| LOCALES.get(language.as_str())) }
+    }
+
+    pub(crate) fn tr(&self, key: &'static str) -> &'static str {
+        self.messages
+            .and_then(|messages| messages.get(key))
+            .or_else(|| LOCALES.get(FALLBACK_LANGUAGE).and_then(|messages| messages.get(key)))
+            .map_or(key, String::as_str)
+    }
+
+    /// Text for `key` with each `{name}` replaced by its value from `args`
+    pub(crate) fn fill(&self, key: &'static str, args: &[(&str, &str)]) -> String {
+        args.iter()
+            .fold(self.tr(key).to_string(), |text, (name, value)| text.replace(&format!("{{{}}}", name), value))
+    }
+}
@@ -0,0 +1,306 @@
+use std::sync::Mutex;
+use tauri::image::Image;
+
+use crate::datadirs::expand_home;
+use crate::state::current_settings;
+
+/// Side length of the drawn tray icon in points
+const ICON_SIZE: u32 = 16;
+
+/// Pixels per point the tray icon is drawn at, so it stays sharp on Retina displays
+pub(crate) const ICON_SCALE: u32 = 2;
+
+/// Opaque black; macOS tints template icons to match the menubar
+const INK: [u8; 4] = [0, 0, 0, 255];
+
+/// Vessel outline, in points
+const VESSEL_LEFT: u32 = 4;
+const VESSEL_RIGHT: u32 = 12;
+const VESSEL_TOP: u32 = 1;
+const VESSEL_BOTTOM: u32 = 15;
+
+fn fill_rect(pixels: &mut [u8], size: u32, (left, top, right, bottom): (u32, u32, u32, u32)) {
+    for y in top..bottom {
+        for x in left..right {
+            let offset = ((y * size + x) * 4) as usize;
+            pixels[offset..offset + 4].copy_from_slice(&INK);
+        }
+    }
+}
+
+/// RGBA pixels of the hourglass icon, `ICON_SIZE * scale` pixels square: an
+/// outlined vessel filled from the bottom in proportion to `remaining`
+/// (0.0–1.0), or left empty with a dot in the middle when there's no session.
+/// Only integer arithmetic is used, so the output is identical across machines.
+pub(crate) fn render_hourglass(remaining: Option<f64>, scale: u32) -> Vec<u8> {
+    let size = ICON_SIZE * scale;
+    let mut pixels = vec![0; (size * size * 4) as usize];
+
+    let (left, top) = (VESSEL_LEFT * scale, VESSEL_TOP * scale);
+    let (right, bottom) = (VESSEL_RIGHT * scale, VESSEL_BOTTOM * scale);
+    let stroke = scale;
+    fill_rect(&mut pixels, size, (left, top, right, top + stroke));
+    fill_rect(&mut pixels, size, (left, bottom - stroke, right, bottom));
+    fill_rect(&mut pixels, size, (left, top, left + stroke, bottom));
+    fill_rect(&mut pixels, size, (right - stroke, top, right, bottom));
+
+    match remaining {
+        Some(fraction) => {
+            // Leave a one-point gap between the outline and the fill
+            let (inner_left, inner_top, inner_right, inner_bottom) =
+                (left + 2 * stroke, top + 2 * stroke, right - 2 * stroke, bottom - 2 * stroke);
+            let inner_height = inner_bottom - inner_top;
+            let filled = (fraction.clamp(0.0, 1.0) * inner_height as f64).round() as u32;
+            fill_rect(&mut pixels, size, (inner_left, inner_bottom - filled, inner_right, inner_bottom));
+        }
+        None => {
+            let middle = size / 2;
+            fill_rect(&mut pixels, size, (middle - scale, middle - scale, middle + scale, middle + scale));
+        }
+    }
+
+    pixels
+}
+
+pub(crate) fn hourglass_icon(remaining: Option<f64>) -> Image<'static> {
+    let size = ICON_SIZE * ICON_SCALE;
+    Image::new_owned(render_hourglass(remaining, ICON_SCALE), size, size)
+}
+
+/// Diameter of the unread-alert dot, in points
+const BADGE_DIAMETER: u32 = 4;
+
+/// Draw the unread-alert dot in the top-right corner of `pixels`, an RGBA
+/// image `width` by `height` pixels at `scale` pixels per point. A one-point
+/// transparent ring is cleared around the dot, so it stays distinct from the
+/// icon beneath when macOS tints a template icon to a single color.
+pub(crate) fn overlay_badge(pixels: &mut [u8], width: u32, height: u32, scale: u32) {
+    let radius = BADGE_DIAMETER * scale / 2;
+    let ring = radius + scale;
+    // Work in doubled coordinates so pixel centers fall on integers
+    let (center_x, center_y) = (2 * i64::from(width) - 2 * i64::from(ring), 2 * i64::from(ring));
+    let (dot, clear) = (4 * i64::from(radius).pow(2), 4 * i64::from(ring).pow(2));
+    for y in 0..(2 * ring).min(height) {
+        for x in width.saturating_sub(2 * ring)..width {
+            let dx = 2 * i64::from(x) + 1 - center_x;
+            let dy = 2 * i64::from(y) + 1 - center_y;
+            let distance = dx * dx + dy * dy;
+            let offset = ((y * width + x) * 4) as usize;
+            if distance <= dot {
+                pixels[offset..offset + 4].copy_from_slice(&INK);
+            } else if distance <= clear {
+                pixels[offset..offset + 4].copy_from_slice(&[0; 4]);
+            }
+        }
+    }
+}
+
+/// `image` with the unread-alert dot on it, at whatever size the icon is
+pub(crate) fn with_badge(image: Image<'_>) -> Image<'static> {
+    let (width, height) = (image.width(), image.height());
+    let mut pixels = image.rgba().to_vec();
+    overlay_badge(&mut pixels, width, height, (width / ICON_SIZE).max(1));
+    Image::new_owned(pixels, width, height)
+}
+
+/// Bars of the drawn fallback icon as (left, top, right) in points, all reaching down to `VESSEL_BOTTOM`
+const FALLBACK_BARS: [(u32, u32, u32); 3] = [(2, 9, 5), (7, 5, 10), (12, 1, 15)];
+
+/// RGBA pixels of a plain bar chart, `ICON_SIZE * scale` pixels square, used
+/// when the bundled icon can't be decoded
+pub(crate) fn render_bars(scale: u32) -> Vec<u8> {
+    let size = ICON_SIZE * scale;
+    let mut pixels = vec![0; (size * size * 4) as usize];
+    for (left, top, right) in FALLBACK_BARS {
+        fill_rect(&mut pixels, size, (left * scale, top * scale, right * scale, VESSEL_BOTTOM * scale));
+    }
+    pixels
+}
+
+/// Whether `image` would show nothing: no pixels, or none that aren't fully transparent
+pub(crate) fn is_blank(image: &Image) -> bool {
+    image.rgba().chunks_exact(4).all(|pixel| pixel[3] == 0)
+}
+
+fn bundled_icon() -> Image<'static> {
+    match Image::from_bytes(include_bytes!("../icons/bars.png")) {
+        Ok(image) => image.to_owned(),
+        Err(e) => {
+            eprintln!("Bundled tray icon is invalid, drawing a plain one: {}", e);
+            let size = ICON_SIZE * ICON_SCALE;
+            Image::new_owned(render_bars(ICON_SCALE), size, size)
+        }
+    }
+}
+
+fn load_icon(custom_path: Option<&str>) -> Image<'static> {
+    let Some(path) = custom_path else {
+        return bundled_icon();
+    };
+    match Image::from_path(expand_home(path)) {
+        Ok(image) => image,
+        Err(e) => {
+            eprintln!("Couldn't load custom icon {}, using the default: {}", path, e);
+            bundled_icon()
+        }
+    }
+}
+
+/// The icon last loaded and the `custom_icon_path` it was loaded for, so the
+/// file is only read, and failures only logged, when the setting changes
+static LOADED_ICON: Mutex<Option<(Option<String>, Image<'static>)>> = Mutex::new(None);
+
+/// The regular tray icon: the user's `custom_icon_path` if it loads, else the bundled bar chart
+pub(crate) fn default_icon() -> Image<'static> {
+    let custom_path = current_settings().custom_icon_path.filter(|path| !path.trim().is_empty());
+    let mut loaded = LOADED_ICON.lock().unwrap();
+    if let Some((path, image)) = loaded.as_ref() {
+        if *path == custom_path {
+            return image.clone();
+        }
+    }
+    let image = load_icon(custom_path.as_deref());
+    *loaded = Some((custom_path, image.clone()));
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// FNV-1a, spelled out so the expected hashes never depend on the standard library's hasher
+    fn fnv1a(bytes: &[u8]) -> u64 {
+        bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(0x100_0000_01b3))
+    }
+
+    fn ink_pixels(pixels: &[u8]) -> usize {
+        pixels.chunks_exact(4).filter(|pixel| pixel[3] == 255).count()
+    }
+
+    #[test]
+    fn hourglass_pixels_are_deterministic() {
+        let expected = [
+            (None, 1, 0x3c8e_817e_0324_0105),
+            (Some(0.0), 1, 0xaef8_4ace_831a_5a65),
+            (Some(0.5), 1, 0xe2f6_59e4_c7ff_3385),
+            (Some(1.0), 1, 0xe410_f2c6_88d8_eca5),
+            (None, 2, 0xd4b2_25ed_d4d8_03a5),
+            (Some(0.0), 2, 0xdb04_4743_672a_aa25),
+            (Some(0.5), 2, 0x432d_8e0e_981f_a5a5),
+            (Some(1.0), 2, 0x047a_79af_ce7a_f125),
+        ];
+        for (remaining, scale, hash) in expected {
+            let pixels = render_hourglass(remaining, scale);
+            assert_eq!(pixels.len(), (ICON_SIZE * scale * ICON_SIZE * scale * 4) as usize);
+            assert_eq!(fnv1a(&pixels), hash, "{:?} at {}x", remaining, scale);
+        }
+    }
+
+    #[test]
+    fn hourglass_fill_grows_with_time_remaining() {
+        for scale in [1, 2] {
+            let outline = ink_pixels(&render_hourglass(Some(0.0), scale));
+            let half = ink_pixels(&render_hourglass(Some(0.5), scale)) - outline;
+            let full = ink_pixels(&render_hourglass(Some(1.0), scale)) - outline;
+            assert!(half > 0);
+            assert_eq!(full, 2 * half);
+            // Out-of-range fractions are clamped rather than drawn outside the vessel
+            assert_eq!(render_hourglass(Some(1.5), scale), render_hourglass(Some(1.0), scale));
+            assert_eq!(render_hourglass(Some(-0.5), scale), render_hourglass(Some(0.0), scale));
+        }
+    }
+
+    #[test]
+    fn idle_hourglass_is_empty_with_a_dot() {
+        for scale in [1, 2] {
+            let outline = ink_pixels(&render_hourglass(Some(0.0), scale));
+            let idle = render_hourglass(None, scale);
+            assert_eq!(ink_pixels(&idle) - outline, (4 * scale * scale) as usize);
+            let middle = (ICON_SIZE * scale / 2) as usize;
+            let offset = (middle * (ICON_SIZE * scale) as usize + middle) * 4;
+            assert_eq!(idle[offset + 3], 255);
+        }
+    }
+
+    /// Whether pixel `(x, y)` of a `size`-wide image is opaque
+    fn is_ink(pixels: &[u8], size: u32, x: u32, y: u32) -> bool {
+        pixels[((y * size + x) * 4 + 3) as usize] == 255
+    }
+
+    #[test]
+    fn badge_is_a_dot_in_the_top_right_corner() {
+        // (scale, dot pixels)
+        for (scale, dot) in [(1, 12), (2, 52)] {
+            let size = ICON_SIZE * scale;
+            let mut pixels = vec![0; (size * size * 4) as usize];
+            overlay_badge(&mut pixels, size, size, scale);
+            assert_eq!(ink_pixels(&pixels), dot, "at {}x", scale);
+            let corner = (BADGE_DIAMETER + 2) * scale;
+            for y in 0..size {
+                for x in 0..size {
+                    if is_ink(&pixels, size, x, y) {
+                        assert!(x >= size - corner && y < corner, "({}, {}) at {}x", x, y, scale);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn badge_clears_a_ring_around_itself() {
+        // (scale, pixels cleared around the dot)
+        for (scale, ring) in [(1, 20), (2, 60)] {
+            let size = ICON_SIZE * scale;
+            let mut blank = vec![0; (size * size * 4) as usize];
+            overlay_badge(&mut blank, size, size, scale);
+            let mut solid = INK.repeat((size * size) as usize);
+            overlay_badge(&mut solid, size, size, scale);
+            assert_eq!((size * size) as usize - ink_pixels(&solid), ring, "at {}x", scale);
+
+            // Once tinted, no part of the dot touches the icon underneath
+            for y in 0..size {
+                for x in 0..size {
+                    if !is_ink(&blank, size, x, y) {
+                        continue;
+                    }
+                    let neighbours = [(x + 1, y), (x.wrapping_sub(1), y), (x, y + 1), (x, y.wrapping_sub(1))];
+                    for (nx, ny) in neighbours.into_iter().filter(|&(nx, ny)| nx < size && ny < size) {
+                        let icon_beneath = is_ink(&solid, size, nx, ny) && !is_ink(&blank, size, nx, ny);
+                        assert!(!icon_beneath, "({}, {}) at {}x", nx, ny, scale);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn badge_leaves_the_rest_of_the_icon_alone() {
+        let size = ICON_SIZE * 2;
+        let icon = render_hourglass(Some(0.5), 2);
+        let mut badged = icon.clone();
+        overlay_badge(&mut badged, size, size, 2);
+        let corner = (BADGE_DIAMETER + 2) * 2;
+        for y in 0..size {
+            for x in 0..size {
+                if x < size - corner || y >= corner {
+                    let offset = ((y * size + x) * 4) as usize;
+                    assert_eq!(badged[offset..offset + 4], icon[offset..offset + 4], "({}, {})", x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn with_badge_scales_the_dot_to_the_image() {
+        for scale in [1, 2] {
+            let size = ICON_SIZE * scale;
+            let icon = render_bars(scale);
+            let mut expected = icon.clone();
+            overlay_badge(&mut expected, size, size, scale);
+            let badged = with_badge(Image::new_owned(icon, size, size));
+            assert_eq!((badged.width(), badged.height()), (size, size));
+            assert_eq!(badged.rgba(), expected.as_slice(), "at {}x", scale);
+        }
+    }
+}
+
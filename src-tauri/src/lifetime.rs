@@ -0,0 +1,232 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+use crate::clock::Clock;
+use crate::fetch::{fetch_ccusage, CommandRunner, RecentUsage};
+use crate::model::{BlockData, DailyResponse, DailyUsage};
+use crate::state::{cache_dir, record_write_result, CacheFile};
+use crate::stats::total_cost;
+
+/// How often the lifetime total is recomputed from scratch, picking up
+/// history rewrites such as pricing updates
+const LIFETIME_FULL_RECOMPUTE_DAYS: i64 = 7;
+
+/// Bumped whenever `LifetimeTotals` gains fields, forcing a recompute of old caches
+const LIFETIME_CACHE_VERSION: u32 = 2;
+
+/// Running all-time spend, persisted so only new days need fetching
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct LifetimeTotals {
+    version: u32,
+    /// When the whole history was last summed from scratch
+    computed_on: Option<chrono::NaiveDate>,
+    first_date: Option<chrono::NaiveDate>,
+    /// Cost and tokens of all completed days before `settled_through`
+    settled_cost: f64,
+    settled_tokens: u64,
+    settled_through: Option<chrono::NaiveDate>,
+}
+
+static LIFETIME: Mutex<Option<LifetimeTotals>> = Mutex::new(None);
+
+fn load_lifetime() -> Option<LifetimeTotals> {
+    let contents = std::fs::read_to_string(CacheFile::Lifetime.path()?).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+fn save_lifetime(totals: &LifetimeTotals) {
+    let Some(dir) = cache_dir() else {
+        return;
+    };
+    let Ok(json) = serde_json::to_string_pretty(totals) else {
+        return;
+    };
+    let result =
+        std::fs::create_dir_all(&dir).and_then(|()| std::fs::write(dir.join(CacheFile::Lifetime.file_name()), json));
+    record_write_result(&dir, &result);
+    if let Err(e) = result {
+        eprintln!("Failed to save lifetime totals: {}", e);
+    }
+}
+
+fn lifetime_needs_full_recompute(totals: &LifetimeTotals, today: chrono::NaiveDate) -> bool {
+    if totals.version != LIFETIME_CACHE_VERSION {
+        return true;
+    }
+    match totals.computed_on {
+        Some(computed_on) => (today - computed_on).num_days() >= LIFETIME_FULL_RECOMPUTE_DAYS || computed_on > today,
+        None => true,
+    }
+}
+
+/// Fold daily totals into the running lifetime sum. Only completed days
+/// (before `today`) on or after `settled_through` are added, so re-applying
+/// an overlapping range never double counts.
+fn apply_daily_totals(totals: &LifetimeTotals, days: &[DailyUsage], today: chrono::NaiveDate) -> LifetimeTotals {
+    let mut updated = totals.clone();
+    for day in days {
+        let Ok(date) = chrono::NaiveDate::parse_from_str(&day.date, "%Y-%m-%d") else {
+            continue;
+        };
+        if date >= today || totals.settled_through.is_some_and(|through| date < through) {
+            continue;
+        }
+        updated.settled_cost += day.total_cost;
+        updated.settled_tokens += day.total_tokens;
+        updated.first_date = Some(updated.first_date.map_or(date, |first| first.min(date)));
+    }
+    updated.settled_through = Some(today);
+    updated
+}
+
+/// Bring the lifetime total up to date, running a full-range `ccusage daily`
+/// only when the cache is missing or older than a week. New days are taken
+/// from `recent` when it reaches back far enough, saving a ccusage run.
+pub(crate) async fn refresh_lifetime_totals(
+    runner: &dyn CommandRunner,
+    clock: &dyn Clock,
+    recent: Option<&RecentUsage>,
+) {
+    let today = clock.now().date_naive();
+    let cached = LIFETIME.lock().unwrap().clone().or_else(load_lifetime);
+
+    let updated = match cached {
+        Some(totals) if !lifetime_needs_full_recompute(&totals, today) => {
+            if totals.settled_through == Some(today) {
+                *LIFETIME.lock().unwrap() = Some(totals);
+                return;
+            }
+            let covers_new_days =
+                |recent: &&RecentUsage| totals.settled_through.is_some_and(|through| recent.since <= through);
+            if let Some(recent) = recent.filter(covers_new_days) {
+                apply_daily_totals(&totals, &recent.unfiltered_days, today)
+            } else {
+                let since = totals.settled_through.unwrap_or(today).format("%Y%m%d").to_string();
+                let args = ["daily", "--json", "--since", &since];
+                let Ok(response) = fetch_ccusage::<DailyResponse>(runner, &args).await else {
+                    return;
+                };
+                apply_daily_totals(&totals, &response.daily, today)
+            }
+        }
+        _ => {
+            let Ok(response) = fetch_ccusage::<DailyResponse>(runner, &["daily", "--json"]).await else {
+                return;
+            };
+            let fresh = LifetimeTotals {
+                version: LIFETIME_CACHE_VERSION,
+                computed_on: Some(today),
+                ..LifetimeTotals::default()
+            };
+            apply_daily_totals(&fresh, &response.daily, today)
+        }
+    };
+
+    save_lifetime(&updated);
+    *LIFETIME.lock().unwrap() = Some(updated);
+}
+
+/// Forget the cached lifetime totals so the next refresh sums the whole history
+/// again, e.g. after ccusage's data directory was reset
+pub(crate) fn reset_lifetime_totals() {
+    *LIFETIME.lock().unwrap() = None;
+    if let Some(path) = CacheFile::Lifetime.path() {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LifetimeSummary {
+    pub(crate) cost: f64,
+    pub(crate) tokens: u64,
+    pub(crate) first_date: Option<chrono::NaiveDate>,
+}
+
+/// All-time spend and tokens including today. `None` until the lifetime
+/// totals have been computed at least once.
+pub(crate) fn lifetime_total(today_blocks: &[BlockData]) -> Option<LifetimeSummary> {
+    let lifetime = LIFETIME.lock().unwrap().clone()?;
+    let today_tokens: u64 = today_blocks
+        .iter()
+        .filter(|block| !block.is_gap)
+        .map(|block| block.token_counts.total())
+        .sum();
+    Some(LifetimeSummary {
+        cost: lifetime.settled_cost + total_cost(today_blocks, true),
+        tokens: lifetime.settled_tokens + today_tokens,
+        first_date: lifetime.first_date,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(month: u32, day: u32) -> chrono::NaiveDate {
+        chrono::NaiveDate::from_ymd_opt(2025, month, day).unwrap()
+    }
+
+    fn usage(date: &str, cost: f64, tokens: u64) -> DailyUsage {
+        DailyUsage { date: date.to_string(), total_tokens: tokens, total_cost: cost }
+    }
+
+    fn fresh(today: chrono::NaiveDate) -> LifetimeTotals {
+        LifetimeTotals { version: LIFETIME_CACHE_VERSION, computed_on: Some(today), ..LifetimeTotals::default() }
+    }
+
+    #[test]
+    fn full_run_settles_every_completed_day() {
+        let days = [usage("2025-06-01", 2.0, 100), usage("2025-06-02", 3.0, 200), usage("2025-06-03", 9.0, 900)];
+        let totals = apply_daily_totals(&fresh(date(6, 3)), &days, date(6, 3));
+        assert_eq!(totals.settled_cost, 5.0);
+        assert_eq!(totals.settled_tokens, 300);
+        assert_eq!(totals.first_date, Some(date(6, 1)));
+        assert_eq!(totals.settled_through, Some(date(6, 3)));
+    }
+
+    #[test]
+    fn incremental_update_adds_only_new_days() {
+        let first = [usage("2025-06-01", 2.0, 100), usage("2025-06-02", 3.0, 200)];
+        let totals = apply_daily_totals(&fresh(date(6, 2)), &first, date(6, 2));
+        // The next run overlaps the already settled 1 June
+        let next = [usage("2025-06-01", 2.0, 100), usage("2025-06-02", 3.0, 200), usage("2025-06-03", 4.0, 400)];
+        let totals = apply_daily_totals(&totals, &next, date(6, 4));
+        assert_eq!(totals.settled_cost, 9.0);
+        assert_eq!(totals.settled_tokens, 700);
+        assert_eq!(totals.first_date, Some(date(6, 1)));
+        assert_eq!(totals.settled_through, Some(date(6, 4)));
+    }
+
+    #[test]
+    fn reapplying_the_same_range_is_a_no_op() {
+        let days = [usage("2025-06-01", 2.0, 100), usage("2025-06-02", 3.0, 200)];
+        let once = apply_daily_totals(&fresh(date(6, 3)), &days, date(6, 3));
+        let twice = apply_daily_totals(&once, &days, date(6, 3));
+        assert_eq!(once, twice);
+    }
+
+    #[test]
+    fn unparseable_dates_are_skipped() {
+        let days = [usage("yesterday", 2.0, 100), usage("2025-06-02", 3.0, 200)];
+        let totals = apply_daily_totals(&fresh(date(6, 3)), &days, date(6, 3));
+        assert_eq!(totals.settled_cost, 3.0);
+    }
+
+    #[test]
+    fn cache_is_recomputed_weekly() {
+        let totals = fresh(date(6, 1));
+        assert!(!lifetime_needs_full_recompute(&totals, date(6, 1)));
+        assert!(!lifetime_needs_full_recompute(&totals, date(6, 7)));
+        assert!(lifetime_needs_full_recompute(&totals, date(6, 8)));
+    }
+
+    #[test]
+    fn old_versions_and_future_dates_force_a_recompute() {
+        let old = LifetimeTotals { version: LIFETIME_CACHE_VERSION - 1, ..fresh(date(6, 1)) };
+        assert!(lifetime_needs_full_recompute(&old, date(6, 1)));
+        // The clock was set back since the last full run
+        assert!(lifetime_needs_full_recompute(&fresh(date(6, 5)), date(6, 1)));
+        assert!(lifetime_needs_full_recompute(&LifetimeTotals::default(), date(6, 1)));
+    }
+}
@@ -0,0 +1,1736 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use tauri::menu::{CheckMenuItem, IsMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu};
+use tauri::Wry;
+
+use crate::alerts::over_output_cap;
+use crate::budget::{budget_statuses, period_spend, plan_value_text, BudgetPeriod};
+use crate::capability::{denied_capabilities, Capability};
+use crate::clock::Clock;
+use crate::conversations::{short_id, todays_conversations, ConversationCost};
+use crate::dock::ActivationPolicy;
+use crate::format::{
+    compact_models_line, format_block_length, format_clock_time, format_cost, format_cost_grouped, format_countdown,
+    format_model_name, format_seconds, format_tokens, histogram_rows,
+};
+use crate::glance::SINCE_LAST_LOOKED;
+use crate::i18n::Translator;
+use crate::lifetime::{lifetime_total, LifetimeSummary};
+use crate::model::{BlockData, DailyUsage, ModelBreakdown};
+use crate::modelwatch::models_added;
+use crate::preview::PreviewScenario;
+use crate::state::{
+    current_settings, AppSettings, Availability, ModelsDisplay, RefreshTrigger, CONFIG_DIR_ENV, IS_REFRESHING,
+    NEXT_REFRESH, NPX_DOWNLOADS, ON_BATTERY, REFRESH_TIMINGS, SESSION_CACHE, SETTINGS_BACKUP, SETTINGS_READ_ONLY,
+    WRITE_DENIED_DIR,
+};
+use crate::stats::{
+    biggest_cost_jump, block_duration, block_limit_estimate, block_share_of_day, cache_savings, cost_sparkline,
+    hourly_cost_bins, model_time_shares, percent_shares, project_month_cost, total_cost, total_cost_in_window,
+    LimitEstimate, ModelExclusion, SLOW_REFRESH_SECS,
+};
+use crate::timeline::cost_samples;
+
+/// Points in the session cost sparkline
+const SPARKLINE_WIDTH: usize = 8;
+
+pub(crate) const CCUSAGE_REPO_URL: &str = "https://github.com/ryoppippi/ccusage";
+pub(crate) const CCUSAGE_INSTALL_URL: &str = "https://github.com/ryoppippi/ccusage#installation";
+/// System Settings → Privacy & Security → Full Disk Access
+const FULL_DISK_ACCESS_URL: &str = "x-apple.systempreferences:com.apple.preference.security?Privacy_AllFiles";
+
+/// System Settings → Notifications
+const NOTIFICATIONS_URL: &str = "x-apple.systempreferences:com.apple.preference.notifications";
+
+/// Anthropic's page for each model family, which lists its prices
+const MODEL_FAMILY_PAGES: &[(&str, &str)] = &[
+    ("opus", "https://www.anthropic.com/claude/opus"),
+    ("sonnet", "https://www.anthropic.com/claude/sonnet"),
+    ("haiku", "https://www.anthropic.com/claude/haiku"),
+];
+
+/// All models' prices, for families without a page of their own
+const PRICING_URL: &str = "https://www.anthropic.com/pricing#api";
+
+/// Menu item ids that open an external link, and where they go. Items not
+/// listed here, or allocated as `MenuIdKind::Pricing`, never open a browser.
+const MENU_LINKS: &[(&str, &str)] = &[
+    ("link_github", CCUSAGE_REPO_URL),
+    ("link_install", CCUSAGE_INSTALL_URL),
+    ("install_msg", CCUSAGE_INSTALL_URL),
+    ("slow_refresh_hint", CCUSAGE_INSTALL_URL),
+    ("grant_access", FULL_DISK_ACCESS_URL),
+    ("enable_notifications", NOTIFICATIONS_URL),
+];
+
+/// Where clicking `id` goes, given what the menu allocated it for
+pub(crate) fn menu_link(id: &str, payload: Option<(MenuIdKind, &str)>) -> Option<&'static str> {
+    if let Some((MenuIdKind::Pricing, model)) = payload {
+        return Some(model_pricing_url(model));
+    }
+    MENU_LINKS.iter().find(|(link_id, _)| *link_id == id).map(|(_, url)| *url)
+}
+
+/// The pricing page for `model`'s family
+fn model_pricing_url(model: &str) -> &'static str {
+    let model = model.to_ascii_lowercase();
+    MODEL_FAMILY_PAGES
+        .iter()
+        .find(|(family, _)| model.contains(family))
+        .map_or(PRICING_URL, |(_, url)| *url)
+}
+
+/// What a menu item built from dynamic content stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MenuIdKind {
+    /// A model's submenu
+    Model,
+    ModelTokens,
+    ModelCostShare,
+    /// A model's "Open pricing" item
+    Pricing,
+    ModelAdded,
+    TimeShare,
+    /// A row of "Today's conversations", for its full conversation id
+    Conversation,
+}
+
+impl MenuIdKind {
+    fn prefix(self) -> &'static str {
+        match self {
+            MenuIdKind::Model => "model_",
+            MenuIdKind::ModelTokens => "model_tokens_",
+            MenuIdKind::ModelCostShare => "model_cost_share_",
+            MenuIdKind::Pricing => "pricing_",
+            MenuIdKind::ModelAdded => "model_added_",
+            MenuIdKind::TimeShare => "time_share_",
+            MenuIdKind::Conversation => "conversation_",
+        }
+    }
+}
+
+/// Ids handed out to menu items built from dynamic content such as model
+/// names, and what each stands for, so click handlers look the payload up
+/// instead of parsing it back out of the id
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct MenuIds {
+    payloads: HashMap<String, (MenuIdKind, String)>,
+}
+
+impl MenuIds {
+    /// A fresh id for `value` under `kind`'s prefix. An id that's already
+    /// taken, e.g. by a model listed twice, gets `_2`, `_3`, … in allocation
+    /// order, so the same menu state always produces the same ids.
+    fn allocate(&mut self, kind: MenuIdKind, value: &str) -> String {
+        let base = format!("{}{}", kind.prefix(), value);
+        let mut id = base.clone();
+        let mut n = 1;
+        while self.payloads.contains_key(&id) {
+            n += 1;
+            id = format!("{}_{}", base, n);
+        }
+        if n > 1 {
+            eprintln!("Menu item id {} is already in use, using {} instead", base, id);
+        }
+        self.payloads.insert(id.clone(), (kind, value.to_string()));
+        id
+    }
+
+    /// What the item `id` was allocated for, `None` for fixed items
+    pub(crate) fn payload(&self, id: &str) -> Option<(MenuIdKind, &str)> {
+        self.payloads.get(id).map(|(kind, value)| (*kind, value.as_str()))
+    }
+}
+
+/// A menu's rows together with the ids allocated for its dynamic items
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MenuSpec {
+    pub(crate) entries: Vec<MenuEntry>,
+    pub(crate) ids: MenuIds,
+}
+
+/// Platform-independent description of a menu row, turned into real menu
+/// items by `RenderedMenu::build`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum MenuEntry {
+    Item {
+        id: String,
+        text: String,
+        enabled: bool,
+        accelerator: Option<&'static str>,
+    },
+    Check {
+        id: String,
+        text: String,
+        checked: bool,
+    },
+    Submenu {
+        id: String,
+        text: String,
+        entries: Vec<MenuEntry>,
+    },
+    Separator,
+}
+
+impl MenuEntry {
+    fn item(id: impl Into<String>, text: impl Into<String>) -> Self {
+        MenuEntry::Item {
+            id: id.into(),
+            text: text.into(),
+            enabled: true,
+            accelerator: None,
+        }
+    }
+
+    /// Greyed-out informational row
+    fn label(id: impl Into<String>, text: impl Into<String>) -> Self {
+        MenuEntry::Item {
+            id: id.into(),
+            text: text.into(),
+            enabled: false,
+            accelerator: None,
+        }
+    }
+
+    fn check(id: impl Into<String>, text: impl Into<String>, checked: bool) -> Self {
+        MenuEntry::Check {
+            id: id.into(),
+            text: text.into(),
+            checked,
+        }
+    }
+
+    fn submenu(id: impl Into<String>, text: impl Into<String>, entries: Vec<MenuEntry>) -> Self {
+        MenuEntry::Submenu {
+            id: id.into(),
+            text: text.into(),
+            entries,
+        }
+    }
+}
+
+/// How the menu presents a refresh. While one runs every row keeps its
+/// previous value; only the Refresh row changes, to a ticking "Updating".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RefreshPresentation {
+    Idle,
+    InProgress,
+    /// In progress, and npx is downloading ccusage first
+    Downloading,
+}
+
+/// How often the "Updating" ellipsis advances a dot
+pub(crate) const UPDATING_TICK: Duration = Duration::from_millis(400);
+
+/// The text for `key` (e.g. "Updating") with one to three dots, advancing every `UPDATING_TICK`
+fn updating_text(t: &Translator, key: &'static str, now: chrono::DateTime<chrono::Local>) -> String {
+    let frame = now.timestamp_millis().div_euclid(UPDATING_TICK.as_millis() as i64).rem_euclid(3);
+    format!("{}{}", t.tr(key), ".".repeat(frame as usize + 1))
+}
+
+/// When the next automatic refresh happens, matching what the periodic loop
+/// will actually do. `None` while refreshing, when it's due now, or when the
+/// battery row already says it's paused.
+fn next_refresh_text(t: &Translator, state: &MenuState) -> Option<String> {
+    if state.refresh != RefreshPresentation::Idle {
+        return None;
+    }
+    if state.settings.fetch_on_open_only {
+        return Some(t.tr("next_refresh_on_open").to_string());
+    }
+    // The loop doesn't refresh before the first fetch or once processes are blocked
+    if !state.has_attempted_fetch || state.availability == Availability::Blocked {
+        return Some(t.tr("auto_refresh_paused").to_string());
+    }
+    let remaining = state.next_refresh? - state.now;
+    (remaining > chrono::Duration::zero())
+        .then(|| t.fill("next_refresh_in", &[("duration", &format_countdown(remaining))]))
+}
+
+/// Everything the menu shows, captured at one point in time
+#[derive(Debug, Clone)]
+pub(crate) struct MenuState {
+    pub(crate) active_block: Option<BlockData>,
+    pub(crate) today_blocks: Vec<BlockData>,
+    pub(crate) recent_days: Vec<DailyUsage>,
+    /// `recent_days` counting only usage inside the counting window
+    pub(crate) window_days: Vec<DailyUsage>,
+    pub(crate) previous_block: Option<BlockData>,
+    /// Whether `recent_days` is left over from before the last, failed refresh
+    pub(crate) recent_days_stale: bool,
+    pub(crate) last_activity: Option<chrono::DateTime<chrono::Local>>,
+    pub(crate) has_attempted_fetch: bool,
+    pub(crate) availability: Availability,
+    pub(crate) claude_running: Option<bool>,
+    pub(crate) settings: AppSettings,
+    pub(crate) now: chrono::DateTime<chrono::Local>,
+    pub(crate) lifetime: Option<LifetimeSummary>,
+    /// Today's most expensive conversations, most expensive first
+    pub(crate) conversations: Vec<ConversationCost>,
+    /// Spend between the last two times the menu was opened
+    pub(crate) since_last_looked: Option<f64>,
+    /// Cost of the active block sampled at each refresh
+    pub(crate) cost_samples: Vec<(chrono::DateTime<chrono::Local>, f64)>,
+    /// Models that joined the active block partway through, with when
+    pub(crate) models_added: Vec<(String, chrono::DateTime<chrono::Local>)>,
+    pub(crate) avg_refresh: Option<Duration>,
+    pub(crate) last_trigger: Option<RefreshTrigger>,
+    pub(crate) on_battery: bool,
+    /// How `excluded_models` applied to the fetched blocks
+    pub(crate) model_exclusion: ModelExclusion,
+    /// When the periodic loop will next refresh
+    pub(crate) next_refresh: Option<chrono::DateTime<chrono::Local>>,
+    pub(crate) write_denied_dir: Option<PathBuf>,
+    /// Where a corrupt settings file was moved before defaults were restored
+    pub(crate) settings_backup: Option<PathBuf>,
+    /// Version of a settings file too new to be saved over
+    pub(crate) settings_read_only: Option<u32>,
+    /// Notifications, clipboard or dialogs known not to work
+    pub(crate) denied_capabilities: Vec<Capability>,
+    /// Canned scenario being shown instead of live data
+    pub(crate) preview: Option<PreviewScenario>,
+    pub(crate) refresh: RefreshPresentation,
+}
+
+impl MenuState {
+    pub(crate) fn from_cache(clock: &dyn Clock) -> Self {
+        let cache = SESSION_CACHE.lock().unwrap().clone();
+        let lifetime = lifetime_total(&cache.today_blocks);
+        let cost_samples = cache.active_block.as_ref().map(|block| cost_samples(&block.id)).unwrap_or_default();
+        let models_added = cache.active_block.as_ref().map(|block| models_added(&block.id)).unwrap_or_default();
+        Self {
+            active_block: cache.active_block,
+            today_blocks: cache.today_blocks,
+            recent_days: cache.recent_days,
+            window_days: cache.window_days,
+            previous_block: cache.previous_block,
+            recent_days_stale: cache.recent_days_updated.is_some() && cache.recent_days_updated != cache.last_updated,
+            last_activity: cache.last_activity,
+            has_attempted_fetch: cache.last_updated.is_some(),
+            availability: cache.availability,
+            claude_running: cache.claude_running,
+            settings: current_settings(),
+            now: clock.now(),
+            lifetime,
+            conversations: todays_conversations(clock.now().date_naive()),
+            since_last_looked: *SINCE_LAST_LOOKED.lock().unwrap(),
+            cost_samples,
+            models_added,
+            avg_refresh: REFRESH_TIMINGS.lock().unwrap().refreshes.mean(),
+            last_trigger: cache.last_trigger,
+            on_battery: ON_BATTERY.load(Ordering::Relaxed),
+            model_exclusion: cache.model_exclusion,
+            next_refresh: *NEXT_REFRESH.lock().unwrap(),
+            write_denied_dir: WRITE_DENIED_DIR.lock().unwrap().clone(),
+            settings_backup: SETTINGS_BACKUP.lock().unwrap().clone(),
+            settings_read_only: *SETTINGS_READ_ONLY.lock().unwrap(),
+            denied_capabilities: denied_capabilities(),
+            preview: None,
+            refresh: if !IS_REFRESHING.load(Ordering::Relaxed) {
+                RefreshPresentation::Idle
+            } else if NPX_DOWNLOADS.lock().unwrap().started.is_some() {
+                RefreshPresentation::Downloading
+            } else {
+                RefreshPresentation::InProgress
+            },
+        }
+    }
+
+    /// Daily totals as budgets and the plan value count them: only usage
+    /// inside the counting window when that's on
+    fn counted_days(&self) -> &[DailyUsage] {
+        if self.settings.count_window_only {
+            &self.window_days
+        } else {
+            &self.recent_days
+        }
+    }
+
+    /// Which variant of the session section to show
+    pub(crate) fn session_status(&self) -> SessionStatus<'_> {
+        if let Some(block) = &self.active_block {
+            return SessionStatus::Active(block);
+        }
+        if !self.has_attempted_fetch {
+            return SessionStatus::Loading;
+        }
+        if self.availability == Availability::Available && total_cost(&self.today_blocks, true) == 0.0 {
+            return SessionStatus::Idle;
+        }
+        SessionStatus::NoSession
+    }
+}
+
+/// What the current session section is showing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum SessionStatus<'a> {
+    /// No refresh has finished yet
+    Loading,
+    Active(&'a BlockData),
+    /// ccusage works but nothing has been used yet today
+    Idle,
+    /// No active block, either after earlier use today or because ccusage failed
+    NoSession,
+}
+
+/// The full tray menu for `state`, top to bottom
+pub(crate) fn menu_spec(state: &MenuState) -> MenuSpec {
+    let settings = &state.settings;
+    let t = Translator::new(settings);
+    let mut ids = MenuIds::default();
+
+    // CCUsage header (simple, no timestamp)
+    let mut entries = vec![MenuEntry::label("ccusage_header", "CCUsage")];
+    if let Some(delta) = state.since_last_looked.filter(|delta| *delta >= 0.01) {
+        let text = t.fill("since_last_looked", &[("cost", &format_cost(delta))]);
+        entries.push(MenuEntry::label("since_last_looked", text));
+    }
+    entries.push(MenuEntry::Separator);
+
+    let today = state.now.date_naive();
+    let budgets = if state.has_attempted_fetch {
+        budget_statuses(settings, state.active_block.as_ref(), state.counted_days(), today)
+    } else {
+        Vec::new()
+    };
+    // With a counting window, calendar periods also show what they'd be at all hours
+    let all_hours_suffix = |period: BudgetPeriod| {
+        let all_hours = period_spend(period, None, &state.recent_days, today, settings.week_start);
+        t.fill("all_hours_suffix", &[("cost", &format_cost(all_hours))])
+    };
+    let budget_row = |period: BudgetPeriod| {
+        budgets.iter().find(|status| status.period == period).map(|status| {
+            let mut text = status.row_text();
+            if settings.count_window_only && period != BudgetPeriod::PerBlock {
+                text.push_str(&all_hours_suffix(period));
+            }
+            MenuEntry::label(period.id(), text)
+        })
+    };
+
+    // Current session section
+    entries.push(MenuEntry::label("session_title", t.tr("session_title")));
+
+    match state.session_status() {
+        SessionStatus::Active(block) => {
+            let mut session = session_entries(block, settings, &state.cost_samples, &state.models_added, &mut ids);
+            let limit_estimate =
+                settings.block_token_limit.and_then(|limit| block_limit_estimate(block, limit, state.now));
+            if let Some(estimate) = limit_estimate {
+                let text = match estimate {
+                    LimitEstimate::Exceeded => t.tr("limit_exceeded").to_string(),
+                    LimitEstimate::ReachedIn(eta) => t.fill("limit_eta", &[("duration", &format_block_length(eta))]),
+                };
+                session.insert(1, MenuEntry::label("session_limit", text));
+            }
+            if let Some(row) = budget_row(BudgetPeriod::PerBlock) {
+                session.insert(1, row);
+            }
+            if let Some(share) = block_share_of_day(block, &state.today_blocks) {
+                let text = t.fill("session_day_share", &[("percent", &format!("{:.0}", share * 100.0))]);
+                session.insert(1, MenuEntry::label("session_day_share", text));
+            }
+            entries.extend(session);
+        }
+        SessionStatus::Idle => entries.extend(idle_entries(state)),
+        SessionStatus::NoSession => entries.extend(no_session_entries(state)),
+        SessionStatus::Loading => {
+            entries.push(MenuEntry::label("loading", t.tr("loading")));
+            entries.push(MenuEntry::Separator);
+        }
+    }
+
+    // The block before the current one, so a rollover doesn't hide what was just spent
+    if let Some(previous) = state.previous_block.as_ref().filter(|_| settings.show_previous_session) {
+        entries.push(MenuEntry::label(
+            "previous_session_cost",
+            t.fill("previous_session_cost", &[("cost", &format_cost(previous.cost_usd))]),
+        ));
+        let ended = previous.actual_end_time.as_deref().unwrap_or(&previous.end_time);
+        let text = t.fill("previous_session_end", &[("time", &format_clock_time(ended))]);
+        entries.push(MenuEntry::label("previous_session_end", text));
+        entries.push(MenuEntry::Separator);
+    }
+
+    // Settings file that had to be reset
+    if let Some(backup) = &state.settings_backup {
+        entries.push(MenuEntry::label("settings_reset_msg", t.tr("settings_reset_msg")));
+        let text = t.fill("settings_backup", &[("path", &backup.display().to_string())]);
+        entries.push(MenuEntry::label("settings_backup", text));
+        entries.push(MenuEntry::Separator);
+    }
+
+    // Settings file from a newer version of the app
+    if let Some(version) = state.settings_read_only {
+        let text = t.fill("settings_read_only", &[("version", &version.to_string())]);
+        entries.push(MenuEntry::label("settings_read_only", text));
+        entries.push(MenuEntry::Separator);
+    }
+
+    // Settings or caches that can't be saved
+    if let Some(dir) = &state.write_denied_dir {
+        entries.push(MenuEntry::label(
+            "write_denied_msg",
+            t.fill("write_denied_msg", &[("dir", &dir.display().to_string())]),
+        ));
+        entries.push(MenuEntry::label("write_denied_hint", t.fill("write_denied_hint", &[("env", CONFIG_DIR_ENV)])));
+        entries.push(MenuEntry::Separator);
+    }
+
+    // Integrations that were refused permission or aren't available
+    if !state.denied_capabilities.is_empty() {
+        for capability in &state.denied_capabilities {
+            entries.push(match capability {
+                Capability::Notifications if cfg!(target_os = "macos") => {
+                    MenuEntry::item("enable_notifications", t.tr("enable_notifications"))
+                }
+                Capability::Notifications => MenuEntry::label("notifications_denied", t.tr("notifications_denied")),
+                Capability::Clipboard => MenuEntry::label("clipboard_denied", t.tr("clipboard_denied")),
+                Capability::Dialogs => MenuEntry::label("dialogs_denied", t.tr("dialogs_denied")),
+            });
+        }
+        entries.push(MenuEntry::Separator);
+    }
+
+    // Today's total
+    if state.has_attempted_fetch && state.availability == Availability::Available {
+        let include_active = settings.include_active_in_totals;
+        let all_hours_total = total_cost(&state.today_blocks, include_active);
+        let window = settings.count_window_only.then_some(&settings.counting_window);
+        let today_total = window.map_or(all_hours_total, |window| {
+            total_cost_in_window(&state.today_blocks, include_active, window)
+        });
+        let key = if include_active { "today_total" } else { "today_total_settled" };
+        let estimated = state.today_blocks.iter().any(|block| !block.is_gap && block.is_estimated);
+        let mut text =
+            t.fill(key, &[("cost", &format_cost(today_total)), ("estimated", estimated_suffix(&t, estimated))]);
+        if window.is_some() {
+            text.push_str(&t.fill("all_hours_suffix", &[("cost", &format_cost(all_hours_total))]));
+        }
+        entries.push(MenuEntry::item("today_total", text));
+        if !settings.excluded_models.is_empty() {
+            let models = settings.excluded_models.join(", ");
+            entries.push(MenuEntry::label("excluded_models", t.fill("excluded_models", &[("models", &models)])));
+            if state.model_exclusion.unavailable {
+                entries.push(MenuEntry::label("exclusion_unavailable", t.tr("exclusion_unavailable")));
+            }
+        }
+    }
+    entries.extend(budget_row(BudgetPeriod::Daily));
+
+    // The projection moves slowly, so after a failed refresh it stays up from the last good fetch
+    if state.has_attempted_fetch {
+        let history: Vec<(chrono::NaiveDate, f64)> = state
+            .recent_days
+            .iter()
+            .filter_map(|day| Some((chrono::NaiveDate::parse_from_str(&day.date, "%Y-%m-%d").ok()?, day.total_cost)))
+            .collect();
+        if let Some(projection) = project_month_cost(&history, state.now.date_naive()) {
+            entries.push(MenuEntry::label(
+                "month_projection",
+                t.fill(
+                    "month_projection",
+                    &[
+                        ("cost", &format_cost_grouped(projection.total)),
+                        ("method", projection.method),
+                        ("stale", if state.recent_days_stale { t.tr("stale_suffix") } else { "" }),
+                    ],
+                ),
+            ));
+        }
+    }
+    entries.extend(budget_row(BudgetPeriod::Weekly));
+    entries.extend(budget_row(BudgetPeriod::Monthly));
+    if let Some(plan_cost) = settings.plan_monthly_cost.filter(|_| state.has_attempted_fetch) {
+        let month_spend = period_spend(BudgetPeriod::Monthly, None, state.counted_days(), today, settings.week_start);
+        if let Some(mut text) = plan_value_text(month_spend, plan_cost) {
+            if settings.count_window_only {
+                text.push_str(&all_hours_suffix(BudgetPeriod::Monthly));
+            }
+            entries.push(MenuEntry::label("plan_value", text));
+        }
+    }
+
+    // Power-based auto-refresh state
+    if state.on_battery {
+        let power_label = if settings.battery_refresh_secs == 0 { "power_paused" } else { "power_slowed" };
+        entries.push(MenuEntry::label("power_state", t.tr(power_label)));
+    }
+    if let Some(text) = next_refresh_text(&t, state) {
+        entries.push(MenuEntry::label("next_refresh", text));
+    }
+
+    // Hourly histogram for today
+    let rows = histogram_rows(
+        &hourly_cost_bins(&state.today_blocks, state.now.date_naive(), state.now),
+        settings.glyph_set,
+    );
+    let has_hourly = !rows.is_empty();
+    if has_hourly {
+        let hourly = rows
+            .into_iter()
+            .map(|(hour, label)| MenuEntry::item(format!("hour_{:02}", hour), label))
+            .collect();
+        entries.push(MenuEntry::submenu("hourly_menu", t.tr("hourly_menu"), hourly));
+    }
+
+    // Today's most expensive conversations; clicking one copies its full id
+    if !state.conversations.is_empty() {
+        let conversations = state
+            .conversations
+            .iter()
+            .map(|conversation| {
+                let text = t.fill(
+                    "conversation_row",
+                    &[
+                        ("id", &short_id(&conversation.id)),
+                        ("cost", &format_cost(conversation.cost)),
+                        ("tokens", &format_tokens(conversation.tokens)),
+                    ],
+                );
+                MenuEntry::item(ids.allocate(MenuIdKind::Conversation, &conversation.id), text)
+            })
+            .collect();
+        entries.push(MenuEntry::submenu("conversations_menu", t.tr("conversations_menu"), conversations));
+    }
+    if has_hourly || !state.conversations.is_empty() {
+        entries.push(MenuEntry::Separator);
+    }
+
+    entries.push(MenuEntry::submenu("advanced_menu", t.tr("advanced_menu"), advanced_entries(state)));
+
+    if settings.show_preview_states {
+        let mut previews = vec![MenuEntry::check("preview_live", t.tr("preview_live"), state.preview.is_none())];
+        previews.extend(PreviewScenario::ALL.into_iter().map(|scenario| {
+            MenuEntry::check(scenario.id(), scenario.label(), state.preview == Some(scenario))
+        }));
+        entries.push(MenuEntry::submenu("preview_menu", t.tr("preview_menu"), previews));
+    }
+
+    entries.push(MenuEntry::submenu(
+        "links_menu",
+        t.tr("links_menu"),
+        vec![
+            MenuEntry::item("link_github", t.tr("link_github")),
+            MenuEntry::item("link_install", t.tr("link_install")),
+        ],
+    ));
+
+    // Settings toggles, each labelled by its own id
+    let checks = [
+        ("compact_title", settings.compact_title),
+        ("focus_mode", settings.focus_mode),
+        ("hourglass_icon", settings.hourglass_icon),
+        ("show_previous_session", settings.show_previous_session),
+        ("separate_tokens_tray", settings.separate_tokens_tray),
+        ("show_in_dock", settings.activation_policy == ActivationPolicy::Regular),
+        ("include_active_in_totals", settings.include_active_in_totals),
+        ("count_window_only", settings.count_window_only),
+        ("show_cost_without_cache", settings.show_cost_without_cache),
+        ("pause_on_battery", settings.pause_on_battery),
+        ("fetch_on_open_only", settings.fetch_on_open_only),
+        ("detect_claude_running", settings.detect_claude_running),
+        ("spend_spike_alerts", settings.spend_spike_alerts),
+        ("flash_on_budget_breach", settings.flash_on_budget_breach),
+        ("redact_project_names", settings.redact_project_names),
+        ("write_status_file", settings.write_status_file),
+        ("auto_copy_cost", settings.auto_copy_cost),
+        ("model_change_alerts", settings.model_change_alerts),
+        ("confirm_links", settings.confirm_links),
+    ];
+    let mut settings_entries: Vec<MenuEntry> =
+        checks.into_iter().map(|(id, checked)| MenuEntry::check(id, t.tr(id), checked)).collect();
+    settings_entries.push(MenuEntry::Separator);
+    settings_entries.push(MenuEntry::item("open_settings", t.tr("open_settings")));
+    entries.push(MenuEntry::submenu("settings_menu", t.tr("settings_menu"), settings_entries));
+
+    entries.push(match state.refresh {
+        RefreshPresentation::Idle => MenuEntry::item("refresh", t.tr("refresh")),
+        RefreshPresentation::InProgress => MenuEntry::label("refresh", updating_text(&t, "updating", state.now)),
+        RefreshPresentation::Downloading => {
+            MenuEntry::label("refresh", updating_text(&t, "downloading_ccusage", state.now))
+        }
+    });
+    // Debug info (useful for troubleshooting)
+    entries.push(MenuEntry::item("debug", t.tr("debug")));
+    entries.push(MenuEntry::Separator);
+    entries.push(MenuEntry::Item {
+        id: "quit".into(),
+        text: t.tr("quit").into(),
+        enabled: true,
+        accelerator: Some("CmdOrCtrl+Q"),
+    });
+
+    MenuSpec { entries, ids }
+}
+
+/// Marks costs ccusage worked out offline, which may be off from what's billed
+fn estimated_suffix(t: &Translator, estimated: bool) -> &'static str {
+    if estimated {
+        t.tr("estimated_suffix")
+    } else {
+        ""
+    }
+}
+
+/// Session section when there's no active block, explaining why if ccusage failed
+fn no_session_entries(state: &MenuState) -> Vec<MenuEntry> {
+    let t = Translator::new(&state.settings);
+    let mut entries = Vec::new();
+    let no_session = if state.claude_running == Some(false) { "claude_not_running" } else { "no_session" };
+    entries.push(MenuEntry::item("no_session", t.tr(no_session)));
+
+    if state.availability == Availability::Blocked {
+        entries.push(MenuEntry::label("blocked_msg", t.tr("blocked_msg")));
+    } else if state.availability == Availability::NotFound {
+        // Only show error if ccusage is actually not available
+        entries.push(MenuEntry::label("error_msg", t.tr("error_msg")));
+        entries.push(MenuEntry::item("install_msg", t.tr("install_msg")));
+    } else if state.availability == Availability::AccessDenied {
+        entries.push(MenuEntry::label("access_denied_msg", t.tr("access_denied_msg")));
+        if cfg!(target_os = "macos") {
+            entries.push(MenuEntry::item("grant_access", t.tr("grant_access")));
+        } else {
+            entries.push(MenuEntry::label("access_denied_hint", t.tr("access_denied_hint")));
+        }
+    } else if state.availability == Availability::NoData {
+        entries.push(MenuEntry::label("no_data_msg", t.tr("no_data_msg")));
+        entries.push(MenuEntry::item("show_data_dir", t.tr("show_data_dir")));
+    }
+
+    entries.push(MenuEntry::Separator);
+    entries
+}
+
+/// Session section before any usage today, with yesterday's spend and the
+/// last session for context so it doesn't read like an error
+fn idle_entries(state: &MenuState) -> Vec<MenuEntry> {
+    let t = Translator::new(&state.settings);
+    let mut entries = vec![MenuEntry::item("idle_msg", t.tr("idle_msg"))];
+
+    let yesterday = (state.now.date_naive() - chrono::Duration::days(1)).format("%Y-%m-%d").to_string();
+    let yesterday_cost: f64 = state
+        .recent_days
+        .iter()
+        .filter(|day| day.date == yesterday)
+        .map(|day| day.total_cost)
+        .sum();
+    let text = t.fill("idle_yesterday", &[("cost", &format_cost(yesterday_cost))]);
+    entries.push(MenuEntry::label("idle_yesterday", text));
+
+    if let Some(last) = state.last_activity {
+        let text = t.fill("idle_last_session", &[("time", &last.format("%a %I:%M %p").to_string())]);
+        entries.push(MenuEntry::label("idle_last_session", text));
+    }
+
+    entries.push(MenuEntry::Separator);
+    entries
+}
+
+/// A model's submenu: its tokens and share of the block's cost when ccusage
+/// breaks the block down by model, and a link to its prices
+fn model_details(
+    t: &Translator,
+    ids: &mut MenuIds,
+    model: &str,
+    breakdown: Option<(&ModelBreakdown, u32)>,
+) -> Vec<MenuEntry> {
+    let mut entries = Vec::new();
+    if let Some((breakdown, cost_share)) = breakdown {
+        entries.push(MenuEntry::label(
+            ids.allocate(MenuIdKind::ModelTokens, model),
+            t.fill(
+                "model_tokens",
+                &[
+                    ("input", &format_tokens(breakdown.input_tokens)),
+                    ("output", &format_tokens(breakdown.output_tokens)),
+                    ("cache_write", &format_tokens(breakdown.cache_creation_tokens)),
+                    ("cache_read", &format_tokens(breakdown.cache_read_tokens)),
+                ],
+            ),
+        ));
+        entries.push(MenuEntry::label(
+            ids.allocate(MenuIdKind::ModelCostShare, model),
+            t.fill("model_cost_share", &[("percent", &cost_share.to_string())]),
+        ));
+        entries.push(MenuEntry::Separator);
+    }
+    entries.push(MenuEntry::item(ids.allocate(MenuIdKind::Pricing, model), t.tr("open_pricing")));
+    entries
+}
+
+/// Cost, tokens, times and models of the active block
+fn session_entries(
+    block: &BlockData,
+    settings: &AppSettings,
+    cost_samples: &[(chrono::DateTime<chrono::Local>, f64)],
+    models_added: &[(String, chrono::DateTime<chrono::Local>)],
+    ids: &mut MenuIds,
+) -> Vec<MenuEntry> {
+    let t = Translator::new(settings);
+    let mut entries = vec![MenuEntry::item(
+        "session_cost",
+        t.fill(
+            "session_cost",
+            &[("cost", &format_cost(block.cost_usd)), ("estimated", estimated_suffix(&t, block.is_estimated))],
+        ),
+    )];
+    if over_output_cap(block, settings) {
+        entries.push(MenuEntry::label("high_output", t.tr("high_output")));
+    }
+
+    let cache_visibility = settings.cache_line_visibility;
+    if settings.show_cost_without_cache {
+        let cache_reads: Vec<(String, u64)> = block
+            .model_breakdowns
+            .iter()
+            .map(|breakdown| (breakdown.model_name.clone(), breakdown.cache_read_tokens))
+            .collect();
+        let total_cache_read = block.token_counts.cache_read_input_tokens;
+        // Exact when every model in the breakdown has a known price, blended otherwise
+        let savings = (!cache_reads.is_empty())
+            .then(|| cache_savings(Some(cache_reads.as_slice()), &block.models, total_cache_read))
+            .flatten()
+            .or_else(|| cache_savings(None, &block.models, total_cache_read));
+        if let Some(savings) = savings {
+            if cache_visibility.shows(savings.amount > 0.0) {
+                entries.push(MenuEntry::item(
+                    "session_cost_uncached",
+                    t.fill(
+                        "session_cost_uncached",
+                        &[
+                            ("approx", if savings.estimated { "~" } else { "" }),
+                            ("cost", &format_cost(block.cost_usd + savings.amount)),
+                            ("saved", &format_cost(savings.amount)),
+                        ],
+                    ),
+                ));
+            }
+        }
+    }
+
+    let input_k = block.token_counts.input_tokens as f64 / 1000.0;
+    let output_k = block.token_counts.output_tokens as f64 / 1000.0;
+    entries.push(MenuEntry::item(
+        "session_tokens",
+        t.fill("session_tokens", &[("input", &format!("{:.1}", input_k)), ("output", &format!("{:.1}", output_k))]),
+    ));
+
+    let tokens = &block.token_counts;
+    let cache_tokens = tokens.cache_read_input_tokens + tokens.cache_creation_input_tokens;
+    if cache_visibility.shows(cache_tokens > 0) {
+        entries.push(MenuEntry::item(
+            "session_cache_tokens",
+            t.fill(
+                "session_cache_tokens",
+                &[
+                    ("read", &format_tokens(tokens.cache_read_input_tokens)),
+                    ("written", &format_tokens(tokens.cache_creation_input_tokens)),
+                ],
+            ),
+        ));
+        let prompt_tokens = tokens.input_tokens + cache_tokens;
+        if prompt_tokens > 0 {
+            let ratio = tokens.cache_read_input_tokens as f64 / prompt_tokens as f64;
+            let text = t.fill("session_cache_ratio", &[("percent", &format!("{:.0}", ratio * 100.0))]);
+            entries.push(MenuEntry::label("session_cache_ratio", text));
+        }
+    }
+
+    let start = t.fill("session_start", &[("time", &format_clock_time(&block.start_time))]);
+    entries.push(MenuEntry::item("session_start", start));
+    let end = t.fill("session_end", &[("time", &format_clock_time(&block.end_time))]);
+    entries.push(MenuEntry::item("session_end", end));
+    if let Some(duration) = block_duration(block) {
+        let text = t.fill("session_window", &[("length", &format_block_length(duration))]);
+        entries.push(MenuEntry::label("session_window", text));
+    }
+
+    if let Some(sparkline) = cost_sparkline(cost_samples, SPARKLINE_WIDTH, settings.glyph_set.levels()) {
+        entries.push(MenuEntry::label("session_sparkline", t.fill("session_sparkline", &[("sparkline", &sparkline)])));
+        if let Some((time, amount)) = biggest_cost_jump(cost_samples) {
+            entries.push(MenuEntry::label(
+                "session_biggest_jump",
+                t.fill(
+                    "session_biggest_jump",
+                    &[("time", &time.format("%I:%M %p").to_string()), ("cost", &format_cost(amount))],
+                ),
+            ));
+        }
+    }
+
+    match settings.models_display {
+        ModelsDisplay::Full if !block.models.is_empty() => {
+            entries.push(MenuEntry::Separator);
+            entries.push(MenuEntry::label("models_header", t.tr("models_header")));
+            let costs: Vec<f64> = block.model_breakdowns.iter().map(|breakdown| breakdown.cost).collect();
+            let cost_shares = percent_shares(&costs);
+            for model in &block.models {
+                let breakdown = block.model_breakdowns.iter().position(|breakdown| &breakdown.model_name == model);
+                let breakdown = breakdown.map(|i| (&block.model_breakdowns[i], cost_shares[i]));
+                let id = ids.allocate(MenuIdKind::Model, model);
+                let details = model_details(&t, ids, model, breakdown);
+                entries.push(MenuEntry::submenu(id, format_model_name(model), details));
+            }
+        }
+        ModelsDisplay::Compact => {
+            if let Some(line) = compact_models_line(&block.models) {
+                entries.push(MenuEntry::label("models_compact", line));
+            }
+        }
+        _ => {}
+    }
+    for (model, since) in models_added {
+        entries.push(MenuEntry::label(
+            ids.allocate(MenuIdKind::ModelAdded, model),
+            t.fill(
+                "model_added",
+                &[("model", &format_model_name(model)), ("time", &since.format("%I:%M %p").to_string())],
+            ),
+        ));
+    }
+
+    // Token share stands in for time, which ccusage doesn't break down by model
+    let shares = model_time_shares(block);
+    if settings.models_display != ModelsDisplay::Hidden && shares.len() > 1 {
+        entries.push(MenuEntry::label("time_share_header", t.tr("time_share_header")));
+        for (model, share) in shares {
+            entries.push(MenuEntry::label(
+                ids.allocate(MenuIdKind::TimeShare, model),
+                t.fill(
+                    "time_share",
+                    &[("model", &format_model_name(model)), ("percent", &format!("{:.0}", share * 100.0))],
+                ),
+            ));
+        }
+    }
+
+    entries.push(MenuEntry::Separator);
+    entries
+}
+
+fn advanced_entries(state: &MenuState) -> Vec<MenuEntry> {
+    let t = Translator::new(&state.settings);
+    let mut entries = Vec::new();
+
+    if let Some(lifetime) = &state.lifetime {
+        let mut label = t.fill(
+            "lifetime_total",
+            &[("cost", &format_cost_grouped(lifetime.cost)), ("tokens", &format_tokens(lifetime.tokens))],
+        );
+        if let Some(first_date) = lifetime.first_date {
+            label.push_str(&t.fill("lifetime_since", &[("date", &first_date.format("%b %Y").to_string())]));
+        }
+        entries.push(MenuEntry::item("lifetime_total", label));
+        entries.push(MenuEntry::item("recalculate_lifetime", t.tr("recalculate_lifetime")));
+    } else {
+        entries.push(MenuEntry::label("lifetime_total", t.tr("lifetime_calculating")));
+    }
+
+    entries.push(MenuEntry::Separator);
+    entries.push(MenuEntry::item("preview_alerts", t.tr("preview_alerts")));
+    entries.push(MenuEntry::item("remove_app_data", t.tr("remove_app_data")));
+
+    if let Some(avg) = state.avg_refresh {
+        entries.push(MenuEntry::Separator);
+        entries.push(MenuEntry::label("avg_refresh", t.fill("avg_refresh", &[("duration", &format_seconds(avg))])));
+        if let Some(trigger) = state.last_trigger {
+            entries.push(MenuEntry::label("last_trigger", t.fill("last_trigger", &[("trigger", trigger.label())])));
+        }
+        if avg.as_secs_f64() > SLOW_REFRESH_SECS {
+            entries.push(MenuEntry::item("slow_refresh_hint", t.tr("slow_refresh_hint")));
+        }
+    }
+
+    entries
+}
+
+/// One change turning a rendered menu into a new spec. `parent` is the id of
+/// the submenu being changed, `None` for the top level; indexes are positions
+/// within it at the time the operation is applied, in order.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum MenuOp {
+    Remove { parent: Option<String>, index: usize, key: String },
+    Insert { parent: Option<String>, index: usize, entry: MenuEntry },
+    /// Same kind and id, different text or state
+    Update { old: MenuEntry, new: MenuEntry },
+}
+
+/// Identity of an entry when diffing: its kind and id, or for separators the
+/// entry they follow, so a separator stays put when unrelated rows change
+fn entry_key(entries: &[MenuEntry], index: usize) -> String {
+    match &entries[index] {
+        MenuEntry::Item { id, .. } => format!("item:{}", id),
+        MenuEntry::Check { id, .. } => format!("check:{}", id),
+        MenuEntry::Submenu { id, .. } => format!("submenu:{}", id),
+        MenuEntry::Separator => match index.checked_sub(1) {
+            Some(previous) => format!("separator:{}", entry_key(entries, previous)),
+            None => "separator:".to_string(),
+        },
+    }
+}
+
+/// The operations turning `old` into `new`: rows that went away are removed,
+/// new rows are inserted, and rows with the same key are updated only if
+/// their text or state changed. Submenus are diffed recursively, so changing
+/// one row never rebuilds its neighbours.
+pub(crate) fn diff_menu(old: &[MenuEntry], new: &[MenuEntry]) -> Vec<MenuOp> {
+    let mut ops = Vec::new();
+    diff_entries(None, old, new, &mut ops);
+    ops
+}
+
+fn diff_entries(parent: Option<&str>, old: &[MenuEntry], new: &[MenuEntry], ops: &mut Vec<MenuOp>) {
+    let new_keys: Vec<String> = (0..new.len()).map(|i| entry_key(new, i)).collect();
+
+    // Drop rows missing from the new spec, back to front so indexes stay valid
+    let mut current: Vec<(String, &MenuEntry)> = (0..old.len()).map(|i| (entry_key(old, i), &old[i])).collect();
+    for index in (0..current.len()).rev() {
+        if !new_keys.contains(&current[index].0) {
+            let (key, _) = current.remove(index);
+            ops.push(MenuOp::Remove { parent: parent.map(str::to_string), index, key });
+        }
+    }
+
+    for (index, (key, entry)) in new_keys.iter().zip(new).enumerate() {
+        let existing = current.iter().position(|(current_key, _)| current_key == key);
+        match existing {
+            Some(position) if position == index => {
+                let old_entry = current[index].1;
+                diff_entry(old_entry, entry, ops);
+            }
+            // Moved: take it out and put it back in its new place
+            Some(position) => {
+                let (key, _) = current.remove(position);
+                ops.push(MenuOp::Remove { parent: parent.map(str::to_string), index: position, key });
+                ops.push(MenuOp::Insert { parent: parent.map(str::to_string), index, entry: entry.clone() });
+                current.insert(index, (new_keys[index].clone(), entry));
+            }
+            None => {
+                ops.push(MenuOp::Insert { parent: parent.map(str::to_string), index, entry: entry.clone() });
+                current.insert(index, (key.clone(), entry));
+            }
+        }
+    }
+}
+
+/// Changes to an entry that kept its place
+fn diff_entry(old: &MenuEntry, new: &MenuEntry, ops: &mut Vec<MenuOp>) {
+    match (old, new) {
+        (
+            MenuEntry::Submenu { text: old_text, entries: old_entries, .. },
+            MenuEntry::Submenu { id, text, entries },
+        ) => {
+            if old_text != text {
+                ops.push(MenuOp::Update { old: old.clone(), new: new.clone() });
+            }
+            diff_entries(Some(id), old_entries, entries, ops);
+        }
+        _ if old != new => ops.push(MenuOp::Update { old: old.clone(), new: new.clone() }),
+        _ => {}
+    }
+}
+
+/// Whether applying `ops` only changes text or state, which is safe while the menu is open
+pub(crate) fn is_in_place_only(ops: &[MenuOp]) -> bool {
+    ops.iter().all(|op| matches!(op, MenuOp::Update { .. }))
+}
+
+/// The operations `RenderedMenu::update_to` applies to go from `old` to
+/// `new`, and whether they get all the way there. Without `structural` only
+/// the in-place updates are kept.
+fn plan_update(old: &[MenuEntry], new: &[MenuEntry], structural: bool) -> (Vec<MenuOp>, bool) {
+    let ops = diff_menu(old, new);
+    if structural || is_in_place_only(&ops) {
+        return (ops, true);
+    }
+    (ops.into_iter().filter(|op| matches!(op, MenuOp::Update { .. })).collect(), false)
+}
+
+/// Longest the menu is assumed to stay open. Tray menus don't report closing,
+/// so this stops a missed close from holding back updates forever.
+const MENU_OPEN_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Tracks whether the tray menu is open so structural changes, which can
+/// dismiss it on macOS, wait until it closes. At most one update is queued.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct MenuGate {
+    open_since: Option<Instant>,
+    pending: bool,
+}
+
+impl MenuGate {
+    pub(crate) const fn new() -> Self {
+        Self { open_since: None, pending: false }
+    }
+
+    pub(crate) fn opened(&mut self, now: Instant) {
+        self.open_since = Some(now);
+    }
+
+    /// Returns whether a deferred structural update should run now
+    pub(crate) fn closed(&mut self) -> bool {
+        self.open_since = None;
+        std::mem::take(&mut self.pending)
+    }
+
+    pub(crate) fn is_open(&mut self, now: Instant) -> bool {
+        match self.open_since {
+            Some(since) if now.duration_since(since) < MENU_OPEN_TIMEOUT => true,
+            Some(_) => {
+                self.open_since = None;
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Ask to restructure the menu: true to go ahead now, false when it has
+    /// been queued until the menu closes
+    pub(crate) fn allow_structural(&mut self, now: Instant) -> bool {
+        if self.is_open(now) {
+            self.pending = true;
+            false
+        } else {
+            true
+        }
+    }
+}
+
+/// Handle to a built item whose text or state can change in place
+enum ItemHandle {
+    Item(MenuItem<Wry>),
+    Check(CheckMenuItem<Wry>),
+    Submenu(Submenu<Wry>),
+}
+
+/// A native menu together with the spec it was built from and handles to its
+/// items, so later specs can be applied by changing only what differs
+pub(crate) struct RenderedMenu {
+    pub(crate) menu: Menu<Wry>,
+    spec: MenuSpec,
+    items: HashMap<String, ItemHandle>,
+}
+
+impl RenderedMenu {
+    /// Turn a menu spec into a native menu
+    pub(crate) fn build(app: &tauri::AppHandle, spec: &MenuSpec) -> tauri::Result<Self> {
+        let mut handles = HashMap::new();
+        let items = build_entries(app, &spec.entries, &mut handles)?;
+        let refs: Vec<&dyn IsMenuItem<Wry>> = items.iter().map(|item| item.as_ref()).collect();
+        Ok(Self {
+            menu: Menu::with_items(app, &refs)?,
+            spec: spec.clone(),
+            items: handles,
+        })
+    }
+
+    /// The ids allocated for the shown items, see `MenuIds::payload`
+    pub(crate) fn ids(&self) -> &MenuIds {
+        &self.spec.ids
+    }
+
+    /// Bring the native menu in line with `spec`, touching only the rows
+    /// `diff_menu` reports as changed. Without `structural`, only text and
+    /// state are updated and rows aren't added or removed; the spec is kept so
+    /// the rest is applied by a later call. On error the menu should be rebuilt.
+    pub(crate) fn update_to(
+        &mut self,
+        app: &tauri::AppHandle,
+        spec: &MenuSpec,
+        structural: bool,
+    ) -> tauri::Result<()> {
+        let (ops, complete) = plan_update(&self.spec.entries, &spec.entries, structural);
+        for op in ops {
+            match op {
+                // Handles of removed rows are left in place; they're replaced if the row comes back
+                MenuOp::Remove { parent, index, .. } => {
+                    match self.submenu(parent.as_deref())? {
+                        Some(submenu) => submenu.remove_at(index)?,
+                        None => self.menu.remove_at(index)?,
+                    };
+                }
+                MenuOp::Insert { parent, index, entry } => {
+                    let built = build_entries(app, std::slice::from_ref(&entry), &mut self.items)?;
+                    let item = built[0].as_ref();
+                    match self.submenu(parent.as_deref())? {
+                        Some(submenu) => submenu.insert(item, index)?,
+                        None => self.menu.insert(item, index)?,
+                    }
+                }
+                MenuOp::Update { old, new } => self.apply_update(&old, &new)?,
+            }
+        }
+        if complete {
+            self.spec = spec.clone();
+        }
+        Ok(())
+    }
+
+    /// The submenu `parent` names, `None` for the top level. A parent without
+    /// a handle is an error rather than a change to the top level at the same index.
+    fn submenu(&self, parent: Option<&str>) -> tauri::Result<Option<&Submenu<Wry>>> {
+        let Some(id) = parent else {
+            return Ok(None);
+        };
+        match self.items.get(id) {
+            Some(ItemHandle::Submenu(submenu)) => Ok(Some(submenu)),
+            _ => Err(std::io::Error::other(format!("no submenu {} in the rendered menu", id)).into()),
+        }
+    }
+
+    fn apply_update(&self, old: &MenuEntry, new: &MenuEntry) -> tauri::Result<()> {
+        match (old, new, self.items.get(entry_id(new))) {
+            (
+                MenuEntry::Item { text: old_text, enabled: old_enabled, .. },
+                MenuEntry::Item { text, enabled, .. },
+                Some(ItemHandle::Item(item)),
+            ) => {
+                if old_text != text {
+                    item.set_text(text)?;
+                }
+                if old_enabled != enabled {
+                    item.set_enabled(*enabled)?;
+                }
+            }
+            (
+                MenuEntry::Check { text: old_text, checked: old_checked, .. },
+                MenuEntry::Check { text, checked, .. },
+                Some(ItemHandle::Check(item)),
+            ) => {
+                if old_text != text {
+                    item.set_text(text)?;
+                }
+                if old_checked != checked {
+                    item.set_checked(*checked)?;
+                }
+            }
+            (MenuEntry::Submenu { .. }, MenuEntry::Submenu { text, .. }, Some(ItemHandle::Submenu(submenu))) => {
+                submenu.set_text(text)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn entry_id(entry: &MenuEntry) -> &str {
+    match entry {
+        MenuEntry::Item { id, .. } | MenuEntry::Check { id, .. } | MenuEntry::Submenu { id, .. } => id,
+        MenuEntry::Separator => "",
+    }
+}
+
+fn build_entries(
+    app: &tauri::AppHandle,
+    entries: &[MenuEntry],
+    handles: &mut HashMap<String, ItemHandle>,
+) -> tauri::Result<Vec<Box<dyn IsMenuItem<Wry>>>> {
+    let mut items: Vec<Box<dyn IsMenuItem<Wry>>> = Vec::with_capacity(entries.len());
+    for entry in entries {
+        match entry {
+            MenuEntry::Item {
+                id,
+                text,
+                enabled,
+                accelerator,
+            } => {
+                let item = MenuItem::with_id(app, id.as_str(), text, *enabled, *accelerator)?;
+                handles.insert(id.clone(), ItemHandle::Item(item.clone()));
+                items.push(Box::new(item));
+            }
+            MenuEntry::Check { id, text, checked } => {
+                let item = CheckMenuItem::with_id(app, id.as_str(), text, true, *checked, None::<&str>)?;
+                handles.insert(id.clone(), ItemHandle::Check(item.clone()));
+                items.push(Box::new(item));
+            }
+            MenuEntry::Submenu { id, text, entries } => {
+                let children = build_entries(app, entries, handles)?;
+                let refs: Vec<&dyn IsMenuItem<Wry>> = children.iter().map(|item| item.as_ref()).collect();
+                let submenu = Submenu::with_id_and_items(app, id.as_str(), text, true, &refs)?;
+                handles.insert(id.clone(), ItemHandle::Submenu(submenu.clone()));
+                items.push(Box::new(submenu));
+            }
+            MenuEntry::Separator => items.push(Box::new(PredefinedMenuItem::separator(app)?)),
+        }
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::fixtures::{block, local};
+    use crate::preview::preview_state;
+
+    /// A quiet morning: nothing today, $31.75 yesterday, last used last night
+    fn idle_morning() -> MenuState {
+        let mut state = preview_state(PreviewScenario::Idle, local(2025, 6, 11, 8, 0), AppSettings::default());
+        state.preview = None;
+        state
+    }
+
+    /// Ids of the rows in the session section, up to its separator
+    fn session_ids(spec: &MenuSpec) -> Vec<&str> {
+        let start = spec.entries.iter().position(|entry| entry_id(entry) == "session_title").unwrap() + 1;
+        spec.entries[start..].iter().map(entry_id).take_while(|id| !id.is_empty()).collect()
+    }
+
+    fn entry_text<'a>(spec: &'a MenuSpec, wanted: &str) -> &'a str {
+        spec.entries
+            .iter()
+            .find_map(|entry| match entry {
+                MenuEntry::Item { id, text, .. } if id == wanted => Some(text.as_str()),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn idle_layout_shows_yesterday_and_the_last_session() {
+        let state = idle_morning();
+        assert_eq!(state.session_status(), SessionStatus::Idle);
+        let spec = menu_spec(&state);
+        assert_eq!(session_ids(&spec), ["idle_msg", "idle_yesterday", "idle_last_session"]);
+
+        let t = Translator::new(&state.settings);
+        assert_eq!(entry_text(&spec, "idle_yesterday"), t.fill("idle_yesterday", &[("cost", &format_cost(31.75))]));
+        let last_session = t.fill("idle_last_session", &[("time", "Tue 06:00 AM")]);
+        assert_eq!(entry_text(&spec, "idle_last_session"), last_session);
+    }
+
+    #[test]
+    fn idle_layout_without_history() {
+        let mut state = idle_morning();
+        state.recent_days.clear();
+        state.last_activity = None;
+        let spec = menu_spec(&state);
+        assert_eq!(session_ids(&spec), ["idle_msg", "idle_yesterday"]);
+        let t = Translator::new(&state.settings);
+        assert_eq!(entry_text(&spec, "idle_yesterday"), t.fill("idle_yesterday", &[("cost", &format_cost(0.0))]));
+    }
+
+    #[test]
+    fn idle_needs_a_healthy_ccusage_and_no_spend_today() {
+        let mut state = idle_morning();
+        state.availability = Availability::NotFound;
+        assert_eq!(state.session_status(), SessionStatus::NoSession);
+
+        let mut state = idle_morning();
+        state.today_blocks = vec![block(local(2025, 6, 11, 6, 0), local(2025, 6, 11, 7, 0), 0.5)];
+        assert_eq!(state.session_status(), SessionStatus::NoSession);
+
+        let mut state = idle_morning();
+        state.has_attempted_fetch = false;
+        assert_eq!(state.session_status(), SessionStatus::Loading);
+    }
+
+    #[test]
+    fn only_listed_ids_open_links() {
+        assert_eq!(menu_link("link_github", None), Some(CCUSAGE_REPO_URL));
+        assert_eq!(menu_link("link_install", None), Some(CCUSAGE_INSTALL_URL));
+        assert_eq!(menu_link("install_msg", None), Some(CCUSAGE_INSTALL_URL));
+        assert_eq!(menu_link("grant_access", None), Some(FULL_DISK_ACCESS_URL));
+        for id in ["title", "session_cost", "quit", "refresh", "model_opus"] {
+            assert_eq!(menu_link(id, None), None, "{}", id);
+        }
+    }
+
+    #[test]
+    fn pricing_items_link_to_their_model_family() {
+        let opus = Some((MenuIdKind::Pricing, "claude-opus-4-20250514"));
+        assert_eq!(menu_link("pricing_x", opus), Some("https://www.anthropic.com/claude/opus"));
+        let unknown = Some((MenuIdKind::Pricing, "some-other-model"));
+        assert_eq!(menu_link("pricing_y", unknown), Some(PRICING_URL));
+        // Other dynamic items never open a browser, whatever their payload
+        assert_eq!(menu_link("model_x", Some((MenuIdKind::Model, "claude-opus-4-20250514"))), None);
+    }
+
+    fn row(id: &str, text: &str) -> MenuEntry {
+        MenuEntry::label(id, text)
+    }
+
+    fn models(text: &str, rows: Vec<MenuEntry>) -> MenuEntry {
+        MenuEntry::submenu("models", text, rows)
+    }
+
+    #[test]
+    fn unchanged_menus_need_no_operations() {
+        let menu = [row("session_cost", "$1.00"), MenuEntry::Separator, models("Models", vec![row("m1", "Opus")])];
+        assert!(diff_menu(&menu, &menu).is_empty());
+    }
+
+    #[test]
+    fn a_cost_change_only_updates_its_row() {
+        let old = [row("session_cost", "$1.00"), row("session_tokens", "1.2K")];
+        let new = [row("session_cost", "$1.25"), row("session_tokens", "1.2K")];
+        assert_eq!(diff_menu(&old, &new), [MenuOp::Update { old: old[0].clone(), new: new[0].clone() }]);
+        assert!(is_in_place_only(&diff_menu(&old, &new)));
+    }
+
+    #[test]
+    fn a_new_model_is_inserted_into_its_submenu() {
+        let old = [row("session_cost", "$1.00"), models("Models", vec![row("m1", "Opus")])];
+        let new = [row("session_cost", "$1.00"), models("Models", vec![row("m1", "Opus"), row("m2", "Sonnet")])];
+        let ops = diff_menu(&old, &new);
+        assert_eq!(ops, [MenuOp::Insert { parent: Some("models".to_string()), index: 1, entry: row("m2", "Sonnet") }]);
+        assert!(!is_in_place_only(&ops));
+    }
+
+    #[test]
+    fn a_retitled_submenu_keeps_its_rows() {
+        let old = [models("Models (1)", vec![row("m1", "Opus")])];
+        let new = [models("Models (2)", vec![row("m1", "Opus"), row("m2", "Sonnet")])];
+        assert_eq!(
+            diff_menu(&old, &new),
+            [
+                MenuOp::Update { old: old[0].clone(), new: new[0].clone() },
+                MenuOp::Insert { parent: Some("models".to_string()), index: 1, entry: row("m2", "Sonnet") },
+            ]
+        );
+    }
+
+    #[test]
+    fn a_dropped_project_is_removed_alone() {
+        let projects = |rows: Vec<MenuEntry>| [MenuEntry::submenu("projects", "Projects", rows)];
+        let old = projects(vec![row("p1", "api"), row("p2", "web"), row("p3", "docs")]);
+        let new = projects(vec![row("p1", "api"), row("p3", "docs")]);
+        assert_eq!(
+            diff_menu(&old, &new),
+            [MenuOp::Remove { parent: Some("projects".to_string()), index: 1, key: "item:p2".to_string() }]
+        );
+    }
+
+    #[test]
+    fn a_moved_row_is_taken_out_and_put_back() {
+        let old = [row("a", "A"), row("b", "B"), row("c", "C")];
+        let new = [row("b", "B"), row("a", "A"), row("c", "C")];
+        assert_eq!(
+            diff_menu(&old, &new),
+            [
+                MenuOp::Remove { parent: None, index: 1, key: "item:b".to_string() },
+                MenuOp::Insert { parent: None, index: 0, entry: row("b", "B") },
+            ]
+        );
+    }
+
+    #[test]
+    fn separators_stay_when_the_rows_after_them_change() {
+        let old = [row("a", "A"), MenuEntry::Separator, row("b", "B")];
+        let new = [row("a", "A"), MenuEntry::Separator, row("c", "C")];
+        assert_eq!(
+            diff_menu(&old, &new),
+            [
+                MenuOp::Remove { parent: None, index: 2, key: "item:b".to_string() },
+                MenuOp::Insert { parent: None, index: 2, entry: row("c", "C") },
+            ]
+        );
+    }
+
+    #[test]
+    fn a_row_changing_kind_is_replaced() {
+        let old = [MenuEntry::item("toggle", "Toggle")];
+        let new = [MenuEntry::check("toggle", "Toggle", true)];
+        assert_eq!(
+            diff_menu(&old, &new),
+            [
+                MenuOp::Remove { parent: None, index: 0, key: "item:toggle".to_string() },
+                MenuOp::Insert { parent: None, index: 0, entry: new[0].clone() },
+            ]
+        );
+    }
+
+    #[test]
+    fn text_changes_apply_while_the_menu_is_open() {
+        let old = [row("session_cost", "$1.00")];
+        let new = [row("session_cost", "$1.10")];
+        let (ops, complete) = plan_update(&old, &new, false);
+        assert_eq!(ops, diff_menu(&old, &new));
+        assert!(complete);
+    }
+
+    #[test]
+    fn structural_changes_wait_but_text_still_updates() {
+        let old = [row("session_cost", "$1.00"), models("Models", vec![row("m1", "Opus")])];
+        let new = [row("session_cost", "$1.10"), models("Models", vec![row("m1", "Opus"), row("m2", "Sonnet")])];
+        let (ops, complete) = plan_update(&old, &new, false);
+        assert_eq!(ops, [MenuOp::Update { old: old[0].clone(), new: new[0].clone() }]);
+        assert!(!complete);
+
+        // Once allowed, the same diff is applied in full
+        let (ops, complete) = plan_update(&old, &new, true);
+        assert_eq!(ops, diff_menu(&old, &new));
+        assert!(complete);
+    }
+
+    #[test]
+    fn a_deferred_change_is_still_pending_on_the_next_update() {
+        // The kept spec is still the old one, so a later text-only refresh doesn't lose the new row
+        let old = [row("a", "A")];
+        let new = [row("a", "A"), row("b", "B")];
+        assert!(plan_update(&old, &new, false).0.is_empty());
+        let later = [row("a", "A2"), row("b", "B")];
+        let (ops, complete) = plan_update(&old, &later, true);
+        assert_eq!(ops.len(), 2);
+        assert!(complete);
+    }
+
+    fn active_session(refresh: RefreshPresentation) -> MenuSpec {
+        let mut state = preview_state(PreviewScenario::ActiveBlock, local(2025, 6, 11, 14, 0), AppSettings::default());
+        state.refresh = refresh;
+        menu_spec(&state)
+    }
+
+    #[test]
+    fn refreshing_only_changes_the_refresh_row() {
+        let idle = active_session(RefreshPresentation::Idle);
+        for refresh in [RefreshPresentation::InProgress, RefreshPresentation::Downloading] {
+            let refreshing = active_session(refresh);
+            let ops = diff_menu(&idle.entries, &refreshing.entries);
+            assert_eq!(ops.len(), 1, "{:?}", refresh);
+            assert!(matches!(&ops[0], MenuOp::Update { new, .. } if entry_id(new) == "refresh"));
+        }
+    }
+
+    #[test]
+    fn updating_ellipsis_cycles_through_three_frames() {
+        let t = Translator::new(&AppSettings::default());
+        let start = local(2025, 6, 11, 14, 0);
+        let frame = |ticks: i64| updating_text(&t, "updating", start + chrono::Duration::milliseconds(400 * ticks));
+        let dots = |text: String| text.chars().rev().take_while(|c| *c == '.').count();
+        assert_eq!([frame(0), frame(1), frame(2)].map(dots), [1, 2, 3]);
+        assert_eq!(frame(3), frame(0));
+    }
+
+    fn instant_after(start: Instant, seconds: u64) -> Instant {
+        start + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn closed_menus_restructure_right_away() {
+        let mut gate = MenuGate::new();
+        assert!(gate.allow_structural(Instant::now()));
+        assert!(!gate.closed());
+    }
+
+    #[test]
+    fn updates_while_open_are_flushed_on_close() {
+        let start = Instant::now();
+        let mut gate = MenuGate::new();
+        gate.opened(start);
+        assert!(!gate.allow_structural(instant_after(start, 10)));
+        assert!(!gate.allow_structural(instant_after(start, 130)));
+        // Both requests collapse into one flush
+        assert!(gate.closed());
+        assert!(!gate.closed());
+        assert!(gate.allow_structural(instant_after(start, 140)));
+    }
+
+    #[test]
+    fn opening_and_closing_without_updates_flushes_nothing() {
+        let start = Instant::now();
+        let mut gate = MenuGate::new();
+        gate.opened(start);
+        assert!(gate.is_open(instant_after(start, 1)));
+        assert!(!gate.closed());
+        assert!(!gate.is_open(instant_after(start, 2)));
+    }
+
+    #[test]
+    fn a_missed_close_stops_holding_updates_back() {
+        let start = Instant::now();
+        let mut gate = MenuGate::new();
+        gate.opened(start);
+        assert!(!gate.allow_structural(instant_after(start, 60)));
+        let later = start + MENU_OPEN_TIMEOUT;
+        assert!(!gate.is_open(later));
+        assert!(gate.allow_structural(later));
+    }
+
+    const FIVE_MODELS: [&str; 5] = [
+        "claude-opus-4-20250514",
+        "claude-sonnet-4-20250514",
+        "claude-3-5-sonnet-20241022",
+        "claude-3-haiku-20240307",
+        "claude-3-opus-20240229",
+    ];
+
+    fn session_with_models(display: ModelsDisplay, models: &[&str]) -> MenuSpec {
+        let settings = AppSettings { models_display: display, ..AppSettings::default() };
+        let mut state = preview_state(PreviewScenario::ActiveBlock, local(2025, 6, 11, 14, 0), settings);
+        let block = state.active_block.as_mut().unwrap();
+        block.models = models.iter().map(|model| model.to_string()).collect();
+        block.model_breakdowns.clear();
+        menu_spec(&state)
+    }
+
+    /// Models listed as submenus, in order
+    fn model_rows(spec: &MenuSpec) -> Vec<&str> {
+        spec.entries
+            .iter()
+            .filter_map(|entry| match spec.ids.payload(entry_id(entry)) {
+                Some((MenuIdKind::Model, model)) => Some(model),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn has_row(spec: &MenuSpec, id: &str) -> bool {
+        spec.entries.iter().any(|entry| entry_id(entry) == id)
+    }
+
+    #[test]
+    fn full_models_display_lists_each_model() {
+        for count in [0, 1, 5] {
+            let spec = session_with_models(ModelsDisplay::Full, &FIVE_MODELS[..count]);
+            assert_eq!(model_rows(&spec), FIVE_MODELS[..count], "{} models", count);
+            assert_eq!(has_row(&spec, "models_header"), count > 0, "{} models", count);
+            assert!(!has_row(&spec, "models_compact"));
+        }
+    }
+
+    #[test]
+    fn compact_models_display_is_one_row() {
+        let spec = session_with_models(ModelsDisplay::Compact, &[]);
+        assert!(!has_row(&spec, "models_compact"));
+
+        let spec = session_with_models(ModelsDisplay::Compact, &FIVE_MODELS[..1]);
+        assert_eq!(entry_text(&spec, "models_compact"), "Models: Opus 4");
+
+        // Five different names, of which the first three are shown
+        let spec = session_with_models(ModelsDisplay::Compact, &FIVE_MODELS);
+        assert_eq!(entry_text(&spec, "models_compact"), "Models: Opus 4, Sonnet 4, Sonnet 3.5 …+2 more");
+        assert!(model_rows(&spec).is_empty());
+        assert!(!has_row(&spec, "models_header"));
+    }
+
+    #[test]
+    fn compact_models_display_dedupes_display_names() {
+        let models = ["claude-3-opus-20240229", "claude-opus-4-1-20250805", "claude-3-haiku-20240307"];
+        let spec = session_with_models(ModelsDisplay::Compact, &models);
+        assert_eq!(entry_text(&spec, "models_compact"), "Models: Opus, Haiku");
+    }
+
+    #[test]
+    fn hidden_models_display_shows_nothing() {
+        for count in [0, 1, 5] {
+            let spec = session_with_models(ModelsDisplay::Hidden, &FIVE_MODELS[..count]);
+            assert!(model_rows(&spec).is_empty(), "{} models", count);
+            assert!(!has_row(&spec, "models_header") && !has_row(&spec, "models_compact"));
+        }
+    }
+
+    #[test]
+    fn todays_conversations_copy_their_full_id() {
+        let mut state = idle_morning();
+        assert!(!has_row(&menu_spec(&state), "conversations_menu"));
+
+        let long_id = "0f3c9a1e-7b2d-4c55-9e61-2a8d4f0b7c13";
+        state.conversations = vec![
+            ConversationCost { id: long_id.to_string(), cost: 9.0, tokens: 1_234_567 },
+            ConversationCost { id: "short".to_string(), cost: 0.5, tokens: 950 },
+        ];
+        let spec = menu_spec(&state);
+        let Some(MenuEntry::Submenu { entries, .. }) =
+            spec.entries.iter().find(|entry| entry_id(entry) == "conversations_menu")
+        else {
+            panic!("no conversations submenu");
+        };
+        let rows: Vec<(&str, Option<(MenuIdKind, &str)>)> = entries
+            .iter()
+            .map(|entry| match entry {
+                MenuEntry::Item { id, text, enabled: true, .. } => (text.as_str(), spec.ids.payload(id)),
+                _ => panic!("not a clickable row: {:?}", entry),
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("0f3c9a1e…  $9.00 · 1.2M tokens", Some((MenuIdKind::Conversation, long_id))),
+                ("short  $0.50 · 950 tokens", Some((MenuIdKind::Conversation, "short"))),
+            ]
+        );
+    }
+
+    #[test]
+    fn repeated_values_get_numbered_ids_in_order() {
+        let mut ids = MenuIds::default();
+        let allocated: Vec<String> = (0..3).map(|_| ids.allocate(MenuIdKind::Model, "claude-opus-4")).collect();
+        assert_eq!(allocated, ["model_claude-opus-4", "model_claude-opus-4_2", "model_claude-opus-4_3"]);
+        for id in &allocated {
+            assert_eq!(ids.payload(id), Some((MenuIdKind::Model, "claude-opus-4")));
+        }
+        // The same menu state always gets the same ids
+        let mut again = MenuIds::default();
+        let reallocated: Vec<String> = (0..3).map(|_| again.allocate(MenuIdKind::Model, "claude-opus-4")).collect();
+        assert_eq!(allocated, reallocated);
+    }
+
+    #[test]
+    fn different_values_with_the_same_id_keep_their_own_payload() {
+        let mut ids = MenuIds::default();
+        // "model_" + "tokens_x" reads the same as "model_tokens_" + "x"
+        let tokens = ids.allocate(MenuIdKind::ModelTokens, "x");
+        let model = ids.allocate(MenuIdKind::Model, "tokens_x");
+        assert_eq!((tokens.as_str(), model.as_str()), ("model_tokens_x", "model_tokens_x_2"));
+        assert_eq!(ids.payload(&tokens), Some((MenuIdKind::ModelTokens, "x")));
+        assert_eq!(ids.payload(&model), Some((MenuIdKind::Model, "tokens_x")));
+
+        // A value that already looks like a numbered duplicate
+        ids.allocate(MenuIdKind::Model, "opus");
+        let second = ids.allocate(MenuIdKind::Model, "opus");
+        let lookalike = ids.allocate(MenuIdKind::Model, "opus_2");
+        assert_eq!((second.as_str(), lookalike.as_str()), ("model_opus_2", "model_opus_2_2"));
+        assert_eq!(ids.payload(&lookalike), Some((MenuIdKind::Model, "opus_2")));
+
+        // Conversations from different projects can share an id's start, never the whole id
+        let first = ids.allocate(MenuIdKind::Conversation, "0f3c9a1e-aaaa");
+        let other = ids.allocate(MenuIdKind::Conversation, "0f3c9a1e-bbbb");
+        assert_ne!(first, other);
+        assert_eq!(ids.payload(&other), Some((MenuIdKind::Conversation, "0f3c9a1e-bbbb")));
+    }
+
+    #[test]
+    fn fixed_items_have_no_payload() {
+        let spec = menu_spec(&idle_morning());
+        assert_eq!(spec.ids.payload("refresh"), None);
+        assert_eq!(spec.ids.payload("quit"), None);
+        assert_eq!(spec.ids.payload("model_unknown"), None);
+    }
+
+    /// Every id in `entries` and their submenus allocated as `kind`
+    fn allocated_as(spec: &MenuSpec, entries: &[MenuEntry], kind: MenuIdKind) -> Vec<String> {
+        let mut found = Vec::new();
+        for entry in entries {
+            if spec.ids.payload(entry_id(entry)).is_some_and(|(allocated, _)| allocated == kind) {
+                found.push(entry_id(entry).to_string());
+            }
+            if let MenuEntry::Submenu { entries, .. } = entry {
+                found.extend(allocated_as(spec, entries, kind));
+            }
+        }
+        found
+    }
+
+    #[test]
+    fn a_model_listed_twice_gets_two_working_submenus() {
+        let opus = "claude-opus-4-20250514";
+        let spec = session_with_models(ModelsDisplay::Full, &[opus, opus]);
+        assert_eq!(model_rows(&spec), [opus, opus]);
+        assert_eq!(
+            allocated_as(&spec, &spec.entries, MenuIdKind::Model),
+            ["model_claude-opus-4-20250514", "model_claude-opus-4-20250514_2"]
+        );
+
+        // Both "Open pricing" items open the model's page, the way the click handler looks them up
+        let pricing = allocated_as(&spec, &spec.entries, MenuIdKind::Pricing);
+        assert_eq!(pricing.len(), 2);
+        assert_ne!(pricing[0], pricing[1]);
+        for id in &pricing {
+            assert_eq!(menu_link(id, spec.ids.payload(id)), Some(model_pricing_url(opus)));
+        }
+        assert_ne!(model_pricing_url(opus), PRICING_URL);
+    }
+}
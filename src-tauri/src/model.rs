@@ -0,0 +1,375 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct BlockData {
+    pub(crate) id: String,
+    #[serde(rename = "startTime")]
+    pub(crate) start_time: String,
+    #[serde(rename = "endTime")]
+    pub(crate) end_time: String,
+    #[serde(rename = "actualEndTime", default)]
+    pub(crate) actual_end_time: Option<String>,
+    #[serde(rename = "isActive")]
+    pub(crate) is_active: bool,
+    #[serde(rename = "isGap", default)]
+    pub(crate) is_gap: bool,
+    #[serde(rename = "tokenCounts")]
+    pub(crate) token_counts: TokenCounts,
+    #[serde(rename = "costUSD")]
+    pub(crate) cost_usd: f64,
+    /// Set when ccusage priced the block from its offline table rather than live pricing
+    #[serde(rename = "isEstimated", default)]
+    pub(crate) is_estimated: bool,
+    pub(crate) models: Vec<String>,
+    #[serde(rename = "modelBreakdowns", default)]
+    pub(crate) model_breakdowns: Vec<ModelBreakdown>,
+}
+
+/// Per-model usage within a block, when ccusage reports it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ModelBreakdown {
+    #[serde(rename = "modelName")]
+    pub(crate) model_name: String,
+    #[serde(rename = "inputTokens", default)]
+    pub(crate) input_tokens: u64,
+    #[serde(rename = "outputTokens", default)]
+    pub(crate) output_tokens: u64,
+    #[serde(rename = "cacheCreationTokens", default)]
+    pub(crate) cache_creation_tokens: u64,
+    #[serde(rename = "cacheReadTokens", default)]
+    pub(crate) cache_read_tokens: u64,
+    #[serde(default)]
+    pub(crate) cost: f64,
+}
+
+impl ModelBreakdown {
+    pub(crate) fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens + self.cache_creation_tokens + self.cache_read_tokens
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct TokenCounts {
+    #[serde(rename = "inputTokens")]
+    pub(crate) input_tokens: u64,
+    #[serde(rename = "outputTokens")]
+    pub(crate) output_tokens: u64,
+    #[serde(rename = "cacheCreationInputTokens")]
+    pub(crate) cache_creation_input_tokens: u64,
+    #[serde(rename = "cacheReadInputTokens")]
+    pub(crate) cache_read_input_tokens: u64,
+}
+
+impl TokenCounts {
+    pub(crate) fn total(&self) -> u64 {
+        self.input_tokens
+            + self.output_tokens
+            + self.cache_creation_input_tokens
+            + self.cache_read_input_tokens
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct BlocksResponse {
+    pub(crate) blocks: Vec<BlockData>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct DailyUsage {
+    pub(crate) date: String,
+    #[serde(rename = "totalTokens", default)]
+    pub(crate) total_tokens: u64,
+    #[serde(rename = "totalCost")]
+    pub(crate) total_cost: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct DailyResponse {
+    pub(crate) daily: Vec<DailyUsage>,
+}
+
+/// One conversation from `ccusage session --json`, listed once per project it ran in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct SessionUsage {
+    #[serde(rename = "sessionId")]
+    pub(crate) session_id: String,
+    #[serde(rename = "totalTokens", default)]
+    pub(crate) total_tokens: u64,
+    #[serde(rename = "totalCost")]
+    pub(crate) total_cost: f64,
+    /// Local date of the last message, `YYYY-MM-DD`
+    #[serde(rename = "lastActivity")]
+    pub(crate) last_activity: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct SessionResponse {
+    pub(crate) sessions: Vec<SessionUsage>,
+}
+
+/// Fields a block can't be parsed without, as dotted JSON paths
+const REQUIRED_BLOCK_FIELDS: &[&str] = &[
+    "id",
+    "startTime",
+    "endTime",
+    "isActive",
+    "tokenCounts.inputTokens",
+    "tokenCounts.outputTokens",
+    "tokenCounts.cacheCreationInputTokens",
+    "tokenCounts.cacheReadInputTokens",
+    "costUSD",
+    "models",
+];
+
+/// Fields a daily entry can't be parsed without
+const REQUIRED_DAILY_FIELDS: &[&str] = &["date", "totalCost"];
+
+/// Fields a session entry can't be parsed without
+const REQUIRED_SESSION_FIELDS: &[&str] = &["sessionId", "totalCost", "lastActivity"];
+
+fn has_path(value: &serde_json::Value, path: &str) -> bool {
+    path.split('.').try_fold(value, |value, key| value.get(key)).is_some_and(|value| !value.is_null())
+}
+
+/// Notes on what a ccusage response lacks compared to what `BlockData` and
+/// `DailyUsage` or `SessionUsage` expect, e.g. "missing: tokenCounts.cacheReadInputTokens (in
+/// 3 of 12 blocks)". Empty when every required field is present.
+pub(crate) fn schema_notes(response: &serde_json::Value) -> Vec<String> {
+    let (key, fields) = if response.get("daily").is_some() {
+        ("daily", REQUIRED_DAILY_FIELDS)
+    } else if response.get("sessions").is_some() {
+        ("sessions", REQUIRED_SESSION_FIELDS)
+    } else {
+        ("blocks", REQUIRED_BLOCK_FIELDS)
+    };
+    let Some(entries) = response.get(key).and_then(serde_json::Value::as_array) else {
+        return vec![format!("missing: {} (expected an array)", key)];
+    };
+    fields
+        .iter()
+        .filter_map(|field| {
+            let missing = entries.iter().filter(|entry| !has_path(entry, field)).count();
+            (missing > 0).then(|| format!("missing: {} (in {} of {} {})", field, missing, entries.len(), key))
+        })
+        .collect()
+}
+
+pub(crate) fn parse_local_time(timestamp: &str) -> Option<chrono::DateTime<chrono::Local>> {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|dt| dt.with_timezone(&chrono::Local))
+}
+
+/// Builders for tests, so each only spells out the fields it cares about
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::{BlockData, TokenCounts};
+    use chrono::TimeZone;
+
+    /// A local time on the test machine, whatever its time zone
+    pub(crate) fn local(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> chrono::DateTime<chrono::Local> {
+        chrono::Local
+            .with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .single()
+            .expect("an unambiguous local time")
+    }
+
+    /// A finished block spanning `start..end` with no tokens or models
+    pub(crate) fn block(
+        start: chrono::DateTime<chrono::Local>,
+        end: chrono::DateTime<chrono::Local>,
+        cost: f64,
+    ) -> BlockData {
+        BlockData {
+            id: start.to_rfc3339(),
+            start_time: start.to_rfc3339(),
+            end_time: end.to_rfc3339(),
+            actual_end_time: None,
+            is_active: false,
+            is_gap: false,
+            token_counts: TokenCounts {
+                input_tokens: 0,
+                output_tokens: 0,
+                cache_creation_input_tokens: 0,
+                cache_read_input_tokens: 0,
+            },
+            cost_usd: cost,
+            is_estimated: false,
+            models: Vec::new(),
+            model_breakdowns: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const BLOCKS_JSON: &str = r#"{
+        "blocks": [
+            {
+                "id": "2025-06-11T09:00:00.000Z",
+                "startTime": "2025-06-11T09:00:00.000Z",
+                "endTime": "2025-06-11T14:00:00.000Z",
+                "actualEndTime": "2025-06-11T10:12:00.000Z",
+                "isActive": true,
+                "isGap": false,
+                "entries": 12,
+                "tokenCounts": {
+                    "inputTokens": 1200,
+                    "outputTokens": 3400,
+                    "cacheCreationInputTokens": 500,
+                    "cacheReadInputTokens": 90000
+                },
+                "totalTokens": 95100,
+                "costUSD": 1.25,
+                "models": ["claude-sonnet-4-20250514"],
+                "burnRate": null,
+                "projection": null
+            },
+            {
+                "id": "gap-2025-06-11T04:00:00.000Z",
+                "startTime": "2025-06-11T04:00:00.000Z",
+                "endTime": "2025-06-11T09:00:00.000Z",
+                "isActive": false,
+                "isGap": true,
+                "entries": 0,
+                "tokenCounts": {
+                    "inputTokens": 0,
+                    "outputTokens": 0,
+                    "cacheCreationInputTokens": 0,
+                    "cacheReadInputTokens": 0
+                },
+                "totalTokens": 0,
+                "costUSD": 0,
+                "models": []
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn parses_a_blocks_response() {
+        let response: BlocksResponse = serde_json::from_str(BLOCKS_JSON).unwrap();
+        assert_eq!(response.blocks.len(), 2);
+
+        let active = &response.blocks[0];
+        assert!(active.is_active && !active.is_gap && !active.is_estimated);
+        assert_eq!(active.actual_end_time.as_deref(), Some("2025-06-11T10:12:00.000Z"));
+        assert_eq!(active.token_counts.total(), 95_100);
+        assert_eq!(active.cost_usd, 1.25);
+        assert_eq!(active.models, ["claude-sonnet-4-20250514"]);
+        assert!(active.model_breakdowns.is_empty());
+
+        let gap = &response.blocks[1];
+        assert!(gap.is_gap);
+        assert_eq!(gap.actual_end_time, None);
+        assert_eq!(gap.cost_usd, 0.0);
+    }
+
+    #[test]
+    fn parses_model_breakdowns_and_estimated_costs() {
+        let json = r#"{
+            "id": "b",
+            "startTime": "2025-06-11T09:00:00.000Z",
+            "endTime": "2025-06-11T14:00:00.000Z",
+            "isActive": false,
+            "isEstimated": true,
+            "tokenCounts": {
+                "inputTokens": 10,
+                "outputTokens": 20,
+                "cacheCreationInputTokens": 0,
+                "cacheReadInputTokens": 0
+            },
+            "costUSD": 0.5,
+            "models": ["claude-opus-4-20250514"],
+            "modelBreakdowns": [
+                { "modelName": "claude-opus-4-20250514", "inputTokens": 10, "outputTokens": 20, "cost": 0.5 }
+            ]
+        }"#;
+        let block: BlockData = serde_json::from_str(json).unwrap();
+        assert!(block.is_estimated);
+        assert!(!block.is_gap);
+        assert_eq!(block.model_breakdowns.len(), 1);
+        assert_eq!(block.model_breakdowns[0].total_tokens(), 30);
+        assert_eq!(block.model_breakdowns[0].cache_read_tokens, 0);
+    }
+
+    #[test]
+    fn a_block_missing_a_required_field_fails_to_parse() {
+        let without_cost = BLOCKS_JSON.replace(r#""costUSD": 1.25,"#, "");
+        assert!(serde_json::from_str::<BlocksResponse>(&without_cost).is_err());
+        assert!(serde_json::from_str::<BlocksResponse>(r#"{"daily": []}"#).is_err());
+    }
+
+    #[test]
+    fn parses_rfc3339_timestamps_into_local_time() {
+        let parsed = parse_local_time("2025-06-11T09:00:00.000Z").unwrap();
+        assert_eq!(parsed, chrono::Utc.with_ymd_and_hms(2025, 6, 11, 9, 0, 0).unwrap());
+        let offset = parse_local_time("2025-06-11T11:00:00+02:00").unwrap();
+        assert_eq!(offset, parsed);
+    }
+
+    #[test]
+    fn rejects_timestamps_that_are_not_rfc3339() {
+        assert_eq!(parse_local_time("2025-06-11 09:00"), None);
+        assert_eq!(parse_local_time("2025-06-11"), None);
+        assert_eq!(parse_local_time(""), None);
+    }
+
+    #[test]
+    fn a_valid_response_has_no_schema_notes() {
+        let response: serde_json::Value = serde_json::from_str(BLOCKS_JSON).unwrap();
+        assert!(schema_notes(&response).is_empty());
+        let daily = serde_json::json!({ "daily": [{ "date": "2025-06-11", "totalCost": 1.5 }] });
+        assert!(schema_notes(&daily).is_empty());
+        let sessions = serde_json::json!({
+            "sessions": [
+                { "sessionId": "a1b2c3d4", "totalTokens": 1200, "totalCost": 1.5, "lastActivity": "2025-06-11" }
+            ]
+        });
+        assert!(schema_notes(&sessions).is_empty());
+        let parsed: SessionResponse = serde_json::from_value(sessions).unwrap();
+        assert_eq!(parsed.sessions[0].session_id, "a1b2c3d4");
+    }
+
+    #[test]
+    fn missing_fields_are_counted_per_block() {
+        let mut response: serde_json::Value = serde_json::from_str(BLOCKS_JSON).unwrap();
+        let first = &mut response["blocks"][0];
+        first["tokenCounts"].as_object_mut().unwrap().remove("cacheReadInputTokens");
+        first["costUSD"] = serde_json::Value::Null;
+        response["blocks"][1].as_object_mut().unwrap().remove("tokenCounts");
+        assert_eq!(
+            schema_notes(&response),
+            [
+                "missing: tokenCounts.inputTokens (in 1 of 2 blocks)",
+                "missing: tokenCounts.outputTokens (in 1 of 2 blocks)",
+                "missing: tokenCounts.cacheCreationInputTokens (in 1 of 2 blocks)",
+                "missing: tokenCounts.cacheReadInputTokens (in 2 of 2 blocks)",
+                "missing: costUSD (in 1 of 2 blocks)",
+            ]
+        );
+    }
+
+    #[test]
+    fn renamed_fields_show_up_as_missing() {
+        let daily = serde_json::json!({ "daily": [{ "date": "2025-06-11", "cost": 1.5 }, { "day": "2025-06-12" }] });
+        assert_eq!(
+            schema_notes(&daily),
+            ["missing: date (in 1 of 2 daily)", "missing: totalCost (in 2 of 2 daily)"]
+        );
+        let sessions =
+            serde_json::json!({ "sessions": [{ "id": "a1b2c3d4", "totalCost": 1.5, "lastActivity": "2025-06-11" }] });
+        assert_eq!(schema_notes(&sessions), ["missing: sessionId (in 1 of 1 sessions)"]);
+    }
+
+    #[test]
+    fn responses_without_an_array_are_noted() {
+        assert_eq!(schema_notes(&serde_json::json!({ "blocks": {} })), ["missing: blocks (expected an array)"]);
+        assert_eq!(schema_notes(&serde_json::json!([1, 2])), ["missing: blocks (expected an array)"]);
+        assert_eq!(schema_notes(&serde_json::json!({ "daily": null })), ["missing: daily (expected an array)"]);
+        assert!(schema_notes(&serde_json::json!({ "blocks": [] })).is_empty());
+    }
+}
+
@@ -0,0 +1,148 @@
+use std::sync::Mutex;
+
+use crate::model::BlockData;
+
+/// Models that joined the active block after it was first seen, so a switch
+/// such as Sonnet to Opus partway through doesn't go unnoticed
+#[derive(Debug, Clone, Default)]
+struct BlockModels {
+    block_id: String,
+    /// Every model seen in the block so far
+    known: Vec<String>,
+    /// (model, when it was first seen), for models that weren't there at first
+    added: Vec<(String, chrono::DateTime<chrono::Local>)>,
+}
+
+static BLOCK_MODELS: Mutex<Option<BlockModels>> = Mutex::new(None);
+
+/// Models in `current` that aren't in `previous`, in `current`'s order.
+/// Models that dropped out aren't reported.
+pub(crate) fn new_models<'a>(previous: &[String], current: &'a [String]) -> Vec<&'a str> {
+    current
+        .iter()
+        .filter(|model| !previous.contains(model))
+        .map(String::as_str)
+        .collect()
+}
+
+/// Compare the active block's models with earlier refreshes of the same
+/// block, returning the ones seen for the first time. A block's first refresh
+/// only records its models, and a new block or no block starts over.
+pub(crate) fn record_block_models(block: Option<&BlockData>, now: chrono::DateTime<chrono::Local>) -> Vec<String> {
+    let mut current = BLOCK_MODELS.lock().unwrap();
+    let (tracked, added) = track_models(current.take(), block, now);
+    *current = tracked;
+    added
+}
+
+/// `record_block_models` for one refresh: what to keep tracking, and the
+/// models seen for the first time
+fn track_models(
+    tracked: Option<BlockModels>,
+    block: Option<&BlockData>,
+    now: chrono::DateTime<chrono::Local>,
+) -> (Option<BlockModels>, Vec<String>) {
+    let Some(block) = block else {
+        return (None, Vec::new());
+    };
+    let Some(mut tracked) = tracked.filter(|tracked| tracked.block_id == block.id) else {
+        let fresh = BlockModels { block_id: block.id.clone(), known: block.models.clone(), added: Vec::new() };
+        return (Some(fresh), Vec::new());
+    };
+    let added: Vec<String> = new_models(&tracked.known, &block.models).into_iter().map(String::from).collect();
+    for model in &added {
+        tracked.known.push(model.clone());
+        tracked.added.push((model.clone(), now));
+    }
+    (Some(tracked), added)
+}
+
+/// Models that started being used partway through `block_id`, with when
+pub(crate) fn models_added(block_id: &str) -> Vec<(String, chrono::DateTime<chrono::Local>)> {
+    BLOCK_MODELS
+        .lock()
+        .unwrap()
+        .as_ref()
+        .filter(|tracked| tracked.block_id == block_id)
+        .map(|tracked| tracked.added.clone())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::fixtures::{block, local};
+
+    const SONNET: &str = "claude-sonnet-4-20250514";
+    const OPUS: &str = "claude-opus-4-20250514";
+    const HAIKU: &str = "claude-3-haiku-20240307";
+
+    fn models(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    /// The 1 PM block, using `names`
+    fn afternoon_block(names: &[&str]) -> BlockData {
+        let mut afternoon = block(local(2025, 6, 11, 13, 0), local(2025, 6, 11, 18, 0), 1.0);
+        afternoon.models = models(names);
+        afternoon
+    }
+
+    #[test]
+    fn new_models_reports_only_additions() {
+        assert_eq!(new_models(&models(&[SONNET]), &models(&[SONNET, OPUS])), [OPUS]);
+        assert_eq!(new_models(&models(&[SONNET]), &models(&[HAIKU, SONNET, OPUS])), [HAIKU, OPUS]);
+        // Removed models and reorderings aren't changes
+        assert!(new_models(&models(&[SONNET, OPUS]), &models(&[OPUS])).is_empty());
+        assert!(new_models(&models(&[SONNET, OPUS]), &models(&[OPUS, SONNET])).is_empty());
+        assert!(new_models(&models(&[SONNET]), &models(&[SONNET])).is_empty());
+        assert!(new_models(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn a_model_joining_the_block_is_recorded_with_when() {
+        let (tracked, added) = track_models(None, Some(&afternoon_block(&[SONNET])), local(2025, 6, 11, 13, 5));
+        // The block's first refresh only records what it started with
+        assert!(added.is_empty());
+
+        let switched = local(2025, 6, 11, 15, 12);
+        let (tracked, added) = track_models(tracked, Some(&afternoon_block(&[SONNET, OPUS])), switched);
+        assert_eq!(added, [OPUS]);
+        let tracked = tracked.unwrap();
+        assert_eq!(tracked.added, [(OPUS.to_string(), switched)]);
+
+        // Later refreshes keep the first-seen time
+        let (tracked, added) =
+            track_models(Some(tracked), Some(&afternoon_block(&[SONNET, OPUS])), local(2025, 6, 11, 15, 30));
+        assert!(added.is_empty());
+        assert_eq!(tracked.unwrap().added, [(OPUS.to_string(), switched)]);
+    }
+
+    #[test]
+    fn a_model_dropping_out_and_back_is_not_new() {
+        let (tracked, _) = track_models(None, Some(&afternoon_block(&[SONNET, OPUS])), local(2025, 6, 11, 13, 5));
+        let (tracked, added) = track_models(tracked, Some(&afternoon_block(&[OPUS])), local(2025, 6, 11, 14, 0));
+        assert!(added.is_empty());
+        let (tracked, added) =
+            track_models(tracked, Some(&afternoon_block(&[OPUS, SONNET])), local(2025, 6, 11, 14, 30));
+        assert!(added.is_empty());
+        assert!(tracked.unwrap().added.is_empty());
+    }
+
+    #[test]
+    fn a_new_block_or_no_block_starts_over() {
+        let (tracked, _) = track_models(None, Some(&afternoon_block(&[SONNET])), local(2025, 6, 11, 13, 5));
+        let (tracked, _) = track_models(tracked, Some(&afternoon_block(&[SONNET, OPUS])), local(2025, 6, 11, 15, 12));
+
+        let mut evening = block(local(2025, 6, 11, 18, 0), local(2025, 6, 11, 23, 0), 0.5);
+        evening.models = models(&[SONNET, OPUS, HAIKU]);
+        let (next, added) = track_models(tracked.clone(), Some(&evening), local(2025, 6, 11, 18, 5));
+        assert!(added.is_empty());
+        let next = next.unwrap();
+        assert_eq!(next.block_id, evening.id);
+        assert!(next.added.is_empty());
+
+        let (none, added) = track_models(tracked, None, local(2025, 6, 11, 18, 5));
+        assert!(none.is_none() && added.is_empty());
+    }
+}
@@ -0,0 +1,120 @@
+use std::time::Duration;
+use tauri::AppHandle;
+use tauri_plugin_dialog::{MessageDialogButtons, MessageDialogKind};
+
+use crate::capability::dialogs;
+use crate::events::emit_settings_changed;
+use crate::fetch::{run_shell_with_input, EXTENDED_PATH};
+use crate::state::{current_settings, settings_path, update_settings, Availability};
+
+/// How long `npm install -g ccusage` may take before it's given up on
+const INSTALL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// A first run: onboarding hasn't been completed and no settings have been
+/// saved yet. Checked before anything writes the settings file.
+pub(crate) fn needs_onboarding() -> bool {
+    !current_settings().onboarding_complete && settings_path().is_some_and(|path| !path.exists())
+}
+
+/// Ask a question in a native dialog, returning whether the first button was chosen.
+/// Blocks, so only call it off the main thread.
+fn ask(app: &AppHandle, title: &str, message: &str, yes: &str, no: &str) -> bool {
+    let Some(dialogs) = dialogs(app) else {
+        return false;
+    };
+    dialogs
+        .message(message)
+        .title(title)
+        .kind(MessageDialogKind::Info)
+        .buttons(MessageDialogButtons::OkCancelCustom(yes.to_string(), no.to_string()))
+        .blocking_show()
+}
+
+fn tell(app: &AppHandle, title: &str, message: &str) {
+    let Some(dialogs) = dialogs(app) else {
+        return;
+    };
+    dialogs
+        .message(message)
+        .title(title)
+        .kind(MessageDialogKind::Info)
+        .buttons(MessageDialogButtons::OkCustom("Continue".to_string()))
+        .blocking_show();
+}
+
+/// Walk a new user through what the app does, installing ccusage if the first
+/// refresh couldn't find it, and the refresh interval. Budgets need typing, so
+/// they're pointed at in the settings file. `on_installed` runs after ccusage
+/// was installed, to refresh with it.
+pub(crate) fn run_onboarding(
+    app: &AppHandle,
+    availability: Availability,
+    on_installed: impl FnOnce() + Send + 'static,
+) {
+    // Left for the next launch rather than marked complete without being shown
+    if dialogs(app).is_none() {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        tell(
+            &app,
+            "Welcome to CCUsage",
+            "CCUsage shows what your current Claude Code session costs, right in the menubar. \
+             Click the icon for today's total, token counts and more.\n\n\
+             Usage is read with ccusage, which works from Claude Code's own logs.",
+        );
+
+        if availability == Availability::NotFound
+            && ask(
+                &app,
+                "Install ccusage",
+                "ccusage wasn't found. Install it now with \"npm install -g ccusage\"? This needs Node.js.",
+                "Install",
+                "Not Now",
+            )
+        {
+            let command = format!("{} npm install -g ccusage", EXTENDED_PATH);
+            match tauri::async_runtime::block_on(run_shell_with_input(&command, "", INSTALL_TIMEOUT)) {
+                Ok(()) => {
+                    tell(&app, "Install ccusage", "ccusage is installed.");
+                    on_installed();
+                }
+                Err(e) => tell(
+                    &app,
+                    "Install ccusage",
+                    &format!("Installing ccusage failed ({}). The menu has a link to install it by hand.", e),
+                ),
+            }
+        }
+
+        let timed_to_blocks = ask(
+            &app,
+            "Refresh interval",
+            "How often should usage refresh? The recommended setting refreshes every few minutes and right \
+             after each session ends. Every 5 minutes uses less battery.",
+            "Recommended",
+            "Every 5 Minutes",
+        );
+        let changed = update_settings(|settings| {
+            if !timed_to_blocks {
+                settings.align_refresh_to_blocks = false;
+                settings.refresh_interval_secs = 300;
+            }
+            settings.onboarding_complete = true;
+        });
+        emit_settings_changed(&app, changed);
+
+        let open_settings = ask(
+            &app,
+            "Budgets",
+            "To be warned when spending passes a limit, set block_budget, daily_budget, weekly_budget or \
+             monthly_budget in the settings file. You can open it later from Settings → Open Settings File.",
+            "Open Settings File",
+            "Done",
+        );
+        if let Some(path) = settings_path().filter(|_| open_settings) {
+            let _ = tauri_plugin_opener::open_path(path.to_string_lossy().to_string(), None::<String>);
+        }
+    });
+}
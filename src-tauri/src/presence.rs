@@ -0,0 +1,54 @@
+use crate::fetch::CommandRunner;
+
+/// Executable name of the Claude Code CLI
+#[cfg(windows)]
+const CLAUDE_PROCESS: &str = "claude.exe";
+#[cfg(not(windows))]
+const CLAUDE_PROCESS: &str = "claude";
+
+/// Whether a Claude Code process is running, or `None` if the process list couldn't be read
+#[cfg(not(windows))]
+pub(crate) async fn is_claude_running(runner: &dyn CommandRunner) -> Option<bool> {
+    let args = ["-A", "-o", "comm="].map(String::from);
+    let output = runner.output("ps", &args, &[]).await.ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    // `comm` is a full path on macOS and a bare name on Linux
+    Some(String::from_utf8_lossy(&output.stdout).lines().any(|command| {
+        command.trim().rsplit('/').next() == Some(CLAUDE_PROCESS)
+    }))
+}
+
+#[cfg(windows)]
+pub(crate) async fn is_claude_running(runner: &dyn CommandRunner) -> Option<bool> {
+    let filter = format!("IMAGENAME eq {}", CLAUDE_PROCESS);
+    let args = ["/FI", &filter, "/FO", "CSV", "/NH"].map(String::from);
+    let output = runner.output("tasklist", &args, &[]).await.ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).contains(CLAUDE_PROCESS))
+}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use super::*;
+    use crate::fetch::fake::{failure, success, FakeRunner};
+
+    fn running(result: std::io::Result<std::process::Output>) -> Option<bool> {
+        let runner = FakeRunner::new([result]);
+        let running = tauri::async_runtime::block_on(is_claude_running(&runner));
+        assert_eq!(runner.calls.lock().unwrap()[0].0, "ps");
+        running
+    }
+
+    #[test]
+    fn claude_is_found_by_name_or_path() {
+        assert_eq!(running(success("launchd\n/bin/zsh\n/Users/me/.local/bin/claude\n")), Some(true));
+        assert_eq!(running(success("  claude  \n")), Some(true));
+        assert_eq!(running(success("/bin/zsh\n/usr/bin/claude-helper\n")), Some(false));
+        assert_eq!(running(failure("ps: not permitted")), None);
+    }
+}
+
@@ -0,0 +1,193 @@
+use std::sync::Mutex;
+
+use crate::lifetime::LifetimeSummary;
+use crate::menu::{MenuState, RefreshPresentation};
+use crate::model::{BlockData, DailyUsage, TokenCounts};
+use crate::state::{AppSettings, Availability, RefreshTrigger};
+use crate::stats::ModelExclusion;
+
+/// Canned situations the menu can be rendered in without real data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PreviewScenario {
+    ActiveBlock,
+    NoSession,
+    Idle,
+    EmptyData,
+    Unavailable,
+    PartialData,
+    Stale,
+}
+
+impl PreviewScenario {
+    pub(crate) const ALL: [PreviewScenario; 7] = [
+        PreviewScenario::ActiveBlock,
+        PreviewScenario::NoSession,
+        PreviewScenario::Idle,
+        PreviewScenario::EmptyData,
+        PreviewScenario::Unavailable,
+        PreviewScenario::PartialData,
+        PreviewScenario::Stale,
+    ];
+
+    pub(crate) fn id(self) -> &'static str {
+        match self {
+            PreviewScenario::ActiveBlock => "preview_active",
+            PreviewScenario::NoSession => "preview_no_session",
+            PreviewScenario::Idle => "preview_idle",
+            PreviewScenario::EmptyData => "preview_empty",
+            PreviewScenario::Unavailable => "preview_unavailable",
+            PreviewScenario::PartialData => "preview_partial",
+            PreviewScenario::Stale => "preview_stale",
+        }
+    }
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            PreviewScenario::ActiveBlock => "Active session",
+            PreviewScenario::NoSession => "No session",
+            PreviewScenario::Idle => "No usage yet today",
+            PreviewScenario::EmptyData => "Empty data",
+            PreviewScenario::Unavailable => "ccusage unavailable",
+            PreviewScenario::PartialData => "Partial data",
+            PreviewScenario::Stale => "Stale data",
+        }
+    }
+
+    pub(crate) fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scenario| scenario.id() == id)
+    }
+}
+
+/// Scenario the menu is currently previewing instead of live data
+pub(crate) static PREVIEW: Mutex<Option<PreviewScenario>> = Mutex::new(None);
+
+pub(crate) fn sample_block(
+    now: chrono::DateTime<chrono::Local>,
+    started_minutes_ago: i64,
+    cost_usd: f64,
+    models: &[&str],
+    is_active: bool,
+) -> BlockData {
+    let start = now - chrono::Duration::minutes(started_minutes_ago);
+    BlockData {
+        id: start.to_rfc3339(),
+        start_time: start.to_rfc3339(),
+        end_time: (start + chrono::Duration::hours(5)).to_rfc3339(),
+        actual_end_time: None,
+        is_active,
+        is_gap: false,
+        token_counts: TokenCounts {
+            input_tokens: (cost_usd * 1_000.0) as u64,
+            output_tokens: (cost_usd * 800.0) as u64,
+            cache_creation_input_tokens: (cost_usd * 5_000.0) as u64,
+            cache_read_input_tokens: (cost_usd * 120_000.0) as u64,
+        },
+        cost_usd,
+        is_estimated: false,
+        models: models.iter().map(|model| model.to_string()).collect(),
+        model_breakdowns: Vec::new(),
+    }
+}
+
+/// Menu state for `scenario`, rendered with the user's real settings
+pub(crate) fn preview_state(
+    scenario: PreviewScenario,
+    now: chrono::DateTime<chrono::Local>,
+    settings: AppSettings,
+) -> MenuState {
+    let mut state = MenuState {
+        active_block: None,
+        today_blocks: Vec::new(),
+        recent_days: Vec::new(),
+        window_days: Vec::new(),
+        recent_days_stale: false,
+        previous_block: None,
+        last_activity: None,
+        has_attempted_fetch: true,
+        refresh: RefreshPresentation::Idle,
+        next_refresh: None,
+        availability: Availability::Available,
+        claude_running: None,
+        settings,
+        now,
+        lifetime: None,
+        conversations: Vec::new(),
+        since_last_looked: None,
+        cost_samples: Vec::new(),
+        models_added: Vec::new(),
+        avg_refresh: None,
+        last_trigger: Some(RefreshTrigger::Manual),
+        on_battery: false,
+        model_exclusion: ModelExclusion::NONE,
+        write_denied_dir: None,
+        settings_backup: None,
+        settings_read_only: None,
+        denied_capabilities: Vec::new(),
+        preview: Some(scenario),
+    };
+
+    match scenario {
+        PreviewScenario::ActiveBlock => {
+            let earlier = sample_block(now, 420, 8.25, &["claude-sonnet-4-20250514"], false);
+            let active = sample_block(
+                now,
+                95,
+                17.59,
+                &["claude-opus-4-20250514", "claude-sonnet-4-20250514"],
+                true,
+            );
+            // A quiet start, a burst, then tapering off
+            state.cost_samples = [(90, 0.40), (80, 1.10), (65, 1.60), (50, 2.05), (35, 9.80), (25, 14.20), (5, 17.59)]
+                .into_iter()
+                .map(|(minutes_ago, cost)| (now - chrono::Duration::minutes(minutes_ago), cost))
+                .collect();
+            state.today_blocks = vec![earlier, active.clone()];
+            state.active_block = Some(active);
+            state.lifetime = Some(LifetimeSummary {
+                cost: 1842.0,
+                tokens: 412_000_000,
+                first_date: chrono::NaiveDate::from_ymd_opt(2025, 3, 1),
+            });
+            state.avg_refresh = Some(std::time::Duration::from_millis(3_400));
+            state.since_last_looked = Some(1.20);
+        }
+        PreviewScenario::NoSession => {
+            state.today_blocks = vec![sample_block(now, 420, 8.25, &["claude-sonnet-4-20250514"], false)];
+        }
+        PreviewScenario::Idle => {
+            let yesterday = now - chrono::Duration::days(1);
+            state.recent_days = vec![DailyUsage {
+                date: yesterday.format("%Y-%m-%d").to_string(),
+                total_tokens: 3_400_000,
+                total_cost: 31.75,
+            }];
+            state.last_activity = Some(yesterday - chrono::Duration::hours(2));
+        }
+        PreviewScenario::EmptyData => {}
+        PreviewScenario::Unavailable => {
+            state.availability = Availability::NotFound;
+        }
+        PreviewScenario::PartialData => {
+            let mut active = sample_block(now, 10, 0.0, &[], true);
+            active.token_counts = TokenCounts {
+                input_tokens: 0,
+                output_tokens: 0,
+                cache_creation_input_tokens: 0,
+                cache_read_input_tokens: 0,
+            };
+            active.end_time = String::new();
+            state.active_block = Some(active);
+        }
+        PreviewScenario::Stale => {
+            // A block that should have ended an hour ago but is still cached as active
+            let active = sample_block(now, 360, 23.10, &["claude-opus-4-20250514"], true);
+            state.today_blocks = vec![active.clone()];
+            state.active_block = Some(active);
+            state.avg_refresh = Some(std::time::Duration::from_secs(14));
+        }
+    }
+    // Canned days have no blocks to place within the counting window
+    state.window_days = state.recent_days.clone();
+
+    state
+}
@@ -0,0 +1,172 @@
+use std::path::Path;
+
+use crate::state::current_settings;
+
+/// Path segments under the home directory that say nothing about the user's
+/// projects and are left readable when project names are redacted
+const KNOWN_SEGMENTS: &[&str] = &[
+    "Library",
+    "Application Support",
+    "Caches",
+    "Preferences",
+    "Documents",
+    "Desktop",
+    "Downloads",
+    "bin",
+    "versions",
+    "node",
+    "ccusage-menubar",
+];
+
+/// Characters that end a path inside free text. Spaces don't, since macOS
+/// paths often contain them.
+fn ends_path(c: char) -> bool {
+    matches!(c, ':' | '\n' | ',' | '(' | ')' | '"' | '\'' | ';')
+}
+
+/// Characters a path can start right after inside free text
+fn can_precede_path(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '/' | '=') || ends_path(c)
+}
+
+/// Short stable name for a path segment, the same on every run so redacted
+/// reports can still be compared
+fn hash_segment(segment: &str) -> String {
+    // 32-bit FNV-1a
+    let hash = segment.bytes().fold(0x811c9dc5u32, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x01000193));
+    format!("p-{:08x}", hash)
+}
+
+/// Replace every occurrence of `home` that forms a whole path with `~`
+fn replace_home(text: &str, home: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(found) = rest.find(home) {
+        let after = &rest[found + home.len()..];
+        result.push_str(&rest[..found]);
+        // "/Users/ann" inside "/Users/anna" is someone else's home, and inside
+        // "~/backup/Users/ann" just a folder name
+        let starts_path = result.is_empty() || result.ends_with(can_precede_path);
+        let ends_here =
+            after.is_empty() || after.starts_with('/') || after.starts_with(ends_path) || after.starts_with(' ');
+        if starts_path && ends_here {
+            result.push('~');
+        } else {
+            result.push_str(home);
+        }
+        rest = after;
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Hash the project-like segments of each `~/...` path in `text`
+fn hash_project_segments(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(found) = rest.find("~/") {
+        result.push_str(&rest[..found + 2]);
+        let path = &rest[found + 2..];
+        let end = path.find(ends_path).unwrap_or(path.len());
+        let segments: Vec<String> = path[..end]
+            .split('/')
+            .map(|segment| {
+                let name = segment.trim_end();
+                if name.is_empty() || name.starts_with('.') || KNOWN_SEGMENTS.contains(&name) {
+                    segment.to_string()
+                } else {
+                    format!("{}{}", hash_segment(name), &segment[name.len()..])
+                }
+            })
+            .collect();
+        result.push_str(&segments.join("/"));
+        rest = &path[end..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Remove personal details from `text`: the home directory becomes `~` and,
+/// if `hash_projects` is set, folder names below it are replaced by hashes
+pub(crate) fn redact(text: &str, home: Option<&Path>, hash_projects: bool) -> String {
+    let home = home.map(|home| home.to_string_lossy().trim_end_matches('/').to_string());
+    let text = match home.as_deref() {
+        Some(home) if !home.is_empty() => replace_home(text, home),
+        _ => text.to_string(),
+    };
+    if hash_projects {
+        hash_project_segments(&text)
+    } else {
+        text
+    }
+}
+
+/// Redact text that is about to leave the app, e.g. for a bug report. Every
+/// such egress point goes through here; on-screen menu text does not.
+pub(crate) fn redact_for_egress(text: &str) -> String {
+    redact(text, dirs::home_dir().as_deref(), current_settings().redact_project_names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn home(path: &str) -> Option<&Path> {
+        Some(Path::new(path))
+    }
+
+    #[test]
+    fn home_becomes_a_tilde() {
+        let text = "Data dir: /Users/ann/.claude/projects\nLog: /Users/ann";
+        assert_eq!(redact(text, home("/Users/ann/"), false), "Data dir: ~/.claude/projects\nLog: ~");
+    }
+
+    #[test]
+    fn homes_with_spaces_are_redacted() {
+        let text = "cwd=/Users/Ann Lee/code/my app (main)";
+        assert_eq!(redact(text, home("/Users/Ann Lee"), false), "cwd=~/code/my app (main)");
+    }
+
+    #[test]
+    fn unicode_homes_and_folders_are_redacted() {
+        let text = "/Users/zoë/Projekte/Käsebrot: failed";
+        assert_eq!(redact(text, home("/Users/zoë"), false), "~/Projekte/Käsebrot: failed");
+        let hashed = format!("~/{}/{}: failed", hash_segment("Projekte"), hash_segment("Käsebrot"));
+        assert_eq!(redact(text, home("/Users/zoë"), true), hashed);
+    }
+
+    #[test]
+    fn only_whole_home_paths_are_replaced() {
+        let home = home("/Users/ann");
+        assert_eq!(redact("/Users/anna/x", home, false), "/Users/anna/x");
+        assert_eq!(redact("/Users/ann/backup/Users/ann/x", home, false), "~/backup/Users/ann/x");
+        assert_eq!(redact("/Volumes/old/Users/ann/x", home, false), "/Volumes/old/Users/ann/x");
+        assert_eq!(redact("file:///Users/ann/report.md", home, false), "file://~/report.md");
+    }
+
+    #[test]
+    fn project_folders_are_hashed_but_known_ones_kept() {
+        let text = "~/Library/Application Support/secret-client/.cache/notes.txt";
+        let expected = format!(
+            "~/Library/Application Support/{}/.cache/{}",
+            hash_segment("secret-client"),
+            hash_segment("notes.txt")
+        );
+        assert_eq!(redact(text, None, true), expected);
+    }
+
+    #[test]
+    fn hashes_are_stable_and_distinct() {
+        assert_eq!(hash_segment("api"), hash_segment("api"));
+        assert_ne!(hash_segment("api"), hash_segment("web"));
+        assert_eq!(hash_segment(""), "p-811c9dc5");
+    }
+
+    #[test]
+    fn text_without_home_is_unchanged() {
+        assert_eq!(redact("ccusage 15.2.0", home("/Users/ann"), false), "ccusage 15.2.0");
+        assert_eq!(redact("/Users/ann/x", None, false), "/Users/ann/x");
+        assert_eq!(redact("/Users/ann/x", home("/"), false), "/Users/ann/x");
+    }
+}
+
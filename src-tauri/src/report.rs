@@ -0,0 +1,284 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use tauri::AppHandle;
+
+use crate::capability::notify;
+use crate::datadirs::expand_home;
+use crate::fetch::{CommandRunner, RUNNER};
+use crate::format::{format_cost, format_model_name, format_tokens};
+use crate::model::BlockData;
+use crate::state::{cache_dir, current_settings, record_write_result, AppSettings, CacheFile};
+use crate::stats::{block_window_fraction, CountingWindow};
+
+/// Markdown file the report is appended to when `daily_report_path` is unset
+const DEFAULT_REPORT_PATH: &str = "~/ccusage-daily.md";
+
+/// Where the daily report goes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ReportTarget {
+    #[default]
+    Notification,
+    Markdown,
+    Webhook,
+}
+
+/// End-of-day summary of one day's blocks
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct DailyReport {
+    pub(crate) date: chrono::NaiveDate,
+    /// Only what falls inside the counting window when one is used
+    pub(crate) cost: f64,
+    /// The whole day's cost, when `cost` is restricted to a counting window
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) all_hours_cost: Option<f64>,
+    pub(crate) tokens: u64,
+    pub(crate) top_model: Option<String>,
+    pub(crate) sessions: usize,
+}
+
+impl DailyReport {
+    fn top_model_text(&self) -> String {
+        self.top_model.as_deref().map(format_model_name).unwrap_or_else(|| "none".to_string())
+    }
+
+    /// " (all hours: $X)" when the cost is restricted to a counting window
+    fn all_hours_text(&self) -> String {
+        self.all_hours_cost.map(|cost| format!(" (all hours: {})", format_cost(cost))).unwrap_or_default()
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "{}{} across {} sessions, {} tokens. Top model: {}",
+            format_cost(self.cost),
+            self.all_hours_text(),
+            self.sessions,
+            format_tokens(self.tokens),
+            self.top_model_text()
+        )
+    }
+
+    fn markdown(&self) -> String {
+        format!(
+            "## {}\n\n- Cost: {}{}\n- Tokens: {}\n- Top model: {}\n- Sessions: {}\n\n",
+            self.date.format("%Y-%m-%d"),
+            format_cost(self.cost),
+            self.all_hours_text(),
+            format_tokens(self.tokens),
+            self.top_model_text(),
+            self.sessions
+        )
+    }
+}
+
+/// Summarise `blocks` for `date`. The top model is the one that cost the
+/// most, or without ccusage's breakdown the one used in the most sessions.
+/// With a counting `window`, the cost only counts each block's share inside it.
+pub(crate) fn compose_daily_report(
+    blocks: &[BlockData],
+    date: chrono::NaiveDate,
+    window: Option<&CountingWindow>,
+) -> DailyReport {
+    let sessions: Vec<&BlockData> = blocks.iter().filter(|block| !block.is_gap).collect();
+    let mut model_costs: HashMap<&str, f64> = HashMap::new();
+    let mut model_uses: HashMap<&str, usize> = HashMap::new();
+    for block in &sessions {
+        for breakdown in &block.model_breakdowns {
+            *model_costs.entry(&breakdown.model_name).or_default() += breakdown.cost;
+        }
+        for model in &block.models {
+            *model_uses.entry(model).or_default() += 1;
+        }
+    }
+    // Ties go to the alphabetically first model so the report doesn't depend on hash order
+    let by_cost = model_costs.into_iter().max_by(|a, b| a.1.total_cmp(&b.1).then_with(|| b.0.cmp(a.0)));
+    let by_uses = || model_uses.into_iter().max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)));
+    let top_model = by_cost.map(|(model, _)| model).or_else(|| by_uses().map(|(model, _)| model)).map(str::to_string);
+    let all_hours_cost = sessions.iter().map(|block| block.cost_usd).sum();
+    let cost = window.map_or(all_hours_cost, |window| {
+        sessions.iter().map(|block| block.cost_usd * block_window_fraction(block, window)).sum()
+    });
+    DailyReport {
+        date,
+        cost,
+        all_hours_cost: window.map(|_| all_hours_cost),
+        tokens: sessions.iter().map(|block| block.token_counts.total()).sum(),
+        top_model,
+        sessions: sessions.len(),
+    }
+}
+
+/// Whether the report for `now`'s day is due: past `at` and not yet sent today
+pub(crate) fn report_due(
+    at: chrono::NaiveTime,
+    last_sent: Option<chrono::NaiveDate>,
+    now: chrono::DateTime<chrono::Local>,
+) -> bool {
+    now.time() >= at && !last_sent.is_some_and(|date| date >= now.date_naive())
+}
+
+/// The day the report was last sent, persisted so a restart doesn't send it twice
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ReportLog {
+    last_sent: Option<chrono::NaiveDate>,
+}
+
+fn load_report_log() -> ReportLog {
+    CacheFile::Report
+        .path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_report_log(log: &ReportLog) {
+    let Some(dir) = cache_dir() else {
+        return;
+    };
+    let Ok(json) = serde_json::to_string(log) else {
+        return;
+    };
+    let result =
+        std::fs::create_dir_all(&dir).and_then(|()| std::fs::write(dir.join(CacheFile::Report.file_name()), json));
+    record_write_result(&dir, &result);
+    if let Err(e) = result {
+        eprintln!("Failed to save daily report log: {}", e);
+    }
+}
+
+fn append_markdown(settings: &AppSettings, report: &DailyReport) -> std::io::Result<()> {
+    let path = settings.daily_report_path.as_deref().filter(|path| !path.trim().is_empty());
+    let path = expand_home(path.unwrap_or(DEFAULT_REPORT_PATH));
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?
+        .write_all(report.markdown().as_bytes())
+}
+
+/// POST the report as JSON with curl, which ships with macOS
+async fn post_webhook(runner: &dyn CommandRunner, url: &str, report: &DailyReport) -> Result<(), String> {
+    let json = serde_json::to_string(report).map_err(|e| e.to_string())?;
+    let args: Vec<String> = ["-fsS", "-m", "15", "-X", "POST", "-H", "Content-Type: application/json", "--data-binary"]
+        .into_iter()
+        .map(str::to_string)
+        .chain([json, url.to_string()])
+        .collect();
+    let output = runner.output("curl", &args, &[]).await.map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Send today's report if `daily_report_time` has passed and it hasn't gone
+/// out yet today. It's marked sent even when delivery fails, so a broken
+/// webhook isn't retried on every refresh.
+pub(crate) async fn send_daily_report_if_due(
+    app: &AppHandle,
+    today_blocks: &[BlockData],
+    now: chrono::DateTime<chrono::Local>,
+) {
+    let settings = current_settings();
+    let Some(at) = settings
+        .daily_report_time
+        .as_deref()
+        .and_then(|time| chrono::NaiveTime::parse_from_str(time.trim(), "%H:%M").ok())
+    else {
+        return;
+    };
+    if !report_due(at, load_report_log().last_sent, now) {
+        return;
+    }
+    save_report_log(&ReportLog { last_sent: Some(now.date_naive()) });
+
+    let window = settings.count_window_only.then_some(&settings.counting_window);
+    let report = compose_daily_report(today_blocks, now.date_naive(), window);
+    let result = match settings.daily_report_target {
+        ReportTarget::Notification => {
+            if notify(app, "CCUsage daily report", report.summary()) {
+                Ok(())
+            } else {
+                Err("notifications are unavailable".to_string())
+            }
+        }
+        ReportTarget::Markdown => append_markdown(&settings, &report).map_err(|e| e.to_string()),
+        ReportTarget::Webhook => match settings.daily_report_webhook.as_deref().filter(|url| !url.trim().is_empty()) {
+            Some(url) => post_webhook(&RUNNER, url, &report).await,
+            None => Err("daily_report_webhook isn't set".to_string()),
+        },
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to send daily report: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, FakeClock};
+    use crate::model::fixtures::local;
+
+    fn nine_pm() -> chrono::NaiveTime {
+        chrono::NaiveTime::from_hms_opt(21, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn report_is_due_once_the_time_passes() {
+        let clock = FakeClock::new(local(2025, 6, 11, 20, 59));
+        assert!(!report_due(nine_pm(), None, clock.now()));
+        clock.advance(chrono::Duration::minutes(1));
+        assert!(report_due(nine_pm(), None, clock.now()));
+        assert!(!report_due(nine_pm(), Some(clock.now().date_naive()), clock.now()));
+    }
+
+    #[test]
+    fn a_forward_jump_past_the_time_sends_the_report() {
+        let clock = FakeClock::new(local(2025, 6, 11, 18, 0));
+        clock.set(local(2025, 6, 11, 23, 40));
+        assert!(report_due(nine_pm(), Some(local(2025, 6, 10, 21, 0).date_naive()), clock.now()));
+    }
+
+    #[test]
+    fn a_backward_jump_before_the_time_doesnt_send_it_again() {
+        let clock = FakeClock::new(local(2025, 6, 11, 21, 5));
+        let sent = clock.now().date_naive();
+        clock.set(local(2025, 6, 11, 20, 30));
+        assert!(!report_due(nine_pm(), Some(sent), clock.now()));
+        clock.set(local(2025, 6, 11, 21, 30));
+        assert!(!report_due(nine_pm(), Some(sent), clock.now()));
+    }
+
+    #[test]
+    fn a_backward_jump_across_midnight_doesnt_resend_yesterdays_report() {
+        let clock = FakeClock::new(local(2025, 6, 12, 21, 5));
+        let sent = clock.now().date_naive();
+        clock.set(local(2025, 6, 11, 22, 0));
+        assert!(!report_due(nine_pm(), Some(sent), clock.now()));
+    }
+
+    #[test]
+    fn a_forward_jump_across_midnight_waits_for_the_new_days_time() {
+        let clock = FakeClock::new(local(2025, 6, 11, 21, 5));
+        let sent = clock.now().date_naive();
+        clock.set(local(2025, 6, 12, 8, 0));
+        assert!(!report_due(nine_pm(), Some(sent), clock.now()));
+        clock.advance(chrono::Duration::hours(13));
+        assert!(report_due(nine_pm(), Some(sent), clock.now()));
+    }
+
+    #[test]
+    fn skipping_whole_days_sends_one_report() {
+        let clock = FakeClock::new(local(2025, 6, 11, 21, 5));
+        let sent = clock.now().date_naive();
+        clock.set(local(2025, 6, 14, 22, 0));
+        assert!(report_due(nine_pm(), Some(sent), clock.now()));
+        assert!(!report_due(nine_pm(), Some(clock.now().date_naive()), clock.now()));
+    }
+}
@@ -0,0 +1,212 @@
+use std::time::{Duration, Instant};
+
+use crate::state::{AppSettings, RefreshTrigger};
+
+/// Delay after a block's predicted end before refreshing, giving ccusage
+/// time to see it as finished
+const BLOCK_END_GRACE_SECS: i64 = 15;
+
+/// How long to wait before the next automatic refresh. When aligning to blocks,
+/// mid-block polling relaxes to the ceiling but wakes up just after the active
+/// block is due to end; the result is always kept within the floor/ceiling.
+pub(crate) fn next_refresh_delay(
+    block_end: Option<chrono::DateTime<chrono::Local>>,
+    now: chrono::DateTime<chrono::Local>,
+    settings: &AppSettings,
+) -> Duration {
+    if !settings.align_refresh_to_blocks {
+        return Duration::from_secs(settings.refresh_interval_secs.max(1));
+    }
+
+    let floor = settings.min_refresh_secs.max(1);
+    let ceiling = settings.max_refresh_secs.max(floor);
+    let secs = match block_end {
+        Some(end) => {
+            let until_end = (end - now).num_seconds() + BLOCK_END_GRACE_SECS;
+            if until_end > 0 {
+                (until_end as u64).min(ceiling)
+            } else {
+                floor
+            }
+        }
+        None => settings.refresh_interval_secs,
+    };
+    Duration::from_secs(secs.clamp(floor, ceiling))
+}
+
+/// How often the power source is re-checked while auto-refresh is paused on battery
+pub(crate) const POWER_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Apply the battery policy to a refresh delay: `None` means auto-refresh is
+/// paused, otherwise the longer of the normal and battery intervals is used
+pub(crate) fn power_adjusted_delay(delay: Duration, on_battery: bool, settings: &AppSettings) -> Option<Duration> {
+    if !on_battery {
+        return Some(delay);
+    }
+    match settings.battery_refresh_secs {
+        0 => None,
+        secs => Some(delay.max(Duration::from_secs(secs))),
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) async fn is_on_battery() -> bool {
+    // "Now drawing from 'Battery Power'" vs "'AC Power'"
+    match tokio::process::Command::new("pmset").args(["-g", "batt"]).output().await {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).contains("'Battery Power'"),
+        Err(_) => false,
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) async fn is_on_battery() -> bool {
+    // On battery when there's a mains supply and none of them are online
+    let Ok(entries) = std::fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+    let mut has_mains = false;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let kind = std::fs::read_to_string(path.join("type")).unwrap_or_default();
+        if kind.trim() != "Mains" {
+            continue;
+        }
+        has_mains = true;
+        if std::fs::read_to_string(path.join("online")).unwrap_or_default().trim() == "1" {
+            return false;
+        }
+    }
+    has_mains
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+pub(crate) async fn is_on_battery() -> bool {
+    false
+}
+
+/// Triggers this soon after a fetch started share its result
+pub(crate) const COALESCE_WINDOW: Duration = Duration::from_secs(5);
+
+/// What to do with a refresh trigger
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TriggerDecision {
+    /// Fetch now
+    Start,
+    /// A fetch is running; one more follows it, however many triggers arrive meanwhile
+    Deferred,
+    /// A fetch started within `COALESCE_WINDOW`, so its data is fresh enough
+    Coalesced,
+}
+
+/// Collapses bursts of refresh triggers (manual, wake, periodic, ...) so they
+/// don't run several ccusage fetches back to back
+#[derive(Debug)]
+pub(crate) struct RefreshCoalescer {
+    in_flight: bool,
+    last_started: Option<Instant>,
+    /// Latest trigger that arrived during the running fetch
+    rerun: Option<RefreshTrigger>,
+}
+
+impl RefreshCoalescer {
+    pub(crate) const fn new() -> Self {
+        Self { in_flight: false, last_started: None, rerun: None }
+    }
+
+    pub(crate) fn request(&mut self, trigger: RefreshTrigger, now: Instant) -> TriggerDecision {
+        if self.in_flight {
+            self.rerun = Some(trigger);
+            return TriggerDecision::Deferred;
+        }
+        if self.last_started.is_some_and(|started| now.saturating_duration_since(started) < COALESCE_WINDOW) {
+            return TriggerDecision::Coalesced;
+        }
+        self.in_flight = true;
+        self.last_started = Some(now);
+        TriggerDecision::Start
+    }
+
+    /// The running fetch finished. Returns the trigger to fetch again for if
+    /// any arrived while it ran, in which case that fetch counts as started.
+    pub(crate) fn finished(&mut self, now: Instant) -> Option<RefreshTrigger> {
+        let rerun = self.rerun.take();
+        self.in_flight = rerun.is_some();
+        if rerun.is_some() {
+            self.last_started = Some(now);
+        }
+        rerun
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use RefreshTrigger::{Manual, MenuOpen, Periodic, Wake};
+
+    /// Feed `triggers`, given as milliseconds from the start, to a coalescer
+    /// whose fetches each take `fetch_ms`. Returns when each fetch started and
+    /// for which trigger.
+    fn simulate(triggers: &[(u64, RefreshTrigger)], fetch_ms: u64) -> Vec<(u64, RefreshTrigger)> {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut coalescer = RefreshCoalescer::new();
+        let mut fetches = Vec::new();
+        let mut running_until = None;
+        let mut pending = triggers.iter().copied().peekable();
+        loop {
+            match (running_until, pending.peek().copied()) {
+                // A fetch ending at the same moment as a trigger finishes first
+                (Some(end), next) if !next.is_some_and(|(ms, _)| ms < end) => {
+                    running_until = coalescer.finished(at(end)).map(|trigger| {
+                        fetches.push((end, trigger));
+                        end + fetch_ms
+                    });
+                }
+                (_, Some((ms, trigger))) => {
+                    pending.next();
+                    if coalescer.request(trigger, at(ms)) == TriggerDecision::Start {
+                        fetches.push((ms, trigger));
+                        running_until = Some(ms + fetch_ms);
+                    }
+                }
+                _ => break,
+            }
+        }
+        fetches
+    }
+
+    #[test]
+    fn a_burst_during_a_slow_fetch_runs_one_more() {
+        let triggers = [(0, Manual), (500, Wake), (1_000, Periodic), (2_000, MenuOpen)];
+        assert_eq!(simulate(&triggers, 8_000), [(0, Manual), (8_000, MenuOpen)]);
+    }
+
+    #[test]
+    fn triggers_soon_after_a_fetch_share_it() {
+        let triggers = [(0, Manual), (2_000, Periodic), (4_900, Wake), (5_000, MenuOpen)];
+        assert_eq!(simulate(&triggers, 1_000), [(0, Manual), (5_000, MenuOpen)]);
+    }
+
+    #[test]
+    fn a_follow_up_fetch_opens_a_new_window() {
+        let triggers = [(0, Manual), (500, Wake), (3_000, Periodic), (6_000, Manual)];
+        assert_eq!(simulate(&triggers, 1_000), [(0, Manual), (1_000, Wake), (6_000, Manual)]);
+    }
+
+    #[test]
+    fn spaced_out_triggers_each_fetch() {
+        let triggers = [(0, Periodic), (120_000, Periodic), (240_000, Manual)];
+        assert_eq!(simulate(&triggers, 3_000), triggers);
+    }
+
+    #[test]
+    fn deferred_triggers_keep_only_the_latest() {
+        let now = Instant::now();
+        let mut coalescer = RefreshCoalescer::new();
+        assert_eq!(coalescer.request(Periodic, now), TriggerDecision::Start);
+        assert_eq!(coalescer.request(Wake, now), TriggerDecision::Deferred);
+        assert_eq!(coalescer.request(Manual, now), TriggerDecision::Deferred);
+        assert_eq!(coalescer.finished(now), Some(Manual));
+        assert_eq!(coalescer.finished(now), None);
+    }
+}
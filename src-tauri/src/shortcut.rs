@@ -0,0 +1,28 @@
+use std::sync::Mutex;
+use tauri::AppHandle;
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, ShortcutState};
+
+/// Why the copy-cost shortcut couldn't be registered, e.g. another app holds
+/// the same keys, for Debug Info
+pub(crate) static SHORTCUT_ERROR: Mutex<Option<String>> = Mutex::new(None);
+
+/// Register `accelerator` (e.g. "CmdOrCtrl+Alt+C") system-wide to run
+/// `on_press`. A malformed or taken shortcut is logged and reported rather
+/// than stopping the app from starting. Returns whether it was registered.
+pub(crate) fn register_copy_cost_shortcut<F>(app: &AppHandle, accelerator: &str, on_press: F) -> bool
+where
+    F: Fn(&AppHandle) + Send + Sync + 'static,
+{
+    let registered = app.global_shortcut().on_shortcut(accelerator, move |app, _shortcut, event| {
+        if event.state() == ShortcutState::Pressed {
+            on_press(app);
+        }
+    });
+    let error = registered.err().map(|e| format!("{}: {}", accelerator, e));
+    if let Some(error) = &error {
+        eprintln!("Failed to register the copy-cost shortcut {}", error);
+    }
+    let ok = error.is_none();
+    *SHORTCUT_ERROR.lock().unwrap() = error;
+    ok
+}
@@ -0,0 +1,1184 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::budget::WeekStart;
+use crate::dock::ActivationPolicy;
+use crate::hooks::EventHooks;
+use crate::report::ReportTarget;
+use crate::model::{BlockData, DailyUsage};
+use crate::stats::{CountingWindow, ModelExclusion, RollingStats};
+use crate::status::RenderedTitle;
+
+#[derive(Debug, Clone)]
+pub(crate) struct SessionData {
+    pub(crate) active_block: Option<BlockData>,
+    pub(crate) today_blocks: Vec<BlockData>,
+    /// Daily totals since the start of the month or the last two weeks, whichever is longer
+    pub(crate) recent_days: Vec<DailyUsage>,
+    /// `recent_days` counting only usage inside the counting window
+    pub(crate) window_days: Vec<DailyUsage>,
+    /// When Claude was last used, from the blocks of the last successful refresh
+    pub(crate) last_activity: Option<chrono::DateTime<chrono::Local>>,
+    /// The block before the current one, if it ended within the last two hours
+    pub(crate) previous_block: Option<BlockData>,
+    /// When `recent_days` was last fetched successfully, behind `last_updated` after a failure
+    pub(crate) recent_days_updated: Option<Instant>,
+    /// When a refresh last found ccusage available
+    pub(crate) last_success: Option<Instant>,
+    pub(crate) last_updated: Option<Instant>,
+    /// What started the refresh that produced `last_updated`
+    pub(crate) last_trigger: Option<RefreshTrigger>,
+    pub(crate) availability: Availability,
+    /// Whether Claude Code was running when the last refresh found no session
+    pub(crate) claude_running: Option<bool>,
+    /// How `excluded_models` applied to the fetched blocks
+    pub(crate) model_exclusion: ModelExclusion,
+}
+
+/// What kicked off a refresh
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RefreshTrigger {
+    Startup,
+    Periodic,
+    Manual,
+    /// The first periodic refresh after the system woke from sleep
+    Wake,
+    /// Opening the menu, with `fetch_on_open_only` on
+    MenuOpen,
+}
+
+impl RefreshTrigger {
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            RefreshTrigger::Startup => "startup",
+            RefreshTrigger::Periodic => "periodic",
+            RefreshTrigger::Manual => "manual",
+            RefreshTrigger::Wake => "wake",
+            RefreshTrigger::MenuOpen => "menu open",
+        }
+    }
+}
+
+/// Whether ccusage could be run at all on the last refresh
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Availability {
+    Available,
+    NotFound,
+    /// Spawning processes is denied (App Sandbox, MDM policy)
+    Blocked,
+    /// ccusage ran but was denied access to Claude's data (TCC / Full Disk Access)
+    AccessDenied,
+    /// ccusage ran but found nothing, and there are no logs where it looked
+    NoData,
+}
+
+impl SessionData {
+    /// Nothing fetched yet
+    pub(crate) const fn new() -> Self {
+        Self {
+            active_block: None,
+            today_blocks: Vec::new(),
+            recent_days: Vec::new(),
+            window_days: Vec::new(),
+            last_activity: None,
+            previous_block: None,
+            recent_days_updated: None,
+            last_success: None,
+            last_updated: None,
+            last_trigger: None,
+            availability: Availability::NotFound,
+            claude_running: None,
+            model_exclusion: ModelExclusion::NONE,
+        }
+    }
+}
+
+pub(crate) static SESSION_CACHE: Mutex<SessionData> = Mutex::new(SessionData::new());
+
+pub(crate) static IS_REFRESHING: AtomicBool = AtomicBool::new(false);
+
+/// When the periodic loop will next refresh, `None` while it's paused on battery
+pub(crate) static NEXT_REFRESH: Mutex<Option<chrono::DateTime<chrono::Local>>> = Mutex::new(None);
+
+/// Whether the machine is currently running on battery, as last observed by
+/// the periodic task (only tracked when `pause_on_battery` is enabled)
+pub(crate) static ON_BATTERY: AtomicBool = AtomicBool::new(false);
+
+/// End-to-end refresh times and individual ccusage invocation times
+pub(crate) struct RefreshTimings {
+    pub(crate) refreshes: RollingStats,
+    pub(crate) fetch_attempts: RollingStats,
+}
+
+pub(crate) static REFRESH_TIMINGS: Mutex<RefreshTimings> = Mutex::new(RefreshTimings {
+    refreshes: RollingStats::new(),
+    fetch_attempts: RollingStats::new(),
+});
+
+/// npx downloading ccusage, which makes the first run after a release take
+/// much longer than usual
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct NpxDownloads {
+    /// When the download under way started
+    pub(crate) started: Option<Instant>,
+    /// How long the last finished download took
+    pub(crate) last_duration: Option<Duration>,
+}
+
+pub(crate) static NPX_DOWNLOADS: Mutex<NpxDownloads> = Mutex::new(NpxDownloads {
+    started: None,
+    last_duration: None,
+});
+
+/// Stdout of the last ccusage run that exited successfully, for Debug Info
+#[derive(Debug, Clone)]
+pub(crate) struct RawResponse {
+    /// The ccusage arguments, e.g. "blocks --json"
+    pub(crate) args: String,
+    pub(crate) stdout: String,
+    /// Whether `stdout` was cut off at `RAW_RESPONSE_LIMIT`
+    pub(crate) truncated: bool,
+}
+
+/// Most bytes of a response kept in `LAST_RAW_RESPONSE`
+pub(crate) const RAW_RESPONSE_LIMIT: usize = 256 * 1024;
+
+pub(crate) static LAST_RAW_RESPONSE: Mutex<Option<RawResponse>> = Mutex::new(None);
+
+impl RawResponse {
+    /// Keep the start of `stdout`, converting only that much of it to text
+    pub(crate) fn new(args: &str, stdout: &[u8]) -> Self {
+        let kept = &stdout[..stdout.len().min(RAW_RESPONSE_LIMIT)];
+        // Leave out a character the limit cut in half
+        let kept = match std::str::from_utf8(kept) {
+            Err(e) if e.error_len().is_none() => &kept[..e.valid_up_to()],
+            _ => kept,
+        };
+        Self {
+            args: args.to_string(),
+            stdout: String::from_utf8_lossy(kept).into_owned(),
+            truncated: kept.len() < stdout.len(),
+        }
+    }
+}
+
+/// Outcome of the most recent post-refresh command, for Debug Info
+pub(crate) static LAST_HOOK_RESULT: Mutex<Option<String>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct AppSettings {
+    /// Format of the file these were read from; see `SETTINGS_VERSION`
+    pub(crate) version: u32,
+    /// Switch the tray title to compact notation ("$1.2k") above the threshold
+    pub(crate) compact_title: bool,
+    pub(crate) compact_threshold: f64,
+    /// Count the in-progress block towards totals, as ccusage's own reports do
+    pub(crate) include_active_in_totals: bool,
+    /// Fixed auto-refresh interval, used when not aligning to blocks
+    pub(crate) refresh_interval_secs: u64,
+    /// Poll less often mid-block and refresh right after the active block ends
+    pub(crate) align_refresh_to_blocks: bool,
+    /// Bounds on the aligned refresh delay
+    pub(crate) min_refresh_secs: u64,
+    pub(crate) max_refresh_secs: u64,
+    /// Show what the session would have cost if cache reads were billed as regular input
+    pub(crate) show_cost_without_cache: bool,
+    /// When the cache token, hit ratio and savings lines appear
+    pub(crate) cache_line_visibility: CacheLineVisibility,
+    pub(crate) models_display: ModelsDisplay,
+    /// Characters for sparklines and the hourly histogram
+    pub(crate) glyph_set: GlyphSet,
+    /// Slow down or pause auto-refresh while running on battery
+    pub(crate) pause_on_battery: bool,
+    /// Auto-refresh interval on battery; 0 pauses auto-refresh until back on AC
+    pub(crate) battery_refresh_secs: u64,
+    /// Notify and mark the title when no refresh has succeeded for this many
+    /// minutes while auto-refresh should be running; 0 turns it off
+    pub(crate) stall_alert_mins: u64,
+    /// Local time ("HH:MM") to send a summary of the day; off when unset
+    pub(crate) daily_report_time: Option<String>,
+    pub(crate) daily_report_target: ReportTarget,
+    /// Markdown file the report is appended to; `~/ccusage-daily.md` when unset
+    pub(crate) daily_report_path: Option<String>,
+    /// URL the report is POSTed to as JSON
+    pub(crate) daily_report_webhook: Option<String>,
+    /// Set once the first-run welcome dialogs have been shown
+    pub(crate) onboarding_complete: bool,
+    /// Show the block before the current one while it ended less than two hours ago
+    pub(crate) show_previous_session: bool,
+    /// PNG to use as the tray icon instead of the bar chart
+    pub(crate) custom_icon_path: Option<String>,
+    /// Show the session's tokens as a second menubar item next to the cost
+    pub(crate) separate_tokens_tray: bool,
+    /// Least time between tray redraws caused by refreshes; faster data is
+    /// coalesced into one redraw. 0 redraws on every refresh.
+    pub(crate) tray_update_throttle_ms: u64,
+    /// Ask before handing a URL to the browser
+    pub(crate) confirm_links: bool,
+    /// Shell command run after each successful refresh with the session JSON on
+    /// stdin. Security-sensitive: runs with the app's privileges; off when unset.
+    pub(crate) post_refresh_command: Option<String>,
+    /// Show remaining block time as the icon draining like an hourglass, with no title text
+    pub(crate) hourglass_icon: bool,
+    /// Draw the tray title in bold digits for demos and screen sharing, even in hourglass mode
+    pub(crate) focus_mode: bool,
+    /// Tray title text; `{cost}` and `{model}` are replaced with the session's values
+    pub(crate) tray_title_format: String,
+    /// Which model `{model}` shows for a block that used several
+    pub(crate) tray_model_selection: TrayModelSelection,
+    /// Notify when a new model starts being used partway through a block
+    pub(crate) model_change_alerts: bool,
+    /// Models left out of costs and token counts, matched anywhere in the name, e.g. "haiku"
+    pub(crate) excluded_models: Vec<String>,
+    /// What the tray title shows: the formatted cost, or output and cache-read tokens
+    pub(crate) tray_display: TrayDisplay,
+    /// Mark the token counts with arrow glyphs; off spells them out for ASCII-only menu bars
+    pub(crate) tray_title_glyphs: bool,
+    /// Longest token title, in characters, before the cache-read count is dropped
+    pub(crate) tray_title_max_width: usize,
+    /// Check whether Claude Code is running to explain a missing session
+    pub(crate) detect_claude_running: bool,
+    /// Claude data directories for ccusage to read, exported as `CLAUDE_CONFIG_DIR`;
+    /// ccusage's own default when empty
+    pub(crate) claude_config_dirs: Vec<String>,
+    /// Proxies and npm registry for `npx ccusage@latest`, exported as
+    /// `HTTP_PROXY`, `HTTPS_PROXY` and `NPM_CONFIG_REGISTRY` when set
+    pub(crate) http_proxy: Option<String>,
+    pub(crate) https_proxy: Option<String>,
+    pub(crate) npm_registry: Option<String>,
+    /// Notify once per block when spending speeds up well beyond the usual rate
+    pub(crate) spend_spike_alerts: bool,
+    /// Cost a single block shouldn't go over; no limit when unset
+    pub(crate) block_budget: Option<f64>,
+    /// Spend limits per calendar day, week and month; no limit when unset
+    pub(crate) daily_budget: Option<f64>,
+    pub(crate) weekly_budget: Option<f64>,
+    pub(crate) monthly_budget: Option<f64>,
+    /// Hard ceiling on a day's spend: passing it marks the tray and quiets the
+    /// per-session alerts for the rest of the day; off when unset
+    pub(crate) daily_cost_cap: Option<f64>,
+    /// Day weekly budgets restart on
+    pub(crate) week_start: WeekStart,
+    /// Count today's total, budgets and the daily report only within
+    /// `counting_window`, e.g. when only work hours are reimbursed
+    pub(crate) count_window_only: bool,
+    pub(crate) counting_window: CountingWindow,
+    /// Monthly price of a Claude subscription, to compare this month's
+    /// pay-as-you-go cost against; hidden when unset
+    pub(crate) plan_monthly_cost: Option<f64>,
+    /// Output tokens a single block shouldn't go over, for spotting runaway
+    /// agent sessions that are cheap but long; no limit when unset
+    pub(crate) output_token_cap: Option<u64>,
+    /// Tokens (all kinds, as ccusage's --token-limit counts them) a plan allows
+    /// per block, for estimating when it runs out; off when unset
+    pub(crate) block_token_limit: Option<u64>,
+    /// Flash "OVER BUDGET" in the tray title the first time a period passes its budget
+    pub(crate) flash_on_budget_breach: bool,
+    /// How many times the title flashes
+    pub(crate) budget_flash_count: u32,
+    /// Shell commands run when the budget or spend spike alerts fire
+    pub(crate) event_hooks: EventHooks,
+    /// Hash folder names in paths in Debug Info, on top of hiding the home directory
+    pub(crate) redact_project_names: bool,
+    /// Copy the session cost to the clipboard after a refresh that changed it
+    pub(crate) auto_copy_cost: bool,
+    /// System-wide shortcut that copies the session cost, e.g. "CmdOrCtrl+Alt+C"; off when unset
+    pub(crate) copy_cost_shortcut: Option<String>,
+    /// Write a JSON summary for other tools after every refresh
+    pub(crate) write_status_file: bool,
+    /// Where the status file goes; `~/.cache/ccusage-menubar/status.json` when unset
+    pub(crate) status_file_path: Option<String>,
+    /// Show the "Preview states" submenu for checking how the menu renders
+    pub(crate) show_preview_states: bool,
+    /// Skip background refreshes and fetch when the menu opens instead
+    pub(crate) fetch_on_open_only: bool,
+    /// Menubar only, or also a dock icon and window
+    pub(crate) activation_policy: ActivationPolicy,
+    /// Menu language as a two-letter code such as "de"; the system language when unset
+    pub(crate) language: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TrayModelSelection {
+    /// The first model seen in the block
+    #[default]
+    First,
+    /// The model with the most tokens
+    MostUsed,
+    /// The model with the highest cost
+    MostExpensive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub(crate) enum TrayDisplay {
+    /// `tray_title_format` filled with the session's cost and model
+    #[default]
+    #[serde(rename = "cost")]
+    Cost,
+    /// Output tokens followed by cache-read tokens, "45.2K↑ 1.2M⟳"
+    #[serde(rename = "tokens+cache")]
+    TokensCache,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum CacheLineVisibility {
+    Always,
+    /// Only for sessions that used the cache
+    #[default]
+    WhenNonZero,
+    Never,
+}
+
+impl CacheLineVisibility {
+    pub(crate) fn shows(self, nonzero: bool) -> bool {
+        match self {
+            CacheLineVisibility::Always => true,
+            CacheLineVisibility::WhenNonZero => nonzero,
+            CacheLineVisibility::Never => false,
+        }
+    }
+}
+
+/// How the session's "Models used" section is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ModelsDisplay {
+    /// A header and one row per model
+    #[default]
+    Full,
+    /// A single "Models: Opus 4, Sonnet 4" row
+    Compact,
+    Hidden,
+}
+
+/// Characters used for sparklines and histogram bars. Some fonts draw block
+/// characters at odd widths; braille dots or plain ASCII may line up better.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum GlyphSet {
+    #[default]
+    Blocks,
+    Dots,
+    Ascii,
+}
+
+impl GlyphSet {
+    /// Sparkline levels, lowest first
+    pub(crate) fn levels(self) -> &'static [char] {
+        match self {
+            GlyphSet::Blocks => &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'],
+            GlyphSet::Dots => &['⣀', '⣤', '⣶', '⣿'],
+            GlyphSet::Ascii => &['_', '-', '=', '#'],
+        }
+    }
+
+    /// Filled and empty cells of a bar, the same width as each other in the set
+    pub(crate) fn bar_cells(self) -> (char, char) {
+        match self {
+            GlyphSet::Blocks => ('▓', '░'),
+            GlyphSet::Dots => ('⣿', '⣀'),
+            GlyphSet::Ascii => ('#', '-'),
+        }
+    }
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            compact_title: true,
+            compact_threshold: 1000.0,
+            include_active_in_totals: true,
+            refresh_interval_secs: 120,
+            align_refresh_to_blocks: true,
+            min_refresh_secs: 60,
+            max_refresh_secs: 300,
+            show_cost_without_cache: false,
+            cache_line_visibility: CacheLineVisibility::WhenNonZero,
+            models_display: ModelsDisplay::Full,
+            activation_policy: ActivationPolicy::Accessory,
+            fetch_on_open_only: false,
+            language: None,
+            glyph_set: GlyphSet::Blocks,
+            pause_on_battery: false,
+            battery_refresh_secs: 0,
+            stall_alert_mins: 60,
+            daily_report_time: None,
+            daily_report_target: ReportTarget::Notification,
+            daily_report_path: None,
+            daily_report_webhook: None,
+            onboarding_complete: false,
+            show_previous_session: true,
+            custom_icon_path: None,
+            separate_tokens_tray: false,
+            tray_update_throttle_ms: 2000,
+            confirm_links: false,
+            post_refresh_command: None,
+            hourglass_icon: false,
+            focus_mode: false,
+            tray_title_format: "{cost}".to_string(),
+            tray_model_selection: TrayModelSelection::First,
+            excluded_models: Vec::new(),
+            model_change_alerts: false,
+            tray_display: TrayDisplay::Cost,
+            tray_title_glyphs: true,
+            tray_title_max_width: 20,
+            detect_claude_running: true,
+            claude_config_dirs: Vec::new(),
+            http_proxy: None,
+            https_proxy: None,
+            npm_registry: None,
+            spend_spike_alerts: true,
+            block_budget: None,
+            daily_budget: None,
+            weekly_budget: None,
+            monthly_budget: None,
+            daily_cost_cap: None,
+            plan_monthly_cost: None,
+            week_start: WeekStart::Monday,
+            count_window_only: false,
+            counting_window: CountingWindow::default(),
+            output_token_cap: None,
+            block_token_limit: None,
+            flash_on_budget_breach: false,
+            budget_flash_count: 3,
+            event_hooks: EventHooks::default(),
+            redact_project_names: false,
+            auto_copy_cost: false,
+            copy_cost_shortcut: None,
+            write_status_file: false,
+            status_file_path: None,
+            show_preview_states: false,
+        }
+    }
+}
+
+static SETTINGS: LazyLock<Mutex<AppSettings>> = LazyLock::new(|| Mutex::new(load_settings()));
+
+/// Overrides where settings and cached data are stored, for machines where
+/// the default locations aren't writable
+pub(crate) const CONFIG_DIR_ENV: &str = "CCUSAGE_MENUBAR_CONFIG_DIR";
+
+fn config_dir_override() -> Option<PathBuf> {
+    // Tests never read or write the real settings and caches
+    if cfg!(test) {
+        return Some(std::env::temp_dir().join(format!("ccusage-menubar-test-{}", std::process::id())));
+    }
+    std::env::var_os(CONFIG_DIR_ENV)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+}
+
+/// A fresh directory for one test, removed with everything in it when dropped
+#[cfg(test)]
+pub(crate) struct TestDir(PathBuf);
+
+#[cfg(test)]
+impl TestDir {
+    pub(crate) fn new(name: &str) -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("ccusage-menubar-test-{}-{}-{}", std::process::id(), name, n));
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+pub(crate) fn settings_path() -> Option<PathBuf> {
+    config_dir_override()
+        .or_else(|| dirs::config_dir().map(|dir| dir.join("ccusage-menubar")))
+        .map(|dir| dir.join("settings.json"))
+}
+
+/// Directory for derived data that can be regenerated, like the lifetime totals
+pub(crate) fn cache_dir() -> Option<PathBuf> {
+    config_dir_override().or_else(|| dirs::cache_dir().map(|dir| dir.join("ccusage-menubar")))
+}
+
+/// Every file the app keeps in `cache_dir()`. Features name their file here
+/// rather than spelling it out, so removing the app's data can't miss one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CacheFile {
+    /// The active session as of the last refresh, for `--print --cached`
+    Session,
+    Lifetime,
+    /// Cost samples behind the sparkline
+    Timeline,
+    /// When the daily report was last sent
+    Report,
+    /// The last day the daily cost cap was passed
+    DailyCap,
+    /// The last raw ccusage response, saved by Debug Info
+    LastResponse,
+}
+
+impl CacheFile {
+    pub(crate) const ALL: [CacheFile; 6] = [
+        CacheFile::Session,
+        CacheFile::Lifetime,
+        CacheFile::Timeline,
+        CacheFile::Report,
+        CacheFile::DailyCap,
+        CacheFile::LastResponse,
+    ];
+
+    pub(crate) fn file_name(self) -> &'static str {
+        match self {
+            CacheFile::Session => "session.json",
+            CacheFile::Lifetime => "lifetime.json",
+            CacheFile::Timeline => "timeline.json",
+            CacheFile::Report => "report.json",
+            CacheFile::DailyCap => "daily-cap.json",
+            CacheFile::LastResponse => "last-response.json",
+        }
+    }
+
+    pub(crate) fn path(self) -> Option<PathBuf> {
+        cache_dir().map(|dir| dir.join(self.file_name()))
+    }
+}
+
+/// Directory the app was last refused permission to write to, if any
+pub(crate) static WRITE_DENIED_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Track permission failures when writing into `dir`, clearing them once a
+/// write there succeeds again
+pub(crate) fn record_write_result(dir: &Path, result: &io::Result<()>) {
+    let mut denied = WRITE_DENIED_DIR.lock().unwrap();
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => *denied = Some(dir.to_path_buf()),
+        Ok(()) if denied.as_deref() == Some(dir) => *denied = None,
+        _ => {}
+    }
+}
+
+/// Bumped whenever `SessionSnapshot` changes shape. Version 1 files hold
+/// just the block, or `null`, and are still read.
+const SESSION_SNAPSHOT_VERSION: u32 = 2;
+
+/// The active session as of the last successful refresh, with the tray title
+/// it was shown as
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SessionSnapshot {
+    pub(crate) version: u32,
+    pub(crate) session: Option<BlockData>,
+    #[serde(flatten)]
+    pub(crate) title: RenderedTitle,
+}
+
+/// Save the active session for `--print --cached`
+pub(crate) fn save_session_snapshot(block: &Option<BlockData>, title: RenderedTitle) {
+    let Some(dir) = cache_dir() else {
+        return;
+    };
+    let snapshot = SessionSnapshot { version: SESSION_SNAPSHOT_VERSION, session: block.clone(), title };
+    let Ok(json) = serde_json::to_string(&snapshot) else {
+        return;
+    };
+    let result =
+        std::fs::create_dir_all(&dir).and_then(|()| std::fs::write(dir.join(CacheFile::Session.file_name()), json));
+    record_write_result(&dir, &result);
+    if let Err(e) = result {
+        eprintln!("Failed to save session snapshot: {}", e);
+    }
+}
+
+/// `None` when no snapshot has been saved yet. A version 1 file comes back
+/// with an empty title.
+pub(crate) fn load_session_snapshot() -> Option<SessionSnapshot> {
+    let contents = std::fs::read_to_string(CacheFile::Session.path()?).ok()?;
+    parse_session_snapshot(&contents)
+}
+
+fn parse_session_snapshot(contents: &str) -> Option<SessionSnapshot> {
+    if let Ok(snapshot) = serde_json::from_str::<SessionSnapshot>(contents) {
+        return Some(snapshot);
+    }
+    let session = serde_json::from_str::<Option<BlockData>>(contents).ok()?;
+    Some(SessionSnapshot { version: 1, session, title: RenderedTitle::default() })
+}
+
+/// Numbers temporary files so concurrent writers never share one
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Replace the file at `path` in one step, so a crash mid-write can't leave
+/// it truncated and readers never see a half-written document
+pub(crate) fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let n = TMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let tmp = path.with_extension(format!("json.{}-{}.tmp", std::process::id(), n));
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&tmp);
+    })
+}
+
+/// Where the last corrupt settings file was moved to, if one was found
+pub(crate) static SETTINGS_BACKUP: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Start of the name a corrupt settings file is moved aside to
+const CORRUPT_SETTINGS_PREFIX: &str = "settings.corrupt-";
+
+/// Start of the name a settings file is copied to before it's migrated
+const MIGRATED_SETTINGS_PREFIX: &str = "settings.v";
+
+/// Current format of the settings file. Files without a `version` are v1.
+/// Bump this and add a step to `SETTINGS_MIGRATIONS` whenever a setting is
+/// renamed or its meaning changes.
+pub(crate) const SETTINGS_VERSION: u32 = 2;
+
+/// Step `i` turns a version `i + 1` settings file into version `i + 2`
+const SETTINGS_MIGRATIONS: [fn(&mut serde_json::Map<String, serde_json::Value>); SETTINGS_VERSION as usize - 1] =
+    [migrate_settings_v1_to_v2];
+
+/// Optional text settings, every one of which is unset when blank
+const OPTIONAL_TEXT_SETTINGS: [&str; 11] = [
+    "daily_report_time",
+    "daily_report_path",
+    "daily_report_webhook",
+    "custom_icon_path",
+    "post_refresh_command",
+    "http_proxy",
+    "https_proxy",
+    "npm_registry",
+    "copy_cost_shortcut",
+    "status_file_path",
+    "language",
+];
+
+/// v1 files edited by hand often cleared an optional text setting to `""`,
+/// which its readers took as unset. v2 stores unset as `null`, so later steps
+/// only have one spelling to deal with.
+fn migrate_settings_v1_to_v2(settings: &mut serde_json::Map<String, serde_json::Value>) {
+    for key in OPTIONAL_TEXT_SETTINGS {
+        if settings.get(key).and_then(serde_json::Value::as_str).is_some_and(|text| text.trim().is_empty()) {
+            settings.insert(key.to_string(), serde_json::Value::Null);
+        }
+    }
+}
+
+/// `fields` laid over the defaults one at a time, leaving out any this build
+/// can't read so that one bad field doesn't reset the others. Fields it
+/// doesn't know are ignored.
+fn merge_onto_defaults(fields: serde_json::Map<String, serde_json::Value>) -> AppSettings {
+    let Ok(serde_json::Value::Object(mut merged)) = serde_json::to_value(AppSettings::default()) else {
+        return AppSettings::default();
+    };
+    for (key, value) in fields {
+        let previous = merged.insert(key.clone(), value);
+        if serde_json::from_value::<AppSettings>(serde_json::Value::Object(merged.clone())).is_err() {
+            eprintln!("Ignoring setting {} this version can't read", key);
+            match previous {
+                Some(previous) => merged.insert(key, previous),
+                None => merged.remove(&key),
+            };
+        }
+    }
+    serde_json::from_value(serde_json::Value::Object(merged)).unwrap_or_default()
+}
+
+/// Version of the settings file found at startup when it's newer than this
+/// build understands. The settings are used but never written back.
+pub(crate) static SETTINGS_READ_ONLY: Mutex<Option<u32>> = Mutex::new(None);
+
+/// Every corrupt or pre-migration settings file set aside so far in `dir`,
+/// the directory holding the settings file
+pub(crate) fn settings_backups(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            name.starts_with(CORRUPT_SETTINGS_PREFIX) || name.starts_with(MIGRATED_SETTINGS_PREFIX)
+        })
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// Move an unreadable settings file aside and write `replacement` in its place
+fn recover_corrupt_settings(path: &Path, replacement: &AppSettings) {
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let backup = path.with_file_name(format!("{}{}.json", CORRUPT_SETTINGS_PREFIX, stamp));
+    match std::fs::rename(path, &backup) {
+        Ok(()) => {
+            eprintln!("Settings file was corrupt, moved it to {}", backup.display());
+            *SETTINGS_BACKUP.lock().unwrap() = Some(backup);
+        }
+        Err(e) => eprintln!("Settings file was corrupt and couldn't be backed up: {}", e),
+    }
+    write_settings_file(replacement);
+}
+
+enum SettingsFile {
+    Missing,
+    /// Couldn't be read at all, e.g. no permission; left alone
+    Unreadable(io::Error),
+    /// Read but not valid settings JSON
+    Corrupt(serde_json::Error),
+    /// Written by an older version and migrated to the current one
+    Outdated { settings: AppSettings, from: u32 },
+    /// Written by a newer version; whatever this build understands of it
+    Future { settings: AppSettings, version: u32 },
+    Valid(AppSettings),
+}
+
+fn read_settings_file(path: &Path) -> SettingsFile {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => match parse_settings(&contents) {
+            Ok(file) => file,
+            Err(e) => SettingsFile::Corrupt(e),
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => SettingsFile::Missing,
+        Err(e) => SettingsFile::Unreadable(e),
+    }
+}
+
+/// Parse settings JSON of any version, running the migrations needed to bring
+/// an older file up to `SETTINGS_VERSION`
+fn parse_settings(contents: &str) -> Result<SettingsFile, serde_json::Error> {
+    let mut value: serde_json::Value = serde_json::from_str(contents)?;
+    let version = match value.get("version") {
+        Some(version) => version.as_u64().map_or(0, |v| u32::try_from(v).unwrap_or(u32::MAX)),
+        None => 1,
+    };
+    if version > SETTINGS_VERSION {
+        let settings = match value {
+            serde_json::Value::Object(fields) => merge_onto_defaults(fields),
+            _ => AppSettings::default(),
+        };
+        return Ok(SettingsFile::Future { settings, version });
+    }
+    if version == SETTINGS_VERSION {
+        return serde_json::from_value(value).map(SettingsFile::Valid);
+    }
+    if let serde_json::Value::Object(fields) = &mut value {
+        for migrate in &SETTINGS_MIGRATIONS[version.saturating_sub(1) as usize..] {
+            migrate(fields);
+        }
+        fields.insert("version".to_string(), SETTINGS_VERSION.into());
+    }
+    let settings = serde_json::from_value(value)?;
+    Ok(SettingsFile::Outdated { settings, from: version })
+}
+
+/// Copy a settings file about to be migrated next to it, so the old version
+/// can still be restored
+fn back_up_before_migration(path: &Path, from: u32) -> io::Result<PathBuf> {
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let backup = path.with_file_name(format!("{}{}-{}.json", MIGRATED_SETTINGS_PREFIX, from, stamp));
+    std::fs::copy(path, &backup)?;
+    Ok(backup)
+}
+
+fn load_settings() -> AppSettings {
+    let Some(path) = settings_path() else {
+        return AppSettings::default();
+    };
+    match read_settings_file(&path) {
+        SettingsFile::Valid(settings) => settings,
+        SettingsFile::Outdated { settings, from } => {
+            match back_up_before_migration(&path, from) {
+                Ok(backup) => {
+                    eprintln!("Migrated settings from v{}, old file kept as {}", from, backup.display());
+                    write_settings_file(&settings);
+                }
+                // Not rewritten until a setting changes, so a restart retries the backup first
+                Err(e) => eprintln!("Migrated settings from v{} but couldn't back up the old file: {}", from, e),
+            }
+            settings
+        }
+        SettingsFile::Future { settings, version } => {
+            eprintln!("Settings file is v{}, newer than this app understands; not saving changes", version);
+            *SETTINGS_READ_ONLY.lock().unwrap() = Some(version);
+            settings
+        }
+        SettingsFile::Missing => AppSettings::default(),
+        SettingsFile::Unreadable(e) => {
+            eprintln!("Failed to read settings, using defaults: {}", e);
+            AppSettings::default()
+        }
+        SettingsFile::Corrupt(e) => {
+            eprintln!("Failed to parse settings: {}", e);
+            let defaults = AppSettings::default();
+            recover_corrupt_settings(&path, &defaults);
+            defaults
+        }
+    }
+}
+
+/// Re-check the settings file on disk, e.g. after a partial write or a bad
+/// manual edit. A corrupt file is backed up and replaced with the settings in use.
+pub(crate) fn check_settings_file() {
+    let Some(path) = settings_path() else {
+        return;
+    };
+    // Held throughout so a change made meanwhile isn't overwritten by an older copy
+    let settings = SETTINGS.lock().unwrap();
+    if let SettingsFile::Corrupt(e) = read_settings_file(&path) {
+        eprintln!("Settings file became unreadable: {}", e);
+        recover_corrupt_settings(&path, &settings);
+    }
+}
+
+/// Write `settings` to disk. Only call this with the `SETTINGS` lock held (or
+/// while it's being initialized), so writes happen in the order changes were made.
+/// Does nothing when the file on disk is from a newer version.
+fn write_settings_file(settings: &AppSettings) {
+    if SETTINGS_READ_ONLY.lock().unwrap().is_some() {
+        return;
+    }
+    let Some(path) = settings_path() else {
+        return;
+    };
+    let json = match serde_json::to_string_pretty(settings) {
+        Ok(json) => json,
+        Err(e) => {
+            eprintln!("Failed to serialize settings: {}", e);
+            return;
+        }
+    };
+    let Some(dir) = path.parent() else {
+        return;
+    };
+    let result = write_atomically(&path, &json);
+    record_write_result(dir, &result);
+    if let Err(e) = result {
+        eprintln!("Failed to save settings: {}", e);
+    }
+}
+
+pub(crate) fn current_settings() -> AppSettings {
+    SETTINGS.lock().unwrap().clone()
+}
+
+/// Names of the settings, as in settings.json, that differ between `before` and `after`
+fn changed_setting_keys(before: &AppSettings, after: &AppSettings) -> Vec<String> {
+    let (Ok(serde_json::Value::Object(before)), Ok(serde_json::Value::Object(after))) =
+        (serde_json::to_value(before), serde_json::to_value(after))
+    else {
+        return Vec::new();
+    };
+    after.iter().filter(|(key, value)| before.get(*key) != Some(value)).map(|(key, _)| key.clone()).collect()
+}
+
+/// Change the settings and save them, returning the names of the ones that
+/// changed. Changes from any thread are applied and written one at a time, so
+/// none is lost and the file always matches the settings in use.
+pub(crate) fn update_settings(f: impl FnOnce(&mut AppSettings)) -> Vec<String> {
+    let mut settings = SETTINGS.lock().unwrap();
+    let before = settings.clone();
+    f(&mut settings);
+    write_settings_file(&settings);
+    changed_setting_keys(&before, &settings)
+}
+
+/// Write the settings in use to disk, e.g. to create the file for editing
+pub(crate) fn save_current_settings() {
+    write_settings_file(&SETTINGS.lock().unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::fixtures::{block, local};
+
+    #[test]
+    fn concurrent_setting_changes_are_all_saved() {
+        let start = current_settings().output_token_cap.unwrap_or(0);
+        let writers: Vec<_> = (0..8)
+            .map(|writer| {
+                std::thread::spawn(move || {
+                    for _ in 0..25 {
+                        update_settings(|settings| {
+                            // Read-modify-write, so an interleaved change would lose a count
+                            settings.output_token_cap = Some(settings.output_token_cap.unwrap_or(0) + 1);
+                            match writer {
+                                0 => settings.block_budget = Some(1.0),
+                                1 => settings.daily_budget = Some(2.0),
+                                2 => settings.weekly_budget = Some(3.0),
+                                3 => settings.monthly_budget = Some(4.0),
+                                4 => settings.daily_cost_cap = Some(5.0),
+                                5 => settings.model_change_alerts = true,
+                                _ => {}
+                            }
+                        });
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let SettingsFile::Valid(saved) = read_settings_file(&settings_path().unwrap()) else {
+            panic!("settings file isn't valid current-version settings");
+        };
+        assert_eq!(saved.output_token_cap, Some(start + 200));
+        assert_eq!(saved.block_budget, Some(1.0));
+        assert_eq!(saved.daily_budget, Some(2.0));
+        assert_eq!(saved.weekly_budget, Some(3.0));
+        assert_eq!(saved.monthly_budget, Some(4.0));
+        assert_eq!(saved.daily_cost_cap, Some(5.0));
+        assert!(saved.model_change_alerts);
+        assert_eq!(serde_json::to_value(&saved).unwrap(), serde_json::to_value(current_settings()).unwrap());
+    }
+
+    #[test]
+    fn raw_responses_within_the_limit_are_kept_whole() {
+        let raw = RawResponse::new("blocks --json", br#"{"blocks": []}"#);
+        assert_eq!(raw.args, "blocks --json");
+        assert_eq!(raw.stdout, r#"{"blocks": []}"#);
+        assert!(!raw.truncated);
+
+        let exactly = vec![b'x'; RAW_RESPONSE_LIMIT];
+        let raw = RawResponse::new("daily --json", &exactly);
+        assert_eq!(raw.stdout.len(), RAW_RESPONSE_LIMIT);
+        assert!(!raw.truncated);
+    }
+
+    #[test]
+    fn long_raw_responses_are_cut_at_the_limit() {
+        let long = vec![b'x'; RAW_RESPONSE_LIMIT * 3];
+        let raw = RawResponse::new("blocks --json", &long);
+        assert_eq!(raw.stdout.len(), RAW_RESPONSE_LIMIT);
+        assert!(raw.truncated);
+    }
+
+    #[test]
+    fn a_character_cut_by_the_limit_is_left_out() {
+        let mut stdout = vec![b'x'; RAW_RESPONSE_LIMIT - 1];
+        stdout.extend_from_slice("é and more".as_bytes());
+        let raw = RawResponse::new("blocks --json", &stdout);
+        assert_eq!(raw.stdout.len(), RAW_RESPONSE_LIMIT - 1);
+        assert!(raw.stdout.bytes().all(|byte| byte == b'x'));
+        assert!(raw.truncated);
+
+        // One that ends exactly at the limit is kept
+        let mut fits = vec![b'x'; RAW_RESPONSE_LIMIT - 2];
+        fits.extend_from_slice("é".as_bytes());
+        let raw = RawResponse::new("blocks --json", &fits);
+        assert!(raw.stdout.ends_with('é'));
+        assert!(!raw.truncated);
+    }
+
+    #[test]
+    fn invalid_utf8_before_the_limit_is_replaced() {
+        let raw = RawResponse::new("blocks --json", b"ok \xff then");
+        assert_eq!(raw.stdout, "ok \u{FFFD} then");
+        assert!(!raw.truncated);
+    }
+
+    #[test]
+    fn session_snapshots_round_trip_with_their_title() {
+        let title = RenderedTitle {
+            rendered_title: "⚡ $3.20".to_string(),
+            display_mode: TrayDisplay::Cost,
+            formatted_cost: Some("$3.20".to_string()),
+        };
+        let session = block(local(2025, 6, 11, 9, 0), local(2025, 6, 11, 14, 0), 3.2);
+        let snapshot = SessionSnapshot { version: SESSION_SNAPSHOT_VERSION, session: Some(session), title };
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(json["version"], 2);
+        assert_eq!(json["rendered_title"], "⚡ $3.20");
+        assert_eq!(json["display_mode"], "cost");
+        assert_eq!(json["formatted_cost"], "$3.20");
+        assert_eq!(parse_session_snapshot(&json.to_string()), Some(snapshot.clone()));
+
+        let idle = SessionSnapshot { session: None, title: RenderedTitle::default(), ..snapshot };
+        assert_eq!(parse_session_snapshot(&serde_json::to_string(&idle).unwrap()), Some(idle));
+    }
+
+    #[test]
+    fn version_1_session_snapshots_still_load() {
+        // Version 1 saved the bare block
+        let session = block(local(2025, 6, 11, 9, 0), local(2025, 6, 11, 14, 0), 3.2);
+        let loaded = parse_session_snapshot(&serde_json::to_string(&session).unwrap()).unwrap();
+        assert_eq!(loaded.version, 1);
+        assert_eq!(loaded.session.unwrap().cost_usd, 3.2);
+        assert_eq!(loaded.title, RenderedTitle::default());
+
+        // ... or null without an active session
+        let loaded = parse_session_snapshot("null").unwrap();
+        assert_eq!((loaded.version, loaded.session), (1, None));
+
+        assert_eq!(parse_session_snapshot("{\"truncated"), None);
+    }
+
+    /// A v1 settings file as written by the app before settings were
+    /// versioned, then edited by hand
+    const CAPTURED_V1_SETTINGS: &str = r#"{
+        "compact_title": true,
+        "compact_threshold": 1000.0,
+        "include_active_in_totals": false,
+        "tray_display": "tokens+cache",
+        "block_budget": 25.0,
+        "daily_cost_cap": 80.0,
+        "excluded_models": ["claude-3-haiku-20240307"],
+        "http_proxy": "",
+        "https_proxy": "http://proxy.corp:8080",
+        "status_file_path": "   ",
+        "daily_report_time": "18:00",
+        "language": ""
+    }"#;
+
+    fn as_json(settings: &AppSettings) -> serde_json::Value {
+        serde_json::to_value(settings).unwrap()
+    }
+
+    /// Parse `contents`, which must be an older file, returning the migrated settings
+    fn migrated(contents: &str) -> (AppSettings, u32) {
+        match parse_settings(contents).unwrap() {
+            SettingsFile::Outdated { settings, from } => (settings, from),
+            _ => panic!("expected an outdated settings file: {}", contents),
+        }
+    }
+
+    #[test]
+    fn captured_v1_settings_are_migrated() {
+        let (settings, from) = migrated(CAPTURED_V1_SETTINGS);
+        assert_eq!((from, settings.version), (1, SETTINGS_VERSION));
+        assert!(settings.compact_title);
+        assert!(!settings.include_active_in_totals);
+        assert_eq!(settings.tray_display, TrayDisplay::TokensCache);
+        assert_eq!((settings.block_budget, settings.daily_cost_cap), (Some(25.0), Some(80.0)));
+        assert_eq!(settings.excluded_models, ["claude-3-haiku-20240307"]);
+        // Blank optional text becomes unset; real values are kept
+        assert_eq!((settings.http_proxy, settings.status_file_path, settings.language), (None, None, None));
+        assert_eq!(settings.https_proxy.as_deref(), Some("http://proxy.corp:8080"));
+        assert_eq!(settings.daily_report_time.as_deref(), Some("18:00"));
+        // Settings v1 didn't have yet get their defaults
+        assert_eq!(settings.stall_alert_mins, AppSettings::default().stall_alert_mins);
+    }
+
+    #[test]
+    fn empty_v1_settings_are_the_defaults() {
+        let (settings, from) = migrated("{}");
+        assert_eq!(from, 1);
+        assert_eq!(as_json(&settings), as_json(&AppSettings::default()));
+    }
+
+    #[test]
+    fn current_settings_files_are_not_migrated() {
+        let json = serde_json::to_string(&AppSettings { block_budget: Some(5.0), ..AppSettings::default() }).unwrap();
+        match parse_settings(&json).unwrap() {
+            SettingsFile::Valid(settings) => assert_eq!(settings.block_budget, Some(5.0)),
+            _ => panic!("expected a current settings file"),
+        }
+        assert!(parse_settings(r#"{"version": 2, "block_budget": "lots"}"#).is_err());
+        assert!(parse_settings("{\"compact_title\": tr").is_err());
+    }
+
+    #[test]
+    fn v1_to_v2_only_clears_blank_optional_text() {
+        let mut file = serde_json::json!({
+            "http_proxy": " ",
+            "custom_icon_path": "",
+            "post_refresh_command": "say done",
+            "npm_registry": null,
+            "tray_title_format": "",
+        });
+        let fields = file.as_object_mut().unwrap();
+        migrate_settings_v1_to_v2(fields);
+        let expected = serde_json::json!({
+            "http_proxy": null,
+            "custom_icon_path": null,
+            "post_refresh_command": "say done",
+            "npm_registry": null,
+            // Not optional, so blank means blank
+            "tray_title_format": "",
+        });
+        assert_eq!(serde_json::Value::Object(fields.clone()), expected);
+        // Running it again changes nothing
+        let once = fields.clone();
+        migrate_settings_v1_to_v2(fields);
+        assert_eq!(*fields, once);
+    }
+
+    #[test]
+    fn future_settings_keep_every_field_they_can_read() {
+        let json = r#"{
+            "version": 9,
+            "block_budget": "per-model budgets, say",
+            "daily_budget": 5.0,
+            "compact_title": true,
+            "tray_display": "sparkline",
+            "setting_from_the_future": {"enabled": true}
+        }"#;
+        match parse_settings(json).unwrap() {
+            SettingsFile::Future { settings, version } => {
+                assert_eq!((version, settings.version), (9, 9));
+                assert_eq!(settings.daily_budget, Some(5.0));
+                assert!(settings.compact_title);
+                // Unreadable ones keep their defaults rather than resetting the rest
+                assert_eq!(settings.block_budget, AppSettings::default().block_budget);
+                assert_eq!(settings.tray_display, AppSettings::default().tray_display);
+            }
+            _ => panic!("expected a future settings file"),
+        }
+    }
+
+    #[test]
+    fn migrating_saving_and_loading_again_is_stable() {
+        let values = [
+            serde_json::json!(""),
+            serde_json::json!("  "),
+            serde_json::json!("18:00"),
+            serde_json::json!("~/reports/ccusage.csv"),
+            serde_json::Value::Null,
+        ];
+        let mut files = vec![CAPTURED_V1_SETTINGS.to_string(), "{}".to_string()];
+        for (i, key) in OPTIONAL_TEXT_SETTINGS.iter().enumerate() {
+            for (j, value) in values.iter().enumerate() {
+                // Each key with each value, alongside a neighbour with another
+                let neighbour = OPTIONAL_TEXT_SETTINGS[(i + 1) % OPTIONAL_TEXT_SETTINGS.len()];
+                let other = &values[(i + j + 1) % values.len()];
+                files.push(serde_json::json!({ *key: value, neighbour: other, "block_budget": i }).to_string());
+            }
+        }
+        for file in files {
+            let (settings, _) = migrated(&file);
+            // Saved the way write_settings_file does
+            let saved = serde_json::to_string_pretty(&settings).unwrap();
+            let SettingsFile::Valid(loaded) = parse_settings(&saved).unwrap() else {
+                panic!("a migrated file should load as current: {}", saved);
+            };
+            assert_eq!(as_json(&loaded), as_json(&settings), "{}", file);
+            assert_eq!(serde_json::to_string_pretty(&loaded).unwrap(), saved, "{}", file);
+        }
+    }
+
+    #[test]
+    fn settings_are_backed_up_before_migrating() {
+        let dir = TestDir::new("settings-migration");
+        let path = dir.path().join("settings.json");
+        std::fs::write(&path, CAPTURED_V1_SETTINGS).unwrap();
+        let backup = back_up_before_migration(&path, 1).unwrap();
+        assert!(backup.file_name().unwrap().to_string_lossy().starts_with("settings.v1-"));
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), CAPTURED_V1_SETTINGS);
+        assert_eq!(settings_backups(dir.path()), [backup]);
+    }
+}
+
@@ -0,0 +1,1298 @@
+use chrono::{Datelike, Timelike, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+
+use crate::model::{parse_local_time, BlockData, DailyUsage, ModelBreakdown};
+use crate::state::TrayModelSelection;
+
+/// Number of recent samples kept for refresh timing statistics
+const TIMING_WINDOW: usize = 20;
+
+/// Average refresh time above which the menu suggests faster alternatives to npx
+pub(crate) const SLOW_REFRESH_SECS: f64 = 10.0;
+
+/// Rolling window of the most recent durations
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RollingStats {
+    pub(crate) samples: VecDeque<Duration>,
+}
+
+impl RollingStats {
+    pub(crate) const fn new() -> Self {
+        Self { samples: VecDeque::new() }
+    }
+
+    pub(crate) fn push(&mut self, sample: Duration) {
+        if self.samples.len() == TIMING_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub(crate) fn mean(&self) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        Some(self.samples.iter().sum::<Duration>() / self.samples.len() as u32)
+    }
+
+    /// Nearest-rank 95th percentile
+    pub(crate) fn p95(&self) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<_> = self.samples.iter().copied().collect();
+        sorted.sort();
+        let rank = ((sorted.len() as f64) * 0.95).ceil() as usize;
+        Some(sorted[rank.clamp(1, sorted.len()) - 1])
+    }
+}
+
+/// Sum of block costs, optionally leaving out the in-progress block for a "settled" total
+pub(crate) fn total_cost(blocks: &[BlockData], include_active: bool) -> f64 {
+    blocks
+        .iter()
+        .filter(|block| !block.is_gap && (include_active || !block.is_active))
+        .map(|block| block.cost_usd)
+        .sum()
+}
+
+/// Fraction of today's spend that came from `block`, counting the active block
+/// in today's total whatever the totals setting says. `None` before any spend
+/// today. Capped at 1 for a block that started yesterday.
+pub(crate) fn block_share_of_day(block: &BlockData, today_blocks: &[BlockData]) -> Option<f64> {
+    let day_total = total_cost(today_blocks, true);
+    (day_total > 0.0).then(|| (block.cost_usd / day_total).min(1.0))
+}
+
+/// Length of the block's window as reported by ccusage, rather than assuming
+/// five hours. `None` if either end is missing or the window is empty.
+pub(crate) fn block_duration(block: &BlockData) -> Option<chrono::Duration> {
+    let start = parse_local_time(&block.start_time)?;
+    let end = parse_local_time(&block.end_time)?;
+    (end > start).then_some(end - start)
+}
+
+/// How recently the previous block must have ended to be shown beside the active one
+const PREVIOUS_BLOCK_WINDOW_HOURS: i64 = 2;
+
+/// When the block's last activity was, falling back to the end of its window
+pub(crate) fn block_last_end(block: &BlockData) -> Option<chrono::DateTime<chrono::Local>> {
+    parse_local_time(block.actual_end_time.as_deref().unwrap_or(&block.end_time))
+}
+
+/// The most recently ended block that isn't active or a gap, if it ended
+/// within the last `PREVIOUS_BLOCK_WINDOW_HOURS` before `now`
+pub(crate) fn previous_block(blocks: &[BlockData], now: chrono::DateTime<chrono::Local>) -> Option<&BlockData> {
+    blocks
+        .iter()
+        .filter(|block| !block.is_active && !block.is_gap)
+        .filter_map(|block| Some((block_last_end(block)?, block)))
+        .filter(|(end, _)| *end <= now && now - *end < chrono::Duration::hours(PREVIOUS_BLOCK_WINDOW_HOURS))
+        .max_by_key(|(end, _)| *end)
+        .map(|(_, block)| block)
+}
+
+/// Share of the block's window still to go at `now`, from 1.0 at its start to 0.0 at its end
+pub(crate) fn block_remaining_fraction(block: &BlockData, now: chrono::DateTime<chrono::Local>) -> Option<f64> {
+    let total = block_duration(block)?.num_seconds();
+    let end = parse_local_time(&block.end_time)?;
+    Some(((end - now).num_seconds() as f64 / total as f64).clamp(0.0, 1.0))
+}
+
+/// Minutes into a block before its token rate is trusted; early rates swing widely
+pub(crate) const BURN_RATE_WARMUP_MINUTES: i64 = 10;
+
+/// Slowest pace, in tokens per minute, still counted as using tokens at all
+const MIN_TOKENS_PER_MINUTE: f64 = 1.0;
+
+/// Where a block stands against its token limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LimitEstimate {
+    Exceeded,
+    /// At the pace so far, the limit is reached after this long
+    ReachedIn(chrono::Duration),
+}
+
+/// When `used` tokens reach `limit` at the average pace over `elapsed`. `None`
+/// during the warm-up, when barely any tokens are being used, or when the
+/// limit wouldn't be reached before the block ends in `until_end`.
+pub(crate) fn estimate_until_limit(
+    used: u64,
+    limit: u64,
+    elapsed: chrono::Duration,
+    until_end: chrono::Duration,
+) -> Option<LimitEstimate> {
+    if used >= limit {
+        return Some(LimitEstimate::Exceeded);
+    }
+    if elapsed.num_minutes() < BURN_RATE_WARMUP_MINUTES {
+        return None;
+    }
+    let per_minute = used as f64 / (elapsed.num_seconds() as f64 / 60.0);
+    if per_minute < MIN_TOKENS_PER_MINUTE {
+        return None;
+    }
+    let eta = chrono::Duration::seconds(((limit - used) as f64 / per_minute * 60.0).round() as i64);
+    (eta <= until_end).then_some(LimitEstimate::ReachedIn(eta))
+}
+
+/// `estimate_until_limit` for the active block at `now`
+pub(crate) fn block_limit_estimate(
+    block: &BlockData,
+    limit: u64,
+    now: chrono::DateTime<chrono::Local>,
+) -> Option<LimitEstimate> {
+    let start = parse_local_time(&block.start_time)?;
+    let end = parse_local_time(&block.end_time)?;
+    estimate_until_limit(block.token_counts.total(), limit, now - start, end - now)
+}
+
+/// The model to name in the tray title. Token and cost rankings need ccusage's
+/// per-model breakdown; without one the first model is used.
+pub(crate) fn select_tray_model(block: &BlockData, selection: TrayModelSelection) -> Option<&str> {
+    let breakdowns = &block.model_breakdowns;
+    let ranked = match selection {
+        TrayModelSelection::First => None,
+        TrayModelSelection::MostUsed => breakdowns.iter().max_by_key(|breakdown| breakdown.total_tokens()),
+        TrayModelSelection::MostExpensive => breakdowns.iter().max_by(|a, b| a.cost.total_cmp(&b.cost)),
+    };
+    ranked
+        .map(|breakdown| breakdown.model_name.as_str())
+        .or_else(|| block.models.first().map(String::as_str))
+}
+
+/// Rough share of the block's time spent on each model, taken as its share of
+/// the tokens, largest first. Empty without ccusage's per-model breakdown.
+pub(crate) fn model_time_shares(block: &BlockData) -> Vec<(&str, f64)> {
+    let total: u64 = block.model_breakdowns.iter().map(ModelBreakdown::total_tokens).sum();
+    if total == 0 {
+        return Vec::new();
+    }
+    let mut shares: Vec<(&str, f64)> = block
+        .model_breakdowns
+        .iter()
+        .map(|breakdown| (breakdown.model_name.as_str(), breakdown.total_tokens() as f64 / total as f64))
+        .collect();
+    shares.sort_by(|a, b| b.1.total_cmp(&a.1));
+    shares
+}
+
+/// Whole-number percentages of `values`' total that add up to exactly 100,
+/// using the largest-remainder method: round everything down, then give the
+/// leftover points to the largest fractional parts. All zeros when the total is zero.
+pub(crate) fn percent_shares(values: &[f64]) -> Vec<u32> {
+    let total: f64 = values.iter().map(|value| value.max(0.0)).sum();
+    if total <= 0.0 {
+        return vec![0; values.len()];
+    }
+    let exact: Vec<f64> = values.iter().map(|value| value.max(0.0) / total * 100.0).collect();
+    let mut shares: Vec<u32> = exact.iter().map(|share| share.floor() as u32).collect();
+    let leftover = 100u32.saturating_sub(shares.iter().sum());
+    let mut by_remainder: Vec<usize> = (0..exact.len()).collect();
+    by_remainder.sort_by(|&a, &b| (exact[b] - exact[b].floor()).total_cmp(&(exact[a] - exact[a].floor())));
+    for &index in by_remainder.iter().take(leftover as usize) {
+        shares[index] += 1;
+    }
+    shares
+}
+
+/// Heatmap intensity 0–4 for each of `values`: 0 for days without spend, and
+/// 1–4 by the quartile of the nonzero values a day falls in. Ties take the
+/// higher level, so a single busy day or a run of equal days shows as 4 rather
+/// than being spread across levels, and empty days don't drag the quartiles down.
+/// This is intended: with the same spend every day, every day is 4, since no
+/// day used less than the others.
+pub(crate) fn heatmap_levels(values: &[f64]) -> Vec<u8> {
+    let mut nonzero: Vec<f64> = values.iter().copied().filter(|value| *value > 0.0).collect();
+    nonzero.sort_by(f64::total_cmp);
+    values
+        .iter()
+        .map(|&value| {
+            if value <= 0.0 {
+                return 0;
+            }
+            let at_or_below = nonzero.partition_point(|&other| other <= value);
+            (at_or_below * 4).div_ceil(nonzero.len()).clamp(1, 4) as u8
+        })
+        .collect()
+}
+
+/// Whether `model_name` is one of `excluded`. Entries match case-insensitively
+/// anywhere in the name, so "haiku" covers every Haiku release.
+pub(crate) fn is_excluded_model(model_name: &str, excluded: &[String]) -> bool {
+    let name = model_name.to_ascii_lowercase();
+    excluded
+        .iter()
+        .map(|entry| entry.trim().to_ascii_lowercase())
+        .any(|entry| !entry.is_empty() && name.contains(&entry))
+}
+
+/// What removing excluded models did across the fetched blocks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct ModelExclusion {
+    /// Some excluded usage was subtracted
+    pub(crate) applied: bool,
+    /// Some excluded usage had no per-model breakdown, so it's still counted
+    pub(crate) unavailable: bool,
+}
+
+impl ModelExclusion {
+    pub(crate) const NONE: ModelExclusion = ModelExclusion { applied: false, unavailable: false };
+
+    pub(crate) fn merge(self, other: ModelExclusion) -> ModelExclusion {
+        ModelExclusion { applied: self.applied || other.applied, unavailable: self.unavailable || other.unavailable }
+    }
+}
+
+/// Subtract the cost and tokens of `excluded` models from `block`, using
+/// ccusage's per-model breakdown. An excluded model the block lists without a
+/// breakdown can't be separated out, so it stays in the figures.
+pub(crate) fn exclude_models(block: &mut BlockData, excluded: &[String]) -> ModelExclusion {
+    let (removed, kept): (Vec<ModelBreakdown>, Vec<ModelBreakdown>) = std::mem::take(&mut block.model_breakdowns)
+        .into_iter()
+        .partition(|breakdown| is_excluded_model(&breakdown.model_name, excluded));
+    block.model_breakdowns = kept;
+    let counts = &mut block.token_counts;
+    for breakdown in &removed {
+        block.cost_usd = (block.cost_usd - breakdown.cost).max(0.0);
+        counts.input_tokens = counts.input_tokens.saturating_sub(breakdown.input_tokens);
+        counts.output_tokens = counts.output_tokens.saturating_sub(breakdown.output_tokens);
+        counts.cache_creation_input_tokens =
+            counts.cache_creation_input_tokens.saturating_sub(breakdown.cache_creation_tokens);
+        counts.cache_read_input_tokens = counts.cache_read_input_tokens.saturating_sub(breakdown.cache_read_tokens);
+    }
+    let unavailable = block.models.iter().any(|model| {
+        is_excluded_model(model, excluded) && !removed.iter().any(|breakdown| &breakdown.model_name == model)
+    });
+    if !unavailable {
+        block.models.retain(|model| !is_excluded_model(model, excluded));
+    }
+    ModelExclusion { applied: !removed.is_empty(), unavailable }
+}
+
+/// Bucket the cost of `blocks` into the 24 local hours of `day`. A block's cost
+/// is spread across the hours it spans proportionally to elapsed time, where
+/// an active block spans up to `now` and a finished one up to its last activity.
+pub(crate) fn hourly_cost_bins(
+    blocks: &[BlockData],
+    day: chrono::NaiveDate,
+    now: chrono::DateTime<chrono::Local>,
+) -> [f64; 24] {
+    let mut bins = [0.0; 24];
+
+    for block in blocks.iter().filter(|block| !block.is_gap && block.cost_usd > 0.0) {
+        let Some(start) = parse_local_time(&block.start_time) else {
+            continue;
+        };
+        let end = if block.is_active {
+            now
+        } else {
+            match parse_local_time(block.actual_end_time.as_deref().unwrap_or(&block.end_time)) {
+                Some(end) => end,
+                None => continue,
+            }
+        };
+
+        let span = (end - start).num_seconds();
+        if span <= 0 {
+            // No measurable duration: attribute everything to the starting hour
+            if start.date_naive() == day {
+                bins[start.hour() as usize] += block.cost_usd;
+            }
+            continue;
+        }
+
+        for (hour, bin) in bins.iter_mut().enumerate() {
+            let Some(bin_start) = day
+                .and_hms_opt(hour as u32, 0, 0)
+                .and_then(|t| t.and_local_timezone(chrono::Local).earliest())
+            else {
+                continue;
+            };
+            let bin_end = bin_start + chrono::Duration::hours(1);
+            let overlap = (end.min(bin_end) - start.max(bin_start)).num_seconds();
+            if overlap > 0 {
+                *bin += block.cost_usd * overlap as f64 / span as f64;
+            }
+        }
+    }
+
+    bins
+}
+
+/// Per-day totals of `blocks`, oldest first. Each block counts towards the
+/// local day it started on, so a block running past midnight stays with the
+/// day it began rather than being split as `ccusage daily` would.
+pub(crate) fn daily_usage_from_blocks(blocks: &[BlockData]) -> Vec<DailyUsage> {
+    weighted_daily_usage(blocks, |_| 1.0)
+}
+
+/// `daily_usage_from_blocks` counting only the share of each block inside `window`
+pub(crate) fn daily_usage_in_window(blocks: &[BlockData], window: &CountingWindow) -> Vec<DailyUsage> {
+    weighted_daily_usage(blocks, |block| block_window_fraction(block, window))
+}
+
+fn weighted_daily_usage(blocks: &[BlockData], weight: impl Fn(&BlockData) -> f64) -> Vec<DailyUsage> {
+    let mut days: BTreeMap<chrono::NaiveDate, (u64, f64)> = BTreeMap::new();
+    for block in blocks.iter().filter(|block| !block.is_gap) {
+        let Some(start) = parse_local_time(&block.start_time) else {
+            continue;
+        };
+        let weight = weight(block);
+        let day = days.entry(start.date_naive()).or_default();
+        day.0 += (block.token_counts.total() as f64 * weight).round() as u64;
+        day.1 += block.cost_usd * weight;
+    }
+    days.into_iter()
+        .map(|(date, (total_tokens, total_cost))| DailyUsage {
+            date: date.format("%Y-%m-%d").to_string(),
+            total_tokens,
+            total_cost,
+        })
+        .collect()
+}
+
+/// Days and hours of the week that usage counts towards when only part of it
+/// is reimbursed, e.g. Mon–Fri 09:00–18:00. A window whose end is at or before
+/// its start runs past midnight into the next day.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct CountingWindow {
+    /// Days the window opens on
+    pub(crate) days: Vec<Weekday>,
+    /// Local time the window opens, `HH:MM`
+    pub(crate) start: String,
+    /// Local time the window closes, `HH:MM`
+    pub(crate) end: String,
+}
+
+impl Default for CountingWindow {
+    fn default() -> Self {
+        Self {
+            days: vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri],
+            start: "09:00".to_string(),
+            end: "18:00".to_string(),
+        }
+    }
+}
+
+impl CountingWindow {
+    /// Opening and closing times, `None` when either doesn't parse
+    fn times(&self) -> Option<(chrono::NaiveTime, chrono::NaiveTime)> {
+        let parse = |time: &str| chrono::NaiveTime::parse_from_str(time.trim(), "%H:%M").ok();
+        Some((parse(&self.start)?, parse(&self.end)?))
+    }
+
+    /// Seconds of `from..to` inside the window, in local wall-clock time. A
+    /// window that doesn't parse covers nothing.
+    fn overlap_seconds(&self, from: chrono::NaiveDateTime, to: chrono::NaiveDateTime) -> i64 {
+        let Some((open, close)) = self.times() else {
+            return 0;
+        };
+        let mut total = 0;
+        // Start a day early to catch a window opening the evening before `from`
+        let mut date = from.date() - chrono::Duration::days(1);
+        while date <= to.date() {
+            if self.days.contains(&date.weekday()) {
+                let window_start = date.and_time(open);
+                let close_date = if close > open { date } else { date + chrono::Duration::days(1) };
+                let window_end = close_date.and_time(close);
+                total += (window_end.min(to) - window_start.max(from)).num_seconds().max(0);
+            }
+            date += chrono::Duration::days(1);
+        }
+        total
+    }
+}
+
+/// Share of `block`'s cost that falls inside `window`: the fraction of its
+/// active span, from its start to its last activity, inside the window. A
+/// block whose activity is a single instant counts fully or not at all, and
+/// one without parseable times doesn't count.
+pub(crate) fn block_window_fraction(block: &BlockData, window: &CountingWindow) -> f64 {
+    let (Some(start), Some(end)) = (parse_local_time(&block.start_time), block_last_end(block)) else {
+        return 0.0;
+    };
+    let (from, to) = (start.naive_local(), end.naive_local());
+    if to <= from {
+        let in_window = window.overlap_seconds(from, from + chrono::Duration::seconds(1)) > 0;
+        return if in_window { 1.0 } else { 0.0 };
+    }
+    window.overlap_seconds(from, to) as f64 / (to - from).num_seconds() as f64
+}
+
+/// `total_cost` counting only the share of each block inside `window`
+pub(crate) fn total_cost_in_window(blocks: &[BlockData], include_active: bool, window: &CountingWindow) -> f64 {
+    blocks
+        .iter()
+        .filter(|block| !block.is_gap && (include_active || !block.is_active))
+        .map(|block| block.cost_usd * block_window_fraction(block, window))
+        .sum()
+}
+
+/// Completed days before today whose spend feeds the monthly projection
+pub(crate) const PROJECTION_WINDOW_DAYS: i64 = 14;
+
+fn is_weekend(date: chrono::NaiveDate) -> bool {
+    matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct MonthProjection {
+    pub(crate) total: f64,
+    pub(crate) method: &'static str,
+}
+
+/// Projected spend for the month of `today`: the month so far plus the rest of
+/// the month at the weekday and weekend daily averages of the trailing window.
+/// Days missing from `history` count as zero spend, so users who never work
+/// weekends project nothing for them. `None` without any spend in the window.
+pub(crate) fn project_month_cost(
+    history: &[(chrono::NaiveDate, f64)],
+    today: chrono::NaiveDate,
+) -> Option<MonthProjection> {
+    let window_start = today - chrono::Duration::days(PROJECTION_WINDOW_DAYS);
+    let in_window = |date: &chrono::NaiveDate| *date >= window_start && *date < today;
+    if !history.iter().any(|(date, cost)| in_window(date) && *cost > 0.0) {
+        return None;
+    }
+
+    let (mut weekday_cost, mut weekend_cost) = (0.0, 0.0);
+    for (date, cost) in history.iter().filter(|(date, _)| in_window(date)) {
+        if is_weekend(*date) {
+            weekend_cost += cost;
+        } else {
+            weekday_cost += cost;
+        }
+    }
+    let window_days: Vec<chrono::NaiveDate> = window_start.iter_days().take_while(|date| *date < today).collect();
+    let weekend_days = window_days.iter().filter(|date| is_weekend(**date)).count();
+    let weekdays = window_days.len() - weekend_days;
+    let average = |cost: f64, days: usize| if days == 0 { 0.0 } else { cost / days as f64 };
+    let (weekday_avg, weekend_avg) = (average(weekday_cost, weekdays), average(weekend_cost, weekend_days));
+
+    let month_to_date: f64 = history
+        .iter()
+        .filter(|(date, _)| date.year() == today.year() && date.month() == today.month() && *date <= today)
+        .map(|(_, cost)| cost)
+        .sum();
+    let remaining: f64 = today
+        .iter_days()
+        .skip(1)
+        .take_while(|date| date.month() == today.month())
+        .map(|date| if is_weekend(date) { weekend_avg } else { weekday_avg })
+        .sum();
+
+    Some(MonthProjection {
+        total: month_to_date + remaining,
+        method: "workday-weighted",
+    })
+}
+
+/// Spend rate that never counts as unusual on its own, in dollars per minute
+const SPIKE_FLOOR_PER_MINUTE: f64 = 0.5;
+
+/// How many times the usual rate counts as unusually fast
+const SPIKE_MULTIPLIER: f64 = 4.0;
+
+/// Number of refresh intervals the spend rate is measured over
+pub(crate) const SPIKE_INTERVALS: usize = 3;
+
+/// Days of history the usual spend rate is averaged over
+const SPIKE_BASELINE_DAYS: i64 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct SpendSpike {
+    pub(crate) amount: f64,
+    pub(crate) minutes: f64,
+}
+
+/// Average spend per minute over the completed days of the past week, `None`
+/// without any spend to compare against
+pub(crate) fn baseline_per_minute(history: &[(chrono::NaiveDate, f64)], today: chrono::NaiveDate) -> Option<f64> {
+    let since = today - chrono::Duration::days(SPIKE_BASELINE_DAYS);
+    let total: f64 = history
+        .iter()
+        .filter(|(date, _)| *date >= since && *date < today)
+        .map(|(_, cost)| cost)
+        .sum();
+    (total > 0.0).then(|| total / (SPIKE_BASELINE_DAYS * 24 * 60) as f64)
+}
+
+/// Spend over the last `SPIKE_INTERVALS` refreshes of a block, given as
+/// (time, block cost so far) samples oldest first, if its per-minute rate is
+/// over `SPIKE_MULTIPLIER` times the baseline and above the floor. Without a
+/// baseline only the floor applies.
+pub(crate) fn detect_spend_spike(
+    samples: &[(chrono::DateTime<chrono::Local>, f64)],
+    baseline_per_minute: Option<f64>,
+) -> Option<SpendSpike> {
+    let window = &samples[samples.len().saturating_sub(SPIKE_INTERVALS + 1)..];
+    let (first, last) = (window.first()?, window.last()?);
+    let minutes = (last.0 - first.0).num_seconds() as f64 / 60.0;
+    let amount = last.1 - first.1;
+    if minutes <= 0.0 || amount <= 0.0 {
+        return None;
+    }
+    let threshold = baseline_per_minute.map_or(0.0, |baseline| baseline * SPIKE_MULTIPLIER).max(SPIKE_FLOOR_PER_MINUTE);
+    (amount / minutes > threshold).then_some(SpendSpike { amount, minutes })
+}
+
+/// Running cost as a sparkline of `width` evenly spaced points between the
+/// first and last sample, drawn with `levels` from lowest to highest. Each
+/// point shows the latest sample at or before it, so irregular refresh
+/// intervals don't stretch or squash the shape. `None` with fewer than two
+/// samples or no time between them.
+pub(crate) fn cost_sparkline(
+    samples: &[(chrono::DateTime<chrono::Local>, f64)],
+    width: usize,
+    levels: &[char],
+) -> Option<String> {
+    let (first, last) = (samples.first()?, samples.last()?);
+    let span = (last.0 - first.0).num_seconds();
+    if samples.len() < 2 || span <= 0 || width == 0 || levels.is_empty() {
+        return None;
+    }
+    let (low, high) = samples.iter().fold((f64::MAX, f64::MIN), |(low, high), (_, cost)| {
+        (low.min(*cost), high.max(*cost))
+    });
+
+    let line = (1..=width)
+        .map(|point| {
+            let at = first.0 + chrono::Duration::seconds(span * point as i64 / width as i64);
+            let cost = samples.iter().take_while(|(time, _)| *time <= at).last().map_or(first.1, |sample| sample.1);
+            let level = if high > low {
+                ((cost - low) / (high - low) * (levels.len() - 1) as f64).round() as usize
+            } else {
+                0
+            };
+            levels[level.min(levels.len() - 1)]
+        })
+        .collect();
+    Some(line)
+}
+
+/// The largest increase between consecutive samples, as the time of the later
+/// sample and the amount added. `None` if the cost never went up.
+pub(crate) fn biggest_cost_jump(
+    samples: &[(chrono::DateTime<chrono::Local>, f64)],
+) -> Option<(chrono::DateTime<chrono::Local>, f64)> {
+    samples
+        .windows(2)
+        .map(|pair| (pair[1].0, pair[1].1 - pair[0].1))
+        .filter(|(_, amount)| *amount > 0.0)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+/// Per-million-token prices for a model family
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ModelPricing {
+    pub(crate) input: f64,
+    pub(crate) output: f64,
+    pub(crate) cache_write: f64,
+    pub(crate) cache_read: f64,
+}
+
+/// Bundled list prices, matched by model family
+pub(crate) fn model_pricing(model_name: &str) -> Option<ModelPricing> {
+    if model_name.contains("opus") {
+        Some(ModelPricing { input: 15.0, output: 75.0, cache_write: 18.75, cache_read: 1.5 })
+    } else if model_name.contains("sonnet") {
+        Some(ModelPricing { input: 3.0, output: 15.0, cache_write: 3.75, cache_read: 0.3 })
+    } else if model_name.contains("3-5-haiku") || model_name.contains("haiku-3-5") {
+        Some(ModelPricing { input: 0.8, output: 4.0, cache_write: 1.0, cache_read: 0.08 })
+    } else if model_name.contains("haiku") {
+        Some(ModelPricing { input: 0.25, output: 1.25, cache_write: 0.3, cache_read: 0.03 })
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct CacheSavings {
+    pub(crate) amount: f64,
+    /// Computed from a blended price rather than a per-model breakdown
+    pub(crate) estimated: bool,
+}
+
+/// How much cheaper cache reads made a session compared to paying the regular
+/// input price for the same tokens. Uses the per-model cache-read breakdown when
+/// given, otherwise a blended price averaged over the block's models.
+pub(crate) fn cache_savings(
+    breakdown: Option<&[(String, u64)]>,
+    models: &[String],
+    total_cache_read: u64,
+) -> Option<CacheSavings> {
+    let saving_per_token = |pricing: ModelPricing| (pricing.input - pricing.cache_read) / 1_000_000.0;
+
+    if let Some(breakdown) = breakdown {
+        let mut amount = 0.0;
+        for (model, cache_read) in breakdown {
+            amount += saving_per_token(model_pricing(model)?) * *cache_read as f64;
+        }
+        return Some(CacheSavings { amount, estimated: false });
+    }
+
+    let known: Vec<ModelPricing> = models.iter().filter_map(|model| model_pricing(model)).collect();
+    if known.is_empty() {
+        return None;
+    }
+    let blended = known.iter().map(|&pricing| saving_per_token(pricing)).sum::<f64>() / known.len() as f64;
+    Some(CacheSavings {
+        amount: blended * total_cache_read as f64,
+        estimated: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, FakeClock};
+    use crate::model::fixtures::{block, local};
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "expected {}, got {}", expected, actual);
+    }
+
+    fn day() -> chrono::NaiveDate {
+        chrono::NaiveDate::from_ymd_opt(2025, 6, 11).unwrap()
+    }
+
+    #[test]
+    fn hourly_bins_split_a_block_across_the_hours_it_spans() {
+        let blocks = [block(local(2025, 6, 11, 9, 30), local(2025, 6, 11, 11, 30), 4.0)];
+        let bins = hourly_cost_bins(&blocks, day(), local(2025, 6, 11, 20, 0));
+        assert_close(bins[9], 1.0);
+        assert_close(bins[10], 2.0);
+        assert_close(bins[11], 1.0);
+        assert_close(bins.iter().sum(), 4.0);
+    }
+
+    #[test]
+    fn hourly_bins_end_at_last_activity_rather_than_the_window_end() {
+        let mut finished = block(local(2025, 6, 11, 10, 0), local(2025, 6, 11, 15, 0), 3.0);
+        finished.actual_end_time = Some(local(2025, 6, 11, 10, 45).to_rfc3339());
+        let bins = hourly_cost_bins(&[finished], day(), local(2025, 6, 11, 20, 0));
+        assert_close(bins[10], 3.0);
+        assert_close(bins.iter().sum(), 3.0);
+    }
+
+    #[test]
+    fn hourly_bins_spread_the_active_block_up_to_now() {
+        let mut active = block(local(2025, 6, 11, 14, 0), local(2025, 6, 11, 19, 0), 3.0);
+        active.is_active = true;
+        let bins = hourly_cost_bins(&[active], day(), local(2025, 6, 11, 15, 30));
+        assert_close(bins[14], 2.0);
+        assert_close(bins[15], 1.0);
+        assert!(bins[16..].iter().all(|&cost| cost == 0.0));
+    }
+
+    #[test]
+    fn hourly_bins_keep_only_the_part_of_a_block_on_the_day() {
+        let overnight = block(local(2025, 6, 10, 23, 0), local(2025, 6, 11, 1, 0), 2.0);
+        let bins = hourly_cost_bins(&[overnight], day(), local(2025, 6, 11, 20, 0));
+        assert_close(bins[0], 1.0);
+        assert_close(bins.iter().sum(), 1.0);
+    }
+
+    #[test]
+    fn hourly_bins_put_an_instant_block_in_its_starting_hour() {
+        let instant = block(local(2025, 6, 11, 8, 20), local(2025, 6, 11, 8, 20), 0.5);
+        let bins = hourly_cost_bins(&[instant], day(), local(2025, 6, 11, 20, 0));
+        assert_close(bins[8], 0.5);
+    }
+
+    #[test]
+    fn hourly_bins_skip_gaps() {
+        let mut gap = block(local(2025, 6, 11, 9, 0), local(2025, 6, 11, 10, 0), 1.0);
+        gap.is_gap = true;
+        let bins = hourly_cost_bins(&[gap], day(), local(2025, 6, 11, 20, 0));
+        assert!(bins.iter().all(|&cost| cost == 0.0));
+    }
+
+    #[test]
+    fn remaining_fraction_counts_down_with_the_clock() {
+        let session = block(local(2025, 6, 11, 10, 0), local(2025, 6, 11, 15, 0), 1.0);
+        let clock = FakeClock::new(local(2025, 6, 11, 9, 0));
+        assert_eq!(block_remaining_fraction(&session, clock.now()), Some(1.0));
+        clock.advance(chrono::Duration::hours(1));
+        assert_eq!(block_remaining_fraction(&session, clock.now()), Some(1.0));
+        clock.advance(chrono::Duration::minutes(150));
+        assert_eq!(block_remaining_fraction(&session, clock.now()), Some(0.5));
+        clock.advance(chrono::Duration::minutes(150));
+        assert_eq!(block_remaining_fraction(&session, clock.now()), Some(0.0));
+        clock.advance(chrono::Duration::hours(1));
+        assert_eq!(block_remaining_fraction(&session, clock.now()), Some(0.0));
+    }
+
+    #[test]
+    fn remaining_fraction_uses_the_block_own_length() {
+        let short = block(local(2025, 6, 11, 10, 0), local(2025, 6, 11, 11, 0), 1.0);
+        let clock = FakeClock::new(local(2025, 6, 11, 10, 45));
+        assert_eq!(block_remaining_fraction(&short, clock.now()), Some(0.25));
+    }
+
+    fn daily_history(from: chrono::NaiveDate, to: chrono::NaiveDate, cost: f64) -> Vec<(chrono::NaiveDate, f64)> {
+        from.iter_days().take_while(|date| *date <= to).map(|date| (date, cost)).collect()
+    }
+
+    #[test]
+    fn projection_follows_the_clock_into_the_next_month() {
+        let date = |month, day| chrono::NaiveDate::from_ymd_opt(2025, month, day).unwrap();
+        let history = daily_history(date(5, 1), date(7, 31), 1.0);
+        let clock = FakeClock::new(local(2025, 6, 11, 12, 0));
+
+        let projection = project_month_cost(&history, clock.now().date_naive()).unwrap();
+        assert_close(projection.total, 30.0);
+        clock.advance(chrono::Duration::days(9));
+        assert_close(project_month_cost(&history, clock.now().date_naive()).unwrap().total, 30.0);
+        // July has a day more
+        clock.advance(chrono::Duration::days(12));
+        assert_eq!(clock.now().date_naive(), date(7, 2));
+        assert_close(project_month_cost(&history, clock.now().date_naive()).unwrap().total, 31.0);
+    }
+
+    const OPUS: &str = "claude-opus-4-20250514";
+    const SONNET: &str = "claude-sonnet-4-20250514";
+
+    #[test]
+    fn cache_savings_price_each_model_from_the_breakdown() {
+        let breakdown = [(OPUS.to_string(), 1_000_000), (SONNET.to_string(), 2_000_000)];
+        let models = [OPUS.to_string(), SONNET.to_string()];
+        let savings = cache_savings(Some(&breakdown[..]), &models, 3_000_000).unwrap();
+        // Opus saves $13.50 per million cache reads, Sonnet $2.70
+        assert_close(savings.amount, 13.5 + 5.4);
+        assert!(!savings.estimated);
+    }
+
+    #[test]
+    fn cache_savings_without_a_breakdown_use_a_blended_price() {
+        let models = [OPUS.to_string(), SONNET.to_string()];
+        let savings = cache_savings(None, &models, 3_000_000).unwrap();
+        assert_close(savings.amount, (13.5 + 2.7) / 2.0 * 3.0);
+        assert!(savings.estimated);
+    }
+
+    #[test]
+    fn blended_cache_savings_ignore_unpriced_models() {
+        let models = [SONNET.to_string(), "some-other-model".to_string()];
+        let savings = cache_savings(None, &models, 1_000_000).unwrap();
+        assert_close(savings.amount, 2.7);
+        assert_eq!(cache_savings(None, &["some-other-model".to_string()], 1_000_000), None);
+    }
+
+    #[test]
+    fn exact_cache_savings_need_a_price_for_every_model() {
+        let breakdown = [(SONNET.to_string(), 1_000_000), ("some-other-model".to_string(), 1_000_000)];
+        assert_eq!(cache_savings(Some(&breakdown[..]), &[], 2_000_000), None);
+    }
+
+    #[test]
+    fn model_pricing_tells_haiku_releases_apart() {
+        assert_eq!(model_pricing("claude-3-5-haiku-20241022").map(|pricing| pricing.input), Some(0.8));
+        assert_eq!(model_pricing("claude-3-haiku-20240307").map(|pricing| pricing.input), Some(0.25));
+        assert_eq!(model_pricing("gpt-4"), None);
+    }
+
+    fn secs(values: &[u64]) -> RollingStats {
+        let mut stats = RollingStats::new();
+        for &value in values {
+            stats.push(Duration::from_secs(value));
+        }
+        stats
+    }
+
+    #[test]
+    fn rolling_stats_are_empty_without_samples() {
+        assert_eq!(RollingStats::new().mean(), None);
+        assert_eq!(RollingStats::new().p95(), None);
+    }
+
+    #[test]
+    fn rolling_mean_and_p95() {
+        let stats = secs(&[1, 2, 3, 4, 10]);
+        assert_eq!(stats.mean(), Some(Duration::from_secs(4)));
+        assert_eq!(stats.p95(), Some(Duration::from_secs(10)));
+        assert_eq!(secs(&[7]).p95(), Some(Duration::from_secs(7)));
+    }
+
+    #[test]
+    fn p95_of_twenty_samples_is_the_nineteenth() {
+        let stats = secs(&(1..=20).collect::<Vec<_>>());
+        assert_eq!(stats.p95(), Some(Duration::from_secs(19)));
+    }
+
+    #[test]
+    fn rolling_stats_keep_only_the_last_twenty() {
+        let stats = secs(&(1..=25).collect::<Vec<_>>());
+        assert_eq!(stats.samples.len(), TIMING_WINDOW);
+        assert_eq!(stats.samples.front(), Some(&Duration::from_secs(6)));
+        // (6 + ... + 25) / 20
+        assert_eq!(stats.mean(), Some(Duration::from_millis(15_500)));
+    }
+
+    fn august(day: u32) -> chrono::NaiveDate {
+        chrono::NaiveDate::from_ymd_opt(2025, 8, day).unwrap()
+    }
+
+    /// Every day of August before `today` at `weekday` or `weekend` dollars, then `today` at `today_cost`
+    fn work_history(today: u32, weekday: f64, weekend: f64, today_cost: f64) -> Vec<(chrono::NaiveDate, f64)> {
+        let mut history: Vec<(chrono::NaiveDate, f64)> = (1..today)
+            .map(august)
+            .map(|date| (date, if is_weekend(date) { weekend } else { weekday }))
+            .collect();
+        history.push((august(today), today_cost));
+        history
+    }
+
+    #[test]
+    fn projection_weights_the_remaining_calendar_mix() {
+        // August 2025 ends on a Sunday: Friday, Saturday and Sunday are left after the 28th
+        assert_eq!(august(31).weekday(), Weekday::Sun);
+        let history = work_history(28, 10.0, 2.0, 3.0);
+        let projection = project_month_cost(&history, august(28)).unwrap();
+        // 209 so far, then a weekday at $10 and two weekend days at $2
+        assert_close(projection.total, 209.0 + 10.0 + 2.0 + 2.0);
+        assert_eq!(projection.method, "workday-weighted");
+    }
+
+    #[test]
+    fn projection_adds_nothing_for_weekends_never_worked() {
+        let history: Vec<_> =
+            work_history(29, 10.0, 0.0, 4.0).into_iter().filter(|(date, _)| !is_weekend(*date)).collect();
+        let month_to_date: f64 = history.iter().map(|(_, cost)| cost).sum();
+        // Only Saturday the 30th and Sunday the 31st remain
+        let projection = project_month_cost(&history, august(29)).unwrap();
+        assert_close(projection.total, month_to_date);
+    }
+
+    #[test]
+    fn todays_partial_spend_counts_so_far_but_not_towards_the_averages() {
+        let idle = project_month_cost(&work_history(20, 10.0, 0.0, 0.0), august(20)).unwrap();
+        let busy = project_month_cost(&work_history(20, 10.0, 0.0, 50.0), august(20)).unwrap();
+        assert_close(busy.total - idle.total, 50.0);
+    }
+
+    #[test]
+    fn projection_needs_spend_in_the_window() {
+        assert_eq!(project_month_cost(&[], august(20)), None);
+        // Spend only today, or only before the window, isn't enough
+        assert_eq!(project_month_cost(&[(august(20), 5.0)], august(20)), None);
+        assert_eq!(project_month_cost(&[(august(1), 5.0)], august(20)), None);
+    }
+
+    /// Samples two minutes apart starting at 10:00, with the block cost so far
+    fn every_two_minutes(costs: &[f64]) -> Vec<(chrono::DateTime<chrono::Local>, f64)> {
+        costs.iter().enumerate().map(|(i, &cost)| (local(2025, 6, 11, 10, 2 * i as u32), cost)).collect()
+    }
+
+    #[test]
+    fn spike_detection_needs_two_samples() {
+        assert_eq!(detect_spend_spike(&[], None), None);
+        assert_eq!(detect_spend_spike(&every_two_minutes(&[5.0]), Some(0.01)), None);
+    }
+
+    #[test]
+    fn without_a_baseline_only_the_floor_applies() {
+        // $2.10 in 6 minutes is under $0.50 a minute
+        assert_eq!(detect_spend_spike(&every_two_minutes(&[1.0, 1.7, 2.4, 3.1]), None), None);
+        let spike = detect_spend_spike(&every_two_minutes(&[1.0, 3.0, 5.0, 7.0]), None).unwrap();
+        assert_close(spike.amount, 6.0);
+        assert_close(spike.minutes, 6.0);
+    }
+
+    #[test]
+    fn steady_heavy_usage_is_not_a_spike() {
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 6, 11).unwrap();
+        // $1,440 a day is $1 a minute
+        let history: Vec<_> = (1..=7).map(|days| (today - chrono::Duration::days(days), 1440.0)).collect();
+        let baseline = baseline_per_minute(&history, today);
+        assert_close(baseline.unwrap(), 1.0);
+        // $2 a minute: over the floor, but not four times the usual rate
+        assert_eq!(detect_spend_spike(&every_two_minutes(&[0.0, 4.0, 8.0, 12.0]), baseline), None);
+        // $10 a minute is
+        let spike = detect_spend_spike(&every_two_minutes(&[0.0, 20.0, 40.0, 60.0]), baseline).unwrap();
+        assert_close(spike.amount, 60.0);
+    }
+
+    #[test]
+    fn a_spike_is_measured_over_the_last_few_refreshes() {
+        let baseline = Some(0.01);
+        // A burst that's more than three refreshes old no longer counts
+        let quiet_since = every_two_minutes(&[0.0, 30.0, 30.1, 30.2, 30.3]);
+        assert_eq!(detect_spend_spike(&quiet_since, baseline), None);
+        let bursting = every_two_minutes(&[0.0, 0.1, 0.2, 0.3, 10.0]);
+        let spike = detect_spend_spike(&bursting, baseline).unwrap();
+        assert_close(spike.amount, 9.9);
+        assert_close(spike.minutes, 6.0);
+    }
+
+    #[test]
+    fn baseline_uses_the_completed_days_of_the_past_week() {
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 6, 11).unwrap();
+        assert_eq!(baseline_per_minute(&[], today), None);
+        let history = [
+            (today, 5000.0),
+            (today - chrono::Duration::days(8), 5000.0),
+            (today - chrono::Duration::days(7), 10_080.0),
+        ];
+        assert_close(baseline_per_minute(&history, today).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn block_length_comes_from_the_block_times() {
+        let short = block(local(2025, 6, 11, 10, 0), local(2025, 6, 11, 11, 0), 1.0);
+        assert_eq!(block_duration(&short), Some(chrono::Duration::hours(1)));
+        let odd = block(local(2025, 6, 11, 10, 0), local(2025, 6, 11, 13, 30), 1.0);
+        assert_eq!(block_duration(&odd), Some(chrono::Duration::minutes(210)));
+        // Halfway through the 3h30 window, not through an assumed five hours
+        assert_eq!(block_remaining_fraction(&odd, local(2025, 6, 11, 11, 45)), Some(0.5));
+    }
+
+    #[test]
+    fn empty_or_unparseable_windows_have_no_length() {
+        let empty = block(local(2025, 6, 11, 10, 0), local(2025, 6, 11, 10, 0), 1.0);
+        assert_eq!(block_duration(&empty), None);
+        let mut broken = block(local(2025, 6, 11, 10, 0), local(2025, 6, 11, 11, 0), 1.0);
+        broken.end_time = "soon".to_string();
+        assert_eq!(block_duration(&broken), None);
+        assert_eq!(block_remaining_fraction(&broken, local(2025, 6, 11, 10, 30)), None);
+    }
+
+    #[test]
+    fn sparkline_spaces_points_by_time_not_by_sample() {
+        let levels = ['_', '-', '#'];
+        // A sample a minute in, then nothing until the tenth minute
+        let samples = [
+            (local(2025, 6, 11, 10, 0), 0.0),
+            (local(2025, 6, 11, 10, 1), 5.0),
+            (local(2025, 6, 11, 10, 10), 10.0),
+        ];
+        assert_eq!(cost_sparkline(&samples, 2, &levels).as_deref(), Some("-#"));
+        assert_eq!(cost_sparkline(&samples, 5, &levels).as_deref(), Some("----#"));
+    }
+
+    #[test]
+    fn flat_sparklines_use_the_lowest_level() {
+        let samples = [(local(2025, 6, 11, 10, 0), 3.0), (local(2025, 6, 11, 10, 5), 3.0)];
+        assert_eq!(cost_sparkline(&samples, 3, &['_', '#']).as_deref(), Some("___"));
+    }
+
+    #[test]
+    fn sparklines_need_two_samples_apart_in_time() {
+        let levels = ['_', '#'];
+        assert_eq!(cost_sparkline(&[], 4, &levels), None);
+        assert_eq!(cost_sparkline(&[(local(2025, 6, 11, 10, 0), 1.0)], 4, &levels), None);
+        let same_time = [(local(2025, 6, 11, 10, 0), 1.0), (local(2025, 6, 11, 10, 0), 2.0)];
+        assert_eq!(cost_sparkline(&same_time, 4, &levels), None);
+    }
+
+    #[test]
+    fn biggest_jump_is_the_largest_increase() {
+        let samples = [
+            (local(2025, 6, 11, 10, 0), 1.0),
+            (local(2025, 6, 11, 10, 2), 1.5),
+            (local(2025, 6, 11, 10, 5), 3.0),
+            (local(2025, 6, 11, 10, 9), 2.0),
+            (local(2025, 6, 11, 10, 12), 2.2),
+        ];
+        assert_eq!(biggest_cost_jump(&samples), Some((local(2025, 6, 11, 10, 5), 1.5)));
+    }
+
+    #[test]
+    fn no_jump_without_an_increase() {
+        assert_eq!(biggest_cost_jump(&[]), None);
+        let falling = [(local(2025, 6, 11, 10, 0), 2.0), (local(2025, 6, 11, 10, 2), 1.0)];
+        assert_eq!(biggest_cost_jump(&falling), None);
+    }
+
+    fn ended(start_hour: u32, last_activity: chrono::DateTime<chrono::Local>, cost: f64) -> BlockData {
+        let mut finished = block(local(2025, 6, 11, start_hour, 0), local(2025, 6, 11, start_hour + 5, 0), cost);
+        finished.actual_end_time = Some(last_activity.to_rfc3339());
+        finished
+    }
+
+    #[test]
+    fn previous_block_is_the_last_one_to_end_before_the_active_one() {
+        let mut active = block(local(2025, 6, 11, 15, 0), local(2025, 6, 11, 20, 0), 0.4);
+        active.is_active = true;
+        let blocks = [
+            ended(6, local(2025, 6, 11, 7, 30), 3.0),
+            ended(9, local(2025, 6, 11, 13, 50), 12.0),
+            active,
+        ];
+        let previous = previous_block(&blocks, local(2025, 6, 11, 15, 20)).unwrap();
+        assert_eq!(previous.cost_usd, 12.0);
+    }
+
+    #[test]
+    fn previous_block_skips_gaps() {
+        let mut gap = block(local(2025, 6, 11, 14, 0), local(2025, 6, 11, 15, 0), 0.0);
+        gap.is_gap = true;
+        let blocks = [ended(9, local(2025, 6, 11, 13, 50), 12.0), gap];
+        assert_eq!(previous_block(&blocks, local(2025, 6, 11, 15, 20)).unwrap().cost_usd, 12.0);
+    }
+
+    #[test]
+    fn previous_block_picks_the_latest_end_not_the_latest_start() {
+        // A long early session that outlasted a short later one
+        let blocks = [ended(8, local(2025, 6, 11, 12, 40), 5.0), ended(10, local(2025, 6, 11, 11, 0), 1.0)];
+        assert_eq!(previous_block(&blocks, local(2025, 6, 11, 13, 0)).unwrap().cost_usd, 5.0);
+    }
+
+    #[test]
+    fn previous_block_must_have_ended_within_two_hours() {
+        let blocks = [ended(9, local(2025, 6, 11, 13, 0), 12.0)];
+        assert!(previous_block(&blocks, local(2025, 6, 11, 14, 59)).is_some());
+        assert!(previous_block(&blocks, local(2025, 6, 11, 15, 0)).is_none());
+        // Not ended yet, e.g. after the clock was set back
+        assert!(previous_block(&blocks, local(2025, 6, 11, 12, 0)).is_none());
+    }
+
+    #[test]
+    fn previous_block_falls_back_to_the_window_end() {
+        let blocks = [block(local(2025, 6, 11, 9, 0), local(2025, 6, 11, 14, 0), 2.0)];
+        assert!(previous_block(&blocks, local(2025, 6, 11, 15, 0)).is_some());
+        assert!(previous_block(&blocks, local(2025, 6, 11, 13, 0)).is_none());
+    }
+
+    fn minutes(minutes: i64) -> chrono::Duration {
+        chrono::Duration::minutes(minutes)
+    }
+
+    #[test]
+    fn limit_eta_follows_the_average_pace() {
+        // 1,000 tokens a minute for an hour, 30,000 to go
+        let estimate = estimate_until_limit(60_000, 90_000, minutes(60), minutes(240));
+        assert_eq!(estimate, Some(LimitEstimate::ReachedIn(minutes(30))));
+    }
+
+    #[test]
+    fn limits_already_reached_are_exceeded() {
+        assert_eq!(estimate_until_limit(90_000, 90_000, minutes(60), minutes(240)), Some(LimitEstimate::Exceeded));
+        // Even during the warm-up
+        assert_eq!(estimate_until_limit(100_000, 90_000, minutes(1), minutes(299)), Some(LimitEstimate::Exceeded));
+    }
+
+    #[test]
+    fn no_limit_eta_during_the_warm_up() {
+        let just_before = minutes(BURN_RATE_WARMUP_MINUTES) - chrono::Duration::seconds(1);
+        assert_eq!(estimate_until_limit(50_000, 90_000, just_before, minutes(290)), None);
+        assert!(estimate_until_limit(50_000, 90_000, minutes(BURN_RATE_WARMUP_MINUTES), minutes(290)).is_some());
+    }
+
+    #[test]
+    fn no_limit_eta_without_usage() {
+        assert_eq!(estimate_until_limit(0, 90_000, minutes(60), minutes(240)), None);
+        // Under a token a minute counts as idle
+        assert_eq!(estimate_until_limit(59, 90_000, minutes(60), minutes(240)), None);
+    }
+
+    #[test]
+    fn no_limit_eta_past_the_end_of_the_block() {
+        // 30 minutes to go at this pace, but the block ends in 20
+        assert_eq!(estimate_until_limit(60_000, 90_000, minutes(60), minutes(20)), None);
+        assert!(estimate_until_limit(60_000, 90_000, minutes(60), minutes(30)).is_some());
+    }
+
+    #[test]
+    fn a_fast_pace_reaches_the_limit_in_seconds() {
+        // About 10,000 tokens a minute with 500 to go
+        let estimate = estimate_until_limit(199_500, 200_000, minutes(20), minutes(280));
+        assert_eq!(estimate, Some(LimitEstimate::ReachedIn(chrono::Duration::seconds(3))));
+    }
+
+    #[test]
+    fn block_limit_estimate_measures_from_the_block_start() {
+        let mut active = block(local(2025, 6, 11, 10, 0), local(2025, 6, 11, 15, 0), 1.0);
+        active.token_counts.output_tokens = 60_000;
+        let estimate = block_limit_estimate(&active, 90_000, local(2025, 6, 11, 11, 0));
+        assert_eq!(estimate, Some(LimitEstimate::ReachedIn(minutes(30))));
+    }
+
+    #[test]
+    fn percent_shares_add_up_to_100() {
+        let splits: [&[f64]; 9] = [
+            &[1.0, 1.0],
+            &[2.0, 0.0],
+            &[0.01, 9.99],
+            &[1.0, 1.0, 1.0],
+            &[2.0, 1.0, 0.0],
+            &[0.1, 0.2, 0.7],
+            &[1.0, 1.0, 1.0, 1.0, 1.0],
+            &[3.0, 1.0, 1.0, 1.0, 0.0],
+            &[12.34, 0.0, 5.67, 0.89, 0.01],
+        ];
+        for values in splits {
+            let shares = percent_shares(values);
+            assert_eq!(shares.iter().sum::<u32>(), 100, "{:?} gave {:?}", values, shares);
+            for (value, share) in values.iter().zip(&shares) {
+                if *value == 0.0 {
+                    assert_eq!(*share, 0, "{:?} gave {:?}", values, shares);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn leftover_points_go_to_the_largest_remainders() {
+        assert_eq!(percent_shares(&[1.0, 1.0]), [50, 50]);
+        assert_eq!(percent_shares(&[2.0, 1.0, 0.0]), [67, 33, 0]);
+        // Equal remainders go to the model listed first
+        assert_eq!(percent_shares(&[1.0, 1.0, 1.0]), [34, 33, 33]);
+        assert_eq!(percent_shares(&[3.0, 1.0, 1.0, 1.0, 0.0]), [50, 17, 17, 16, 0]);
+        // 0.1 + 0.2 + 0.7 isn't exactly 1, which mustn't cost a point
+        assert_eq!(percent_shares(&[0.1, 0.2, 0.7]), [10, 20, 70]);
+    }
+
+    #[test]
+    fn percent_shares_of_nothing_are_zero() {
+        assert_eq!(percent_shares(&[0.0, 0.0, 0.0]), [0, 0, 0]);
+        assert!(percent_shares(&[]).is_empty());
+        // Negative costs count as nothing
+        assert_eq!(percent_shares(&[-1.0, 0.0]), [0, 0]);
+        assert_eq!(percent_shares(&[-1.0, 2.0]), [0, 100]);
+    }
+
+    #[test]
+    fn heatmap_levels_of_empty_days_are_zero() {
+        assert_eq!(heatmap_levels(&[0.0, 0.0, 0.0]), [0, 0, 0]);
+        assert_eq!(heatmap_levels(&[-1.0, 0.0]), [0, 0]);
+        assert!(heatmap_levels(&[]).is_empty());
+    }
+
+    #[test]
+    fn a_single_day_with_spend_is_level_4() {
+        assert_eq!(heatmap_levels(&[0.0, 0.0, 5.0, 0.0]), [0, 0, 4, 0]);
+        assert_eq!(heatmap_levels(&[0.01]), [4]);
+    }
+
+    #[test]
+    fn uniform_spend_is_level_4_every_day() {
+        assert_eq!(heatmap_levels(&[2.0, 2.0, 2.0, 2.0]), [4, 4, 4, 4]);
+        // Days without spend stay empty
+        assert_eq!(heatmap_levels(&[0.0, 3.0, 3.0, 0.0, 3.0]), [0, 4, 4, 0, 4]);
+    }
+
+    #[test]
+    fn heatmap_levels_follow_the_quartiles_of_spending_days() {
+        let days = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0];
+        assert_eq!(heatmap_levels(&days), [1, 1, 2, 2, 3, 3, 4, 4]);
+        // Zeros don't count towards the quartiles
+        assert_eq!(heatmap_levels(&[0.0, 0.0, 0.0, 1.0, 2.0, 3.0, 4.0]), [0, 0, 0, 1, 2, 3, 4]);
+        // Ties take the higher level
+        assert_eq!(heatmap_levels(&[1.0, 1.0, 1.0, 9.0]), [3, 3, 3, 4]);
+    }
+
+    /// Wall-clock June 2025 time; the 11th is a Wednesday
+    fn at(day: u32, hour: u32, minute: u32) -> chrono::NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2025, 6, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn window(days: &[Weekday], start: &str, end: &str) -> CountingWindow {
+        CountingWindow { days: days.to_vec(), start: start.to_string(), end: end.to_string() }
+    }
+
+    #[test]
+    fn overlap_with_a_daytime_window() {
+        let work_hours = CountingWindow::default();
+        let cases = [
+            ("before opening", at(11, 7, 0), at(11, 8, 0), 0),
+            ("after closing", at(11, 19, 0), at(11, 20, 0), 0),
+            ("ending as it opens", at(11, 8, 0), at(11, 9, 0), 0),
+            ("starting as it closes", at(11, 18, 0), at(11, 19, 0), 0),
+            ("inside", at(11, 10, 0), at(11, 12, 0), 2 * 3600),
+            ("covering the whole day", at(11, 8, 0), at(11, 19, 0), 9 * 3600),
+            ("straddling opening", at(11, 8, 30), at(11, 9, 30), 1800),
+            ("straddling closing", at(11, 17, 30), at(11, 18, 30), 1800),
+            ("overnight between workdays", at(11, 17, 0), at(12, 10, 0), 2 * 3600),
+            ("on a Saturday", at(14, 10, 0), at(14, 12, 0), 0),
+            ("over the weekend", at(13, 17, 0), at(16, 10, 0), 2 * 3600),
+            ("empty", at(11, 10, 0), at(11, 10, 0), 0),
+            ("backwards", at(11, 12, 0), at(11, 10, 0), 0),
+        ];
+        for (case, from, to, expected) in cases {
+            assert_eq!(work_hours.overlap_seconds(from, to), expected, "{}", case);
+        }
+    }
+
+    #[test]
+    fn overlap_with_a_window_crossing_midnight() {
+        let night_shift = window(&[Weekday::Wed], "22:00", "06:00");
+        let cases = [
+            ("across midnight", at(11, 23, 0), at(12, 1, 0), 2 * 3600),
+            ("after midnight, opened the evening before", at(12, 5, 0), at(12, 7, 0), 3600),
+            ("starting as it closes", at(12, 6, 0), at(12, 7, 0), 0),
+            ("covering the whole night", at(11, 21, 0), at(12, 7, 0), 8 * 3600),
+            ("on a night it doesn't open", at(12, 23, 0), at(13, 1, 0), 0),
+            ("before it opens that evening", at(11, 5, 0), at(11, 7, 0), 0),
+        ];
+        for (case, from, to, expected) in cases {
+            assert_eq!(night_shift.overlap_seconds(from, to), expected, "{}", case);
+        }
+
+        // Opening and closing at the same time runs for a full day
+        let all_day = window(&[Weekday::Wed], "09:00", "09:00");
+        assert_eq!(all_day.overlap_seconds(at(11, 8, 0), at(11, 10, 0)), 3600);
+        assert_eq!(all_day.overlap_seconds(at(12, 8, 0), at(12, 10, 0)), 3600);
+    }
+
+    #[test]
+    fn unparseable_windows_cover_nothing() {
+        for (start, end) in [("9am", "18:00"), ("09:00", ""), ("25:00", "18:00")] {
+            let broken = window(&[Weekday::Wed], start, end);
+            assert_eq!(broken.overlap_seconds(at(11, 0, 0), at(12, 0, 0)), 0, "{}-{}", start, end);
+        }
+        assert_eq!(window(&[], "09:00", "18:00").overlap_seconds(at(11, 0, 0), at(12, 0, 0)), 0);
+    }
+
+    #[test]
+    fn block_fraction_covers_its_active_span() {
+        let work_hours = CountingWindow::default();
+        let straddling = block(local(2025, 6, 11, 8, 0), local(2025, 6, 11, 10, 0), 4.0);
+        assert_close(block_window_fraction(&straddling, &work_hours), 0.5);
+
+        // Only up to the last activity, not the end of the five-hour window
+        let mut finished_early = block(local(2025, 6, 11, 17, 0), local(2025, 6, 11, 22, 0), 4.0);
+        finished_early.actual_end_time = Some(local(2025, 6, 11, 19, 0).to_rfc3339());
+        assert_close(block_window_fraction(&finished_early, &work_hours), 0.5);
+
+        let inside = block(local(2025, 6, 11, 10, 0), local(2025, 6, 11, 12, 0), 4.0);
+        assert_close(block_window_fraction(&inside, &work_hours), 1.0);
+        let saturday = block(local(2025, 6, 14, 10, 0), local(2025, 6, 14, 12, 0), 4.0);
+        assert_close(block_window_fraction(&saturday, &work_hours), 0.0);
+    }
+
+    #[test]
+    fn single_instant_blocks_count_fully_or_not_at_all() {
+        let work_hours = CountingWindow::default();
+        let during = block(local(2025, 6, 11, 10, 0), local(2025, 6, 11, 10, 0), 1.0);
+        assert_close(block_window_fraction(&during, &work_hours), 1.0);
+        let at_closing = block(local(2025, 6, 11, 18, 0), local(2025, 6, 11, 18, 0), 1.0);
+        assert_close(block_window_fraction(&at_closing, &work_hours), 0.0);
+
+        let mut unparseable = block(local(2025, 6, 11, 10, 0), local(2025, 6, 11, 12, 0), 1.0);
+        unparseable.start_time = "not a time".to_string();
+        assert_close(block_window_fraction(&unparseable, &work_hours), 0.0);
+    }
+
+    #[test]
+    fn window_totals_skip_gaps_and_optionally_the_active_block() {
+        let work_hours = CountingWindow::default();
+        let mut gap = block(local(2025, 6, 11, 12, 0), local(2025, 6, 11, 13, 0), 7.0);
+        gap.is_gap = true;
+        let mut active = block(local(2025, 6, 11, 14, 0), local(2025, 6, 11, 15, 0), 3.0);
+        active.is_active = true;
+        let blocks = [
+            block(local(2025, 6, 11, 8, 0), local(2025, 6, 11, 10, 0), 4.0),
+            block(local(2025, 6, 14, 10, 0), local(2025, 6, 14, 12, 0), 5.0),
+            gap,
+            active,
+        ];
+        assert_close(total_cost_in_window(&blocks, false, &work_hours), 2.0);
+        assert_close(total_cost_in_window(&blocks, true, &work_hours), 5.0);
+    }
+}
+
@@ -0,0 +1,217 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::PathBuf;
+
+use crate::datadirs::expand_home;
+use crate::model::BlockData;
+use crate::state::{write_atomically, AppSettings, Availability, TrayDisplay};
+
+/// Bumped whenever a field of `StatusFile` is renamed or removed, and for
+/// fields readers need to check for: version 2 added the rendered title
+pub(crate) const STATUS_FILE_VERSION: u32 = 2;
+
+/// Where the status file goes unless `status_file_path` says otherwise
+const DEFAULT_STATUS_PATH: &str = "~/.cache/ccusage-menubar/status.json";
+
+/// The tray title as the user saw it, so other tools can show the same text
+/// without redoing the app's formatting
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct RenderedTitle {
+    /// The menubar title exactly as shown, empty when it shows only the icon
+    pub(crate) rendered_title: String,
+    /// `tray_display` at the time
+    pub(crate) display_mode: TrayDisplay,
+    /// The session cost as the title formats it, whichever mode is shown;
+    /// `null` without a session
+    pub(crate) formatted_cost: Option<String>,
+}
+
+/// Small JSON summary written after every refresh for tools like sketchybar
+/// and xbar that would rather read a file than run ccusage themselves
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct StatusFile {
+    pub(crate) version: u32,
+    pub(crate) availability: Availability,
+    /// Unix time of the refresh that produced this file
+    pub(crate) last_updated: i64,
+    /// The active session, `null` when there is none
+    pub(crate) session: Option<StatusSession>,
+    /// Missing from version 1 files
+    #[serde(flatten)]
+    pub(crate) title: RenderedTitle,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct StatusSession {
+    pub(crate) cost_usd: f64,
+    pub(crate) input_tokens: u64,
+    pub(crate) output_tokens: u64,
+    pub(crate) cache_creation_tokens: u64,
+    pub(crate) cache_read_tokens: u64,
+    pub(crate) total_tokens: u64,
+    /// RFC 3339 block window, as reported by ccusage
+    pub(crate) block_start: String,
+    pub(crate) block_end: String,
+}
+
+impl StatusFile {
+    pub(crate) fn new(
+        block: Option<&BlockData>,
+        availability: Availability,
+        last_updated: i64,
+        title: RenderedTitle,
+    ) -> Self {
+        Self {
+            version: STATUS_FILE_VERSION,
+            availability,
+            last_updated,
+            session: block.map(|block| StatusSession {
+                cost_usd: block.cost_usd,
+                input_tokens: block.token_counts.input_tokens,
+                output_tokens: block.token_counts.output_tokens,
+                cache_creation_tokens: block.token_counts.cache_creation_input_tokens,
+                cache_read_tokens: block.token_counts.cache_read_input_tokens,
+                total_tokens: block.token_counts.total(),
+                block_start: block.start_time.clone(),
+                block_end: block.end_time.clone(),
+            }),
+            title,
+        }
+    }
+}
+
+/// The configured status file location, or the default one
+pub(crate) fn status_file_path(settings: &AppSettings) -> PathBuf {
+    let path = settings.status_file_path.as_deref().filter(|path| !path.trim().is_empty());
+    expand_home(path.unwrap_or(DEFAULT_STATUS_PATH))
+}
+
+/// Write the status file if enabled
+pub(crate) fn write_status_file(settings: &AppSettings, status: &StatusFile) {
+    if !settings.write_status_file {
+        return;
+    }
+    let path = status_file_path(settings);
+    let result = serde_json::to_string_pretty(status)
+        .map_err(io::Error::other)
+        .and_then(|json| write_atomically(&path, &json));
+    if let Err(e) = result {
+        eprintln!("Failed to write status file {}: {}", path.display(), e);
+    }
+}
+
+/// Delete the status file so other tools don't keep showing stale data after
+/// the app quits or the setting is turned off
+pub(crate) fn remove_status_file(settings: &AppSettings) {
+    let _ = std::fs::remove_file(status_file_path(settings));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::fixtures::{block, local};
+    use crate::state::TestDir;
+
+    fn sample_status() -> StatusFile {
+        let mut session = block(local(2025, 6, 11, 9, 0), local(2025, 6, 11, 14, 0), 12.4);
+        session.token_counts.output_tokens = 500;
+        session.token_counts.cache_read_input_tokens = 9_000;
+        StatusFile::new(Some(&session), Availability::Available, 1_749_632_400, RenderedTitle::default())
+    }
+
+    #[test]
+    fn status_file_round_trips() {
+        let status = sample_status();
+        let json = serde_json::to_string(&status).unwrap();
+        assert_eq!(serde_json::from_str::<StatusFile>(&json).unwrap(), status);
+
+        let idle = StatusFile::new(None, Availability::NotFound, 0, RenderedTitle::default());
+        let json = serde_json::to_string(&idle).unwrap();
+        assert_eq!(serde_json::from_str::<StatusFile>(&json).unwrap(), idle);
+    }
+
+    #[test]
+    fn status_file_schema_field_names() {
+        let json = serde_json::to_value(sample_status()).unwrap();
+        assert_eq!(json["version"], STATUS_FILE_VERSION);
+        assert_eq!(json["availability"], "available");
+        assert_eq!(json["last_updated"], 1_749_632_400);
+        assert_eq!(json["session"]["cost_usd"], 12.4);
+        assert_eq!(json["session"]["total_tokens"], 9_500);
+        assert_eq!(json["session"]["cache_read_tokens"], 9_000);
+        assert!(json["session"]["block_start"].is_string());
+    }
+
+    #[test]
+    fn status_file_is_written_whole_and_removed() {
+        let dir = TestDir::new("status");
+        let path = dir.path().join("nested").join("status.json");
+        let settings = AppSettings {
+            write_status_file: true,
+            status_file_path: Some(path.display().to_string()),
+            ..AppSettings::default()
+        };
+        write_status_file(&settings, &sample_status());
+        let written: StatusFile = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written, sample_status());
+        // Only the file itself is left, no temporary files beside it
+        assert_eq!(std::fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+
+        remove_status_file(&settings);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn status_file_is_not_written_when_off() {
+        let dir = TestDir::new("status-off");
+        let path = dir.path().join("status.json");
+        let settings = AppSettings { status_file_path: Some(path.display().to_string()), ..AppSettings::default() };
+        write_status_file(&settings, &sample_status());
+        assert!(!path.exists());
+    }
+
+    fn shown_title() -> RenderedTitle {
+        RenderedTitle {
+            rendered_title: "$12.40".to_string(),
+            display_mode: TrayDisplay::TokensCache,
+            formatted_cost: Some("$12.40".to_string()),
+        }
+    }
+
+    #[test]
+    fn status_file_carries_the_title_as_shown() {
+        let status = StatusFile { title: shown_title(), ..sample_status() };
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["version"], 2);
+        assert_eq!(json["rendered_title"], "$12.40");
+        assert_eq!(json["display_mode"], "tokens+cache");
+        assert_eq!(json["formatted_cost"], "$12.40");
+        assert_eq!(serde_json::from_value::<StatusFile>(json).unwrap(), status);
+
+        // Without a session the cost is null rather than missing
+        let idle = StatusFile::new(None, Availability::Available, 0, RenderedTitle::default());
+        let json = serde_json::to_value(idle).unwrap();
+        assert!(json["formatted_cost"].is_null());
+        assert_eq!(json["display_mode"], "cost");
+    }
+
+    #[test]
+    fn version_1_status_files_still_load() {
+        let json = r#"{
+            "version": 1,
+            "availability": "available",
+            "last_updated": 1749632400,
+            "session": {
+                "cost_usd": 12.4, "input_tokens": 0, "output_tokens": 500,
+                "cache_creation_tokens": 0, "cache_read_tokens": 9000, "total_tokens": 9500,
+                "block_start": "2025-06-11T09:00:00+02:00", "block_end": "2025-06-11T14:00:00+02:00"
+            }
+        }"#;
+        let status: StatusFile = serde_json::from_str(json).unwrap();
+        assert_eq!(status.version, 1);
+        assert_eq!(status.session.unwrap().total_tokens, 9_500);
+        assert_eq!(status.title, RenderedTitle::default());
+    }
+}
+
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+use crate::clock::drop_future_samples;
+use crate::model::BlockData;
+use crate::state::{cache_dir, record_write_result, CacheFile};
+
+/// Most samples kept per block, enough for a 5-hour block at the fastest refresh rate
+const MAX_SAMPLES: usize = 300;
+
+/// The active block's cost as sampled at each refresh, persisted so the
+/// timeline survives an app restart within the same block
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct CostTimeline {
+    pub(crate) block_id: String,
+    /// (time, block cost so far), oldest first
+    pub(crate) samples: Vec<(chrono::DateTime<chrono::Local>, f64)>,
+}
+
+static COST_TIMELINE: Mutex<Option<CostTimeline>> = Mutex::new(None);
+
+fn load_timeline() -> Option<CostTimeline> {
+    let contents = std::fs::read_to_string(CacheFile::Timeline.path()?).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+fn save_timeline(timeline: &CostTimeline) {
+    let Some(dir) = cache_dir() else {
+        return;
+    };
+    let Ok(json) = serde_json::to_string(timeline) else {
+        return;
+    };
+    let result =
+        std::fs::create_dir_all(&dir).and_then(|()| std::fs::write(dir.join(CacheFile::Timeline.file_name()), json));
+    record_write_result(&dir, &result);
+    if let Err(e) = result {
+        eprintln!("Failed to save cost timeline: {}", e);
+    }
+}
+
+/// Add the active block's current cost to its timeline, starting a new one
+/// when the block changes and picking up saved samples after a restart
+pub(crate) fn record_cost_sample(block: Option<&BlockData>, now: chrono::DateTime<chrono::Local>) {
+    let Some(block) = block else {
+        return;
+    };
+    let mut current = COST_TIMELINE.lock().unwrap();
+    let timeline = with_sample(current.take().or_else(load_timeline), block, now);
+    save_timeline(&timeline);
+    *current = Some(timeline);
+}
+
+/// `previous` with `block`'s cost at `now` added, or a new timeline if
+/// `previous` belongs to another block
+fn with_sample(
+    previous: Option<CostTimeline>,
+    block: &BlockData,
+    now: chrono::DateTime<chrono::Local>,
+) -> CostTimeline {
+    let mut timeline = previous
+        .filter(|timeline| timeline.block_id == block.id)
+        .unwrap_or_else(|| CostTimeline { block_id: block.id.clone(), samples: Vec::new() });
+    drop_future_samples(&mut timeline.samples, now);
+    timeline.samples.push((now, block.cost_usd));
+    let excess = timeline.samples.len().saturating_sub(MAX_SAMPLES);
+    timeline.samples.drain(..excess);
+    timeline
+}
+
+/// Samples recorded for `block_id`, empty if the timeline belongs to another block
+pub(crate) fn cost_samples(block_id: &str) -> Vec<(chrono::DateTime<chrono::Local>, f64)> {
+    COST_TIMELINE
+        .lock()
+        .unwrap()
+        .as_ref()
+        .filter(|timeline| timeline.block_id == block_id)
+        .map(|timeline| timeline.samples.clone())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::fixtures::{block, local};
+
+    fn session(cost: f64) -> BlockData {
+        block(local(2025, 6, 11, 10, 0), local(2025, 6, 11, 15, 0), cost)
+    }
+
+    #[test]
+    fn samples_accumulate_for_the_same_block() {
+        let timeline = with_sample(None, &session(1.0), local(2025, 6, 11, 10, 5));
+        let timeline = with_sample(Some(timeline), &session(2.5), local(2025, 6, 11, 10, 7));
+        assert_eq!(timeline.samples, [(local(2025, 6, 11, 10, 5), 1.0), (local(2025, 6, 11, 10, 7), 2.5)]);
+    }
+
+    #[test]
+    fn a_new_block_starts_a_new_timeline() {
+        let timeline = with_sample(None, &session(4.0), local(2025, 6, 11, 14, 50));
+        let next = block(local(2025, 6, 11, 15, 0), local(2025, 6, 11, 20, 0), 0.2);
+        let timeline = with_sample(Some(timeline), &next, local(2025, 6, 11, 15, 5));
+        assert_eq!(timeline.block_id, next.id);
+        assert_eq!(timeline.samples, [(local(2025, 6, 11, 15, 5), 0.2)]);
+    }
+
+    #[test]
+    fn saved_samples_carry_over_a_restart() {
+        let saved = with_sample(None, &session(1.0), local(2025, 6, 11, 10, 5));
+        let reloaded: CostTimeline = serde_json::from_str(&serde_json::to_string(&saved).unwrap()).unwrap();
+        let timeline = with_sample(Some(reloaded), &session(3.0), local(2025, 6, 11, 11, 0));
+        assert_eq!(timeline.samples.len(), 2);
+    }
+
+    #[test]
+    fn samples_after_a_clock_set_back_are_dropped() {
+        let timeline = with_sample(None, &session(1.0), local(2025, 6, 11, 12, 0));
+        let timeline = with_sample(Some(timeline), &session(1.5), local(2025, 6, 11, 11, 0));
+        assert_eq!(timeline.samples, [(local(2025, 6, 11, 11, 0), 1.5)]);
+    }
+
+    #[test]
+    fn timelines_are_capped() {
+        let mut timeline = None;
+        let start = local(2025, 6, 11, 10, 0);
+        for i in 0..MAX_SAMPLES + 5 {
+            timeline = Some(with_sample(timeline, &session(i as f64), start + chrono::Duration::seconds(i as i64)));
+        }
+        let timeline = timeline.unwrap();
+        assert_eq!(timeline.samples.len(), MAX_SAMPLES);
+        assert_eq!(timeline.samples[0].1, 5.0);
+    }
+}
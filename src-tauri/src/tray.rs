@@ -0,0 +1,586 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::menu::{Menu, MenuEvent, MenuItem};
+use tauri::tray::{MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent};
+use tauri::AppHandle;
+
+use crate::alerts::{acknowledge_alerts, has_unacknowledged_alert};
+use crate::budget::{check_daily_cap, spend_on_day};
+use crate::clock::{Clock, CLOCK};
+use crate::format::{
+    accessibility_label, emphasize_title, format_model_name, format_title_cost, render_token_title, render_tray_title,
+    totals_tooltip,
+};
+use crate::glance::record_menu_open;
+use crate::icon::{default_icon, hourglass_icon, is_blank, with_badge};
+use crate::menu::{menu_spec, MenuGate, MenuIdKind, MenuIds, MenuState, RenderedMenu};
+use crate::model::BlockData;
+use crate::preview::{preview_state, PREVIEW};
+use crate::state::{current_settings, AppSettings, TrayDisplay, SESSION_CACHE};
+use crate::stats::{block_remaining_fraction, select_tray_model};
+use crate::status::RenderedTitle;
+use crate::watchdog::refresh_stalled;
+
+/// Set while the tray icon can't be shown, so the title carries a text label
+/// instead and the item stays visible and clickable
+static ICON_FAILED: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn tray_icon_failed() -> bool {
+    ICON_FAILED.load(Ordering::Relaxed)
+}
+
+/// Title for a tray item without an icon, "CC $12.40", or just "CC" when idle
+fn text_only_title(title: &str) -> String {
+    if title.is_empty() {
+        "CC".to_string()
+    } else {
+        format!("CC {}", title)
+    }
+}
+
+/// Title marked as out of date after refreshes have failed for a while, "⚠ $12.40"
+fn stalled_title(title: &str) -> String {
+    if title.is_empty() {
+        "⚠".to_string()
+    } else {
+        format!("⚠ {}", title)
+    }
+}
+
+/// Title shown next to the tray icon: the session cost, or nothing when idle
+/// or when the hourglass icon stands in for it, and "cap!" while today's spend
+/// is over the daily cap. Focus mode always shows it, in bold. After refreshes
+/// have stalled it's marked as out of date, and without an icon it's labelled
+/// so the item never goes blank.
+pub(crate) fn tray_title_from_cache() -> String {
+    marked_title(icon_title_from_cache(), refresh_stalled(), tray_icon_failed())
+}
+
+/// `title` marked as out of date when refreshes have stalled, then labelled
+/// when there's no icon, "CC ⚠ $12.40"
+fn marked_title(title: String, stalled: bool, icon_failed: bool) -> String {
+    let title = if stalled { stalled_title(&title) } else { title };
+    if icon_failed {
+        text_only_title(&title)
+    } else {
+        title
+    }
+}
+
+/// Whether today's spend, as the menu totals it, is over `daily_cost_cap` right now
+fn over_daily_cap(settings: &AppSettings) -> bool {
+    let today = CLOCK.now().date_naive();
+    let spent = spend_on_day(&SESSION_CACHE.lock().unwrap().today_blocks, today, settings);
+    check_daily_cap(spent, settings.daily_cost_cap, today, None).over
+}
+
+fn icon_title_from_cache() -> String {
+    let settings = current_settings();
+    if over_daily_cap(&settings) {
+        return "cap!".to_string();
+    }
+    if settings.hourglass_icon && !settings.focus_mode {
+        return String::new();
+    }
+    let cache = SESSION_CACHE.lock().unwrap();
+    let title = cache.active_block.as_ref()
+        .map(|block| {
+            if settings.tray_display == TrayDisplay::TokensCache {
+                let tokens = &block.token_counts;
+                return render_token_title(
+                    tokens.output_tokens,
+                    tokens.cache_read_input_tokens,
+                    settings.tray_title_glyphs,
+                    settings.tray_title_max_width,
+                );
+            }
+            let model = select_tray_model(block, settings.tray_model_selection)
+                .map(format_model_name)
+                .unwrap_or_default();
+            render_tray_title(&settings.tray_title_format, &format_title_cost(block.cost_usd, &settings), &model)
+        })
+        .unwrap_or_default();
+    if settings.focus_mode {
+        emphasize_title(&title)
+    } else {
+        title
+    }
+}
+
+/// The title together with the display mode and the session cost formatted
+/// as the title would, for the status file and the session snapshot
+pub(crate) fn rendered_title_from_cache() -> RenderedTitle {
+    let settings = current_settings();
+    let cost = SESSION_CACHE.lock().unwrap().active_block.as_ref().map(|block| block.cost_usd);
+    RenderedTitle {
+        rendered_title: tray_title_from_cache(),
+        display_mode: settings.tray_display,
+        formatted_cost: cost.map(|cost| format_title_cost(cost, &settings)),
+    }
+}
+
+/// The bar-chart icon, or in hourglass mode one drained by the elapsed part of
+/// the block, with a dot while an alert hasn't been seen or today's spend is
+/// over the daily cap
+fn tray_icon_from_cache() -> tauri::image::Image<'static> {
+    let settings = current_settings();
+    let icon = if settings.hourglass_icon {
+        let remaining = SESSION_CACHE.lock().unwrap().active_block.as_ref()
+            .map(|block| block_remaining_fraction(block, CLOCK.now()).unwrap_or(0.0));
+        hourglass_icon(remaining)
+    } else {
+        default_icon()
+    };
+    if has_unacknowledged_alert() || over_daily_cap(&settings) {
+        with_badge(icon)
+    } else {
+        icon
+    }
+}
+
+/// Redraw just the icon, for the hourglass ticking down between refreshes
+pub(crate) fn update_tray_icon(app_handle: &AppHandle) {
+    if let Some(tray) = app_handle.tray_by_id("main") {
+        if apply_tray_icon(&tray) {
+            let _ = tray.set_title(Some(tray_title_from_cache()));
+        }
+    }
+}
+
+/// Note whether the icon could be shown, logging when that changes. Returns
+/// whether it changed, in which case the title needs redrawing.
+fn record_icon_result(result: &Result<(), String>) -> bool {
+    let failed = result.is_err();
+    if ICON_FAILED.swap(failed, Ordering::Relaxed) == failed {
+        return false;
+    }
+    match result {
+        Err(e) => eprintln!("Tray icon couldn't be shown, falling back to a text title: {}", e),
+        Ok(()) => eprintln!("Tray icon is showing again"),
+    }
+    true
+}
+
+/// Show the current icon, or none when it's blank or the system rejects it,
+/// leaving the text title to mark the item. Returns whether that changed.
+fn apply_tray_icon(tray: &TrayIcon) -> bool {
+    let icon = tray_icon_from_cache();
+    let result = if is_blank(&icon) {
+        Err("the icon has no visible pixels".to_string())
+    } else {
+        tray.set_icon(Some(icon)).map_err(|e| e.to_string())
+    };
+    if result.is_err() {
+        let _ = tray.set_icon(None);
+    }
+    record_icon_result(&result)
+}
+
+/// Tray tooltip, which doubles as the accessibility label read by VoiceOver
+pub(crate) fn tray_tooltip_from_cache() -> String {
+    let cost = SESSION_CACHE.lock().unwrap().active_block.as_ref().map(|block| block.cost_usd);
+    format!(
+        "{}. {}",
+        accessibility_label(cost),
+        totals_tooltip(current_settings().include_active_in_totals)
+    )
+}
+
+/// The menu currently attached to the tray. Only locked on the main thread,
+/// see `on_main_thread`.
+static RENDERED_MENU: Mutex<Option<RenderedMenu>> = Mutex::new(None);
+
+/// The ids allocated for the attached menu's dynamic items. Kept apart from
+/// `RENDERED_MENU` and never held across a menu call, so click handlers can
+/// look up a payload while a menu update is in progress.
+static MENU_IDS: Mutex<Option<MenuIds>> = Mutex::new(None);
+
+/// Whether the menu is open, holding back changes that would dismiss it
+static MENU_GATE: Mutex<MenuGate> = Mutex::new(MenuGate::new());
+
+/// What the menu should show: live cached data, or a canned preview
+fn current_menu_state() -> MenuState {
+    let preview = *PREVIEW.lock().unwrap();
+    match preview {
+        Some(scenario) => preview_state(scenario, CLOCK.now(), current_settings()),
+        None => MenuState::from_cache(&CLOCK),
+    }
+}
+
+/// What the attached menu's item `id` was allocated for, for click handlers
+/// of items built from dynamic content
+pub(crate) fn menu_item_payload(id: &str) -> Option<(MenuIdKind, String)> {
+    let ids = MENU_IDS.lock().unwrap();
+    let (kind, value) = ids.as_ref()?.payload(id)?;
+    Some((kind, value.to_string()))
+}
+
+/// Make `rendered` the menu click handlers and updates refer to
+fn keep_rendered(rendered: RenderedMenu) {
+    *MENU_IDS.lock().unwrap() = Some(rendered.ids().clone());
+    *RENDERED_MENU.lock().unwrap() = Some(rendered);
+}
+
+fn menu_from_cache(app_handle: &AppHandle) -> tauri::Result<RenderedMenu> {
+    RenderedMenu::build(app_handle, &menu_spec(&current_menu_state()))
+}
+
+/// Run `build`, trying once more if it fails
+fn retry_once<T, E: std::fmt::Display>(mut build: impl FnMut() -> Result<T, E>) -> Result<T, String> {
+    match build() {
+        Ok(built) => Ok(built),
+        Err(e) => {
+            eprintln!("Failed to rebuild menu, retrying: {}", e);
+            build().map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// Build a replacement menu, retrying once, and hand it to `install`. When
+/// both attempts fail nothing is installed, so the previous menu and the data
+/// it shows stay up.
+fn rebuild_menu<T, E: std::fmt::Display>(
+    build: impl FnMut() -> Result<T, E>,
+    install: impl FnOnce(T),
+) -> Result<(), String> {
+    install(retry_once(build)?);
+    Ok(())
+}
+
+/// Apply the current state to the attached menu, changing only the rows that
+/// differ. Rows aren't added or removed while the menu is open. Returns false
+/// when there's no menu yet or updating failed, so it has to be rebuilt.
+fn update_menu_in_place(app_handle: &AppHandle, structural: bool) -> bool {
+    let spec = menu_spec(&current_menu_state());
+    let mut rendered = RENDERED_MENU.lock().unwrap();
+    let Some(current) = rendered.as_mut() else {
+        return false;
+    };
+    match current.update_to(app_handle, &spec, structural) {
+        Ok(()) => {
+            *MENU_IDS.lock().unwrap() = Some(current.ids().clone());
+            true
+        }
+        Err(e) => {
+            eprintln!("Failed to update menu items, rebuilding: {}", e);
+            false
+        }
+    }
+}
+
+/// Run `f` on the main thread. Menu calls made from any other thread wait for
+/// the main thread to carry them out, so doing menu work there while holding
+/// `RENDERED_MENU` would deadlock against a click that needs the same lock.
+fn on_main_thread(app_handle: &AppHandle, f: impl FnOnce(&AppHandle) + Send + 'static) {
+    let handle = app_handle.clone();
+    if let Err(e) = app_handle.run_on_main_thread(move || f(&handle)) {
+        eprintln!("Failed to schedule a tray update: {}", e);
+    }
+}
+
+/// Update the menu's text in place without restructuring it or touching the
+/// title, for the ticking "Updating" row during a refresh
+pub(crate) fn update_menu_text(app_handle: &AppHandle) {
+    on_main_thread(app_handle, |app_handle| {
+        update_menu_in_place(app_handle, false);
+    });
+}
+
+/// Re-render the tray title, tooltip and menu from cached data, on the main
+/// thread whichever thread asks
+pub(crate) fn update_tray(app_handle: &AppHandle) {
+    on_main_thread(app_handle, redraw_tray);
+}
+
+/// `update_tray` for callers already on the main thread, such as tray and
+/// menu event handlers, where the menu has to be current before it opens
+fn redraw_tray(app_handle: &AppHandle) {
+    let Some(tray) = app_handle.tray_by_id("main") else {
+        return;
+    };
+
+    // Rebuild the menu first so the title is only updated alongside it;
+    // on failure both keep showing the previous refresh. Replacing the menu
+    // while it's open closes it, so that waits until it's dismissed.
+    let structural = MENU_GATE.lock().unwrap().allow_structural(CLOCK.instant());
+    if !update_menu_in_place(app_handle, structural) && structural {
+        let rebuilt = rebuild_menu(
+            || menu_from_cache(app_handle),
+            |rendered| {
+                let _ = tray.set_menu(Some(rendered.menu.clone()));
+                keep_rendered(rendered);
+            },
+        );
+        if let Err(e) = rebuilt {
+            eprintln!("Failed to rebuild menu, keeping previous data: {}", e);
+            let _ = tray.set_tooltip(Some(format!("Menu failed to update ({}), showing previous data", e)));
+            return;
+        }
+    }
+    apply_tray_icon(&tray);
+    let _ = tray.set_title(Some(tray_title_from_cache()));
+    let _ = tray.set_tooltip(Some(tray_tooltip_from_cache()));
+    *LAST_TRAY_UPDATE.lock().unwrap() = Some(CLOCK.instant());
+    sync_tokens_tray(app_handle);
+}
+
+/// Id of the optional second menubar item showing the session's tokens
+const TOKENS_TRAY_ID: &str = "tokens";
+
+/// Title of the tokens item: output tokens and cache reads, or a dash when idle
+fn tokens_title_from_cache() -> String {
+    tokens_title(SESSION_CACHE.lock().unwrap().active_block.as_ref(), &current_settings())
+}
+
+fn tokens_title(block: Option<&BlockData>, settings: &AppSettings) -> String {
+    let Some(block) = block else {
+        return "–".to_string();
+    };
+    let tokens = &block.token_counts;
+    render_token_title(
+        tokens.output_tokens,
+        tokens.cache_read_input_tokens,
+        settings.tray_title_glyphs,
+        settings.tray_title_max_width,
+    )
+}
+
+/// The tokens item has no icon and a one-item menu. Its menu events reach the
+/// main tray's handler, which Tauri calls for every menu.
+fn create_tokens_tray(app_handle: &AppHandle) -> tauri::Result<TrayIcon> {
+    let hide = MenuItem::with_id(app_handle, "hide_tokens_tray", "Hide Token Count", true, None::<&str>)?;
+    let menu = Menu::with_items(app_handle, &[&hide])?;
+    TrayIconBuilder::with_id(TOKENS_TRAY_ID)
+        .title(tokens_title_from_cache())
+        .tooltip("Claude Code tokens this session")
+        .menu(&menu)
+        .show_menu_on_left_click(true)
+        .build(app_handle)
+}
+
+/// Create, update or remove the tokens item to match `separate_tokens_tray`
+fn sync_tokens_tray(app_handle: &AppHandle) {
+    let wanted = current_settings().separate_tokens_tray;
+    match app_handle.tray_by_id(TOKENS_TRAY_ID) {
+        Some(tray) if wanted => {
+            let _ = tray.set_title(Some(tokens_title_from_cache()));
+        }
+        Some(_) => {
+            app_handle.remove_tray_by_id(TOKENS_TRAY_ID);
+        }
+        None if wanted => {
+            if let Err(e) = create_tokens_tray(app_handle) {
+                eprintln!("Failed to create tokens menubar item: {}", e);
+            }
+        }
+        None => {}
+    }
+}
+
+/// Note the menu was dismissed, applying any update held back while it was open
+fn menu_closed(app_handle: &AppHandle) {
+    let pending = MENU_GATE.lock().unwrap().closed();
+    if pending {
+        redraw_tray(app_handle);
+    }
+}
+
+/// When the tray was last redrawn, for throttling
+static LAST_TRAY_UPDATE: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Whether a throttled redraw is already waiting to run
+static TRAY_UPDATE_SCHEDULED: AtomicBool = AtomicBool::new(false);
+
+/// How much longer to wait before redrawing, `None` if it can happen now
+fn throttle_delay(last: Option<Instant>, now: Instant, min_interval: Duration) -> Option<Duration> {
+    let elapsed = now.saturating_duration_since(last?);
+    (elapsed < min_interval).then(|| min_interval - elapsed)
+}
+
+/// Redraw the tray for new data, at most once per `tray_update_throttle_ms`.
+/// Updates arriving sooner are coalesced into one redraw at the end of the wait.
+pub(crate) fn update_tray_throttled(app_handle: &AppHandle) {
+    let min_interval = Duration::from_millis(current_settings().tray_update_throttle_ms);
+    let last = *LAST_TRAY_UPDATE.lock().unwrap();
+    let Some(delay) = throttle_delay(last, CLOCK.instant(), min_interval) else {
+        update_tray(app_handle);
+        return;
+    };
+    if TRAY_UPDATE_SCHEDULED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(delay).await;
+        TRAY_UPDATE_SCHEDULED.store(false, Ordering::Relaxed);
+        update_tray(&app_handle);
+    });
+}
+
+/// Create the "main" tray icon showing the cached session. `on_menu_open`
+/// runs as the menu is about to open.
+pub(crate) fn create_tray<F, O>(app_handle: &AppHandle, on_menu_event: F, on_menu_open: O) -> tauri::Result<TrayIcon>
+where
+    F: Fn(&AppHandle, MenuEvent) + Send + Sync + 'static,
+    O: Fn(&AppHandle) + Send + Sync + 'static,
+{
+    let rendered = menu_from_cache(app_handle)?;
+
+    let icon = tray_icon_from_cache();
+    let mut tray_builder = TrayIconBuilder::with_id("main");
+    if is_blank(&icon) {
+        record_icon_result(&Err("the icon has no visible pixels".to_string()));
+    } else {
+        tray_builder = tray_builder.icon(icon);
+    }
+
+    // Template icons are tinted by macOS to match the menubar appearance;
+    // other platforms draw the icon as-is
+    #[cfg(target_os = "macos")]
+    let tray_builder = tray_builder.icon_as_template(true);
+
+    let tray = tray_builder
+        .title(tray_title_from_cache())
+        .tooltip(tray_tooltip_from_cache())
+        .menu(&rendered.menu)
+        .show_menu_on_left_click(true)
+        .on_menu_event(move |app, event| {
+            // Choosing an item dismisses the menu
+            menu_closed(app);
+            on_menu_event(app, event);
+        })
+        .on_tray_icon_event(move |tray, event| {
+            // The menu opens on mouse down; snapshot spend then for "since you last looked".
+            // A click while it's open dismisses it instead.
+            if let TrayIconEvent::Click { button_state: MouseButtonState::Down, .. } = event {
+                let now = CLOCK.instant();
+                let was_open = MENU_GATE.lock().unwrap().is_open(now);
+                if was_open {
+                    menu_closed(tray.app_handle());
+                } else {
+                    record_menu_open(CLOCK.now());
+                    acknowledge_alerts();
+                    redraw_tray(tray.app_handle());
+                    MENU_GATE.lock().unwrap().opened(now);
+                    on_menu_open(tray.app_handle());
+                }
+            }
+        })
+        .build(app_handle)?;
+    keep_rendered(rendered);
+    sync_tokens_tray(app_handle);
+    Ok(tray)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::fixtures::{block, local};
+
+    /// A menu build that fails its first `failures` attempts
+    struct FlakyBuild {
+        failures: usize,
+        attempts: usize,
+    }
+
+    impl FlakyBuild {
+        fn build(&mut self) -> Result<&'static str, String> {
+            self.attempts += 1;
+            if self.attempts <= self.failures {
+                Err(format!("attempt {} failed", self.attempts))
+            } else {
+                Ok("menu")
+            }
+        }
+    }
+
+    #[test]
+    fn menu_build_that_succeeds_is_not_retried() {
+        let mut flaky = FlakyBuild { failures: 0, attempts: 0 };
+        assert_eq!(retry_once(|| flaky.build()), Ok("menu"));
+        assert_eq!(flaky.attempts, 1);
+    }
+
+    #[test]
+    fn menu_build_is_retried_once_after_a_failure() {
+        let mut flaky = FlakyBuild { failures: 1, attempts: 0 };
+        assert_eq!(retry_once(|| flaky.build()), Ok("menu"));
+        assert_eq!(flaky.attempts, 2);
+    }
+
+    #[test]
+    fn menu_build_failing_twice_reports_the_last_error() {
+        let mut flaky = FlakyBuild { failures: 2, attempts: 0 };
+        assert_eq!(retry_once(|| flaky.build()), Err("attempt 2 failed".to_string()));
+        assert_eq!(flaky.attempts, 2);
+    }
+
+    #[test]
+    fn a_rebuild_that_fails_twice_keeps_the_previous_menu() {
+        let mut flaky = FlakyBuild { failures: 2, attempts: 0 };
+        let mut shown = "previous menu";
+        let rebuilt = rebuild_menu(|| flaky.build(), |menu| shown = menu);
+        assert_eq!(rebuilt, Err("attempt 2 failed".to_string()));
+        assert_eq!(shown, "previous menu");
+        assert_eq!(flaky.attempts, 2);
+    }
+
+    #[test]
+    fn a_rebuild_that_fails_once_installs_the_retry() {
+        let mut flaky = FlakyBuild { failures: 1, attempts: 0 };
+        let mut shown = "previous menu";
+        assert_eq!(rebuild_menu(|| flaky.build(), |menu| shown = menu), Ok(()));
+        assert_eq!(shown, "menu");
+        assert_eq!(flaky.attempts, 2);
+    }
+
+    #[test]
+    fn stalled_titles_are_marked() {
+        assert_eq!(stalled_title("$12.40"), "⚠ $12.40");
+        assert_eq!(stalled_title(""), "⚠");
+    }
+
+    #[test]
+    fn titles_without_an_icon_are_labelled() {
+        assert_eq!(text_only_title("$12.40"), "CC $12.40");
+        // Never blank, or the item would vanish from the menubar
+        assert_eq!(text_only_title(""), "CC");
+    }
+
+    #[test]
+    fn title_marks_combine() {
+        let title = || "$12.40".to_string();
+        assert_eq!(marked_title(title(), false, false), "$12.40");
+        assert_eq!(marked_title(title(), true, false), "⚠ $12.40");
+        assert_eq!(marked_title(title(), false, true), "CC $12.40");
+        assert_eq!(marked_title(title(), true, true), "CC ⚠ $12.40");
+        assert_eq!(marked_title(String::new(), false, false), "");
+        assert_eq!(marked_title(String::new(), true, true), "CC ⚠");
+    }
+
+    #[test]
+    fn icon_failures_are_recorded_when_they_change() {
+        assert!(!record_icon_result(&Ok(())));
+        assert!(record_icon_result(&Err("the icon has no visible pixels".to_string())));
+        assert!(tray_icon_failed());
+        assert!(!record_icon_result(&Err("rejected".to_string())));
+        assert!(record_icon_result(&Ok(())));
+        assert!(!tray_icon_failed());
+        assert!(!record_icon_result(&Ok(())));
+    }
+
+    #[test]
+    fn tokens_item_shows_the_sessions_tokens_or_a_dash() {
+        let settings = AppSettings { tray_title_glyphs: true, tray_title_max_width: 20, ..AppSettings::default() };
+        assert_eq!(tokens_title(None, &settings), "–");
+
+        let mut session = block(local(2025, 6, 11, 9, 0), local(2025, 6, 11, 14, 0), 3.0);
+        session.token_counts.output_tokens = 45_200;
+        session.token_counts.cache_read_input_tokens = 1_200_000;
+        assert_eq!(tokens_title(Some(&session), &settings), "45.2K↑ 1.2M⟳");
+        let narrow = AppSettings { tray_title_glyphs: false, tray_title_max_width: 10, ..settings };
+        assert_eq!(tokens_title(Some(&session), &narrow), "45.2K out");
+    }
+}
+
@@ -0,0 +1,152 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+use crate::capability::notify;
+use crate::clock::{Clock, CLOCK};
+use crate::format::format_block_length;
+use crate::state::{current_settings, AppSettings, ON_BATTERY, SESSION_CACHE};
+use crate::tray::update_tray;
+
+/// How often the watchdog looks at when a refresh last succeeded
+pub(crate) const WATCHDOG_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Set once refreshes have been failing for longer than `stall_alert_mins`,
+/// marking the tray title until one succeeds again
+static REFRESH_STALLED: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn refresh_stalled() -> bool {
+    REFRESH_STALLED.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum WatchdogAction {
+    Nothing,
+    /// Notify and mark the title
+    Alert,
+    /// A refresh succeeded again; clear the mark
+    Recover,
+}
+
+/// What the watchdog should do, given how long ago a refresh last succeeded,
+/// whether auto-refresh is meant to be running and whether this stall was
+/// already alerted. It alerts once per stall and only while refreshes are
+/// expected, so pausing doesn't count as failing. A `threshold` of `None`
+/// turns the watchdog off.
+pub(crate) fn watchdog_action(
+    since_success: Duration,
+    expected: bool,
+    alerted: bool,
+    threshold: Option<Duration>,
+) -> WatchdogAction {
+    let stalled = threshold.is_some_and(|threshold| since_success >= threshold);
+    match (stalled, alerted) {
+        (false, true) => WatchdogAction::Recover,
+        (true, false) if expected => WatchdogAction::Alert,
+        _ => WatchdogAction::Nothing,
+    }
+}
+
+/// Whether auto-refresh should be running: not fetching only on open, and
+/// not paused on battery
+fn refreshes_expected(settings: &AppSettings, on_battery: bool) -> bool {
+    let paused_on_battery = on_battery && settings.battery_refresh_secs == 0;
+    !settings.fetch_on_open_only && !paused_on_battery
+}
+
+/// Check for a stall and act on it. `launched` stands in for the last success
+/// until the first refresh succeeds.
+pub(crate) fn run_watchdog_check(app: &AppHandle, launched: Instant) {
+    let settings = current_settings();
+    let last_success = SESSION_CACHE.lock().unwrap().last_success.unwrap_or(launched);
+    let since_success = CLOCK.instant().saturating_duration_since(last_success);
+    let threshold = (settings.stall_alert_mins > 0).then(|| Duration::from_secs(settings.stall_alert_mins * 60));
+    let expected = refreshes_expected(&settings, ON_BATTERY.load(Ordering::Relaxed));
+    match watchdog_action(since_success, expected, refresh_stalled(), threshold) {
+        WatchdogAction::Alert => {
+            REFRESH_STALLED.store(true, Ordering::Relaxed);
+            let since = format_block_length(chrono::Duration::from_std(since_success).unwrap_or_default());
+            eprintln!("No successful refresh in {}", since);
+            notify(app, "CCUsage", format!("CCUsage menubar hasn't updated in {}", since));
+            update_tray(app);
+        }
+        WatchdogAction::Recover => {
+            REFRESH_STALLED.store(false, Ordering::Relaxed);
+            update_tray(app);
+        }
+        WatchdogAction::Nothing => {}
+    }
+}
+
+/// Clear the stall mark as soon as a refresh succeeds, rather than at the next check
+pub(crate) fn clear_refresh_stall() {
+    REFRESH_STALLED.store(false, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    fn minutes(n: u64) -> Duration {
+        Duration::from_secs(n * 60)
+    }
+
+    #[test]
+    fn watchdog_decisions() {
+        use WatchdogAction::*;
+        let cases = [
+            ("recent success", minutes(20), true, false, Some(HOUR), Nothing),
+            ("stalled for the threshold", HOUR, true, false, Some(HOUR), Alert),
+            ("still stalled after alerting", minutes(130), true, true, Some(HOUR), Nothing),
+            ("stalled while paused", minutes(90), false, false, Some(HOUR), Nothing),
+            ("stalled while paused after alerting", minutes(90), false, true, Some(HOUR), Nothing),
+            ("succeeded after alerting", minutes(5), true, true, Some(HOUR), Recover),
+            ("succeeded after alerting, now paused", minutes(5), false, true, Some(HOUR), Recover),
+            ("turned off", minutes(500), true, false, None, Nothing),
+            ("turned off after alerting", minutes(500), true, true, None, Recover),
+        ];
+        for (case, since_success, expected, alerted, threshold, action) in cases {
+            assert_eq!(watchdog_action(since_success, expected, alerted, threshold), action, "{}", case);
+        }
+    }
+
+    #[test]
+    fn a_stall_alerts_once_and_again_after_recovering() {
+        let mut alerted = false;
+        let mut alerts = 0;
+        let mut check = |since_success: Duration| {
+            match watchdog_action(since_success, true, alerted, Some(HOUR)) {
+                WatchdogAction::Alert => {
+                    alerted = true;
+                    alerts += 1;
+                }
+                WatchdogAction::Recover => alerted = false,
+                WatchdogAction::Nothing => {}
+            }
+            alerted
+        };
+        // Checks every ten minutes while refreshes keep failing
+        let marked: Vec<bool> = (1..=9).map(|n| check(WATCHDOG_INTERVAL * n)).collect();
+        assert_eq!(marked, [false, false, false, false, false, true, true, true, true]);
+        // A refresh succeeds, then fails for another hour
+        assert!(!check(minutes(3)));
+        assert!(check(minutes(65)));
+        assert_eq!(alerts, 2);
+    }
+
+    #[test]
+    fn refreshes_are_expected_unless_paused() {
+        let settings = AppSettings { fetch_on_open_only: false, battery_refresh_secs: 300, ..AppSettings::default() };
+        assert!(refreshes_expected(&settings, false));
+        assert!(refreshes_expected(&settings, true));
+
+        let paused_on_battery = AppSettings { battery_refresh_secs: 0, ..settings.clone() };
+        assert!(refreshes_expected(&paused_on_battery, false));
+        assert!(!refreshes_expected(&paused_on_battery, true));
+
+        let on_open_only = AppSettings { fetch_on_open_only: true, ..settings };
+        assert!(!refreshes_expected(&on_open_only, false));
+    }
+}